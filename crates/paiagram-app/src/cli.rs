@@ -0,0 +1,424 @@
+//! # CLI
+//! Commands that run without opening the window, for batch processing.
+
+use bevy::{log::LogPlugin, prelude::*};
+use clap::{Subcommand, ValueEnum};
+use eros::bail;
+use paiagram_core::entry::{EntryEstimate, EntryStop};
+use paiagram_core::export::{ExportObject, gtfs::Gtfs, oudia::OuDia};
+use paiagram_core::graph::find_graph_mismatch;
+use paiagram_core::problems::Problems;
+use paiagram_core::route::Route;
+use paiagram_core::station::ParentStationOrStation;
+use paiagram_core::trip::routing::find_unroutable_legs;
+use paiagram_core::trip::scenario::Scenario;
+use paiagram_core::trip::{Trip, TripSchedule, class};
+use paiagram_core::{entry, graph, import, problems, route, settings, station, trip};
+use paiagram_pdf::PdfOptions;
+use paiagram_rw::save::{LoadCandidate, LoadedScene, SavePlugin, write_compressed_cbor, write_ron};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Upper bound of updates to run after loading a file, in case something keeps changing
+const MAX_SETTLE_UPDATES: usize = 10_000;
+
+/// Exit code of `check` when the file has issues
+const ISSUES_FOUND: i32 = 2;
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Convert a file to another format
+    Convert {
+        /// The file to convert. Any format that can be opened is supported
+        input: PathBuf,
+        /// Where to write the converted file
+        output: PathBuf,
+        /// Format of the converted file, guessed from the extension of the output if not given
+        #[arg(long, value_enum)]
+        to: Option<Format>,
+        /// Name of the route to export, for formats holding a single route
+        #[arg(long)]
+        route: Option<String>,
+        /// Name of the scenario to export to GTFS. All trips are exported if not given
+        #[arg(long)]
+        scenario: Option<String>,
+        /// Fonts to embed in PDF files, tried in order for each text
+        #[arg(long)]
+        font: Vec<PathBuf>,
+    },
+    /// Check a file for issues and print a JSON report
+    Check {
+        /// The file to check. Any format that can be opened is supported
+        input: PathBuf,
+        /// Write the report to this file instead of the standard output
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    /// Paiagram project
+    Paia,
+    /// Paiagram project in RON
+    Ron,
+    /// OuDia, for one route
+    Oud,
+    /// PDF with the diagram and timetables of one route
+    Pdf,
+    /// GTFS feed with the trips of one scenario, in a zip file
+    Gtfs,
+}
+
+impl Format {
+    fn from_extension(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "paia" => Some(Self::Paia),
+            "ron" => Some(Self::Ron),
+            "oud" => Some(Self::Oud),
+            "pdf" => Some(Self::Pdf),
+            "zip" => Some(Self::Gtfs),
+            _ => None,
+        }
+    }
+}
+
+/// Run the command and return the exit code.
+pub fn run(command: Command) -> i32 {
+    let result = match command {
+        Command::Convert {
+            input,
+            output,
+            to,
+            route,
+            scenario,
+            font,
+        } => convert(
+            &input,
+            &output,
+            to,
+            route.as_deref(),
+            scenario.as_deref(),
+            &font,
+        )
+        .map(|()| 0),
+        Command::Check { input, output } => check(&input, output.as_deref())
+            .map(|report| if report.is_clean() { 0 } else { ISSUES_FOUND }),
+    };
+    match result {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("{e:?}");
+            1
+        }
+    }
+}
+
+/// A Bevy app with the core plugins and without the user interface
+fn headless_app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins);
+    app.add_plugins(LogPlugin::default());
+    app.add_plugins((
+        entry::EntryPlugin,
+        graph::GraphPlugin,
+        route::RoutePlugin,
+        import::ImportPlugin,
+        trip::TripPlugin,
+        station::StationPlugin,
+        settings::SettingsPlugin,
+        problems::ProblemsPlugin,
+        class::ClassPlugin,
+        SavePlugin,
+        bevy::asset::AssetPlugin::default(),
+        bevy::scene::ScenePlugin,
+    ));
+    app.add_systems(
+        Update,
+        paiagram_ui::save::apply_loaded_scene.run_if(resource_exists::<LoadedScene>),
+    );
+    app
+}
+
+/// Load the file into the app, and let the app compute routes and estimates.
+fn load(app: &mut App, path: &PathBuf) -> eros::Result<()> {
+    let content = std::fs::read(path)?;
+    let world = app.world_mut();
+    import::load_and_trigger(path, content, &mut world.commands())?;
+    world.flush();
+    settle(app);
+    Ok(())
+}
+
+/// Whether the app still has work to do after a change: a scene waiting to be loaded, or
+/// background tasks that have not finished yet.
+fn is_pending(world: &World) -> bool {
+    world.contains_resource::<LoadCandidate>()
+        || world.contains_resource::<LoadedScene>()
+        || trip::is_indexing(world)
+        || problems::is_checking(world)
+}
+
+/// Update the app until routes, estimates, the trip index and problems are up to date.
+/// Changes made in one update are only picked up in the next one, so this stops after an update
+/// that started and ended with nothing pending.
+fn settle(app: &mut App) {
+    for _ in 0..MAX_SETTLE_UPDATES {
+        let was_pending = is_pending(app.world());
+        app.update();
+        if !was_pending && !is_pending(app.world()) {
+            return;
+        }
+        // let the background tasks run
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    warn!("The file did not settle after {MAX_SETTLE_UPDATES} updates");
+}
+
+fn convert(
+    input: &PathBuf,
+    output: &Path,
+    to: Option<Format>,
+    route: Option<&str>,
+    scenario: Option<&str>,
+    fonts: &[PathBuf],
+) -> eros::Result<()> {
+    let Some(format) = to.or_else(|| Format::from_extension(output)) else {
+        bail!("Unknown output format for {:?}, set it with --to", output);
+    };
+    let mut app = headless_app();
+    load(&mut app, input)?;
+    let world = app.world_mut();
+    match format {
+        Format::Paia | Format::Ron => {
+            let scene = paiagram_ui::save::scene(world);
+            let registry = world.resource::<AppTypeRegistry>().read();
+            let mut writer = BufWriter::new(File::create(output)?);
+            if format == Format::Paia {
+                write_compressed_cbor(&scene, &registry, &mut writer)?;
+            } else {
+                write_ron(&scene, &registry, &mut writer)?;
+            }
+            writer.flush()?;
+        }
+        Format::Oud => {
+            let route_entity = find_route(world, route)?;
+            OuDia {
+                route_entity,
+                world,
+            }
+            .export_to_path(output)?;
+        }
+        Format::Pdf => {
+            let route_entity = find_route(world, route)?;
+            let mut options = PdfOptions {
+                fonts: fonts.iter().map(std::fs::read).collect::<Result<_, _>>()?,
+                ..default()
+            };
+            // the font the user interface uses for Chinese and Japanese text, if it is installed
+            options.fonts.extend(paiagram_ui::pdf_fonts());
+            std::fs::write(output, paiagram_pdf::render(world, route_entity, &options)?)?;
+        }
+        Format::Gtfs => {
+            let scenario = find_scenario(world, scenario)?;
+            Gtfs { world, scenario }.export_to_path(output)?;
+        }
+    }
+    info!("Converted {:?} to {:?}", input, output);
+    Ok(())
+}
+
+/// The route with the name, or the only route if no name is given.
+fn find_route(world: &mut World, name: Option<&str>) -> eros::Result<Entity> {
+    let routes: Vec<(Entity, String)> = world
+        .query_filtered::<(Entity, &Name), With<Route>>()
+        .iter(world)
+        .map(|(entity, name)| (entity, name.to_string()))
+        .collect();
+    let names = routes
+        .iter()
+        .map(|(_, it)| it.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    match (name, routes.as_slice()) {
+        (Some(name), _) => match routes.iter().find(|(_, it)| it == name) {
+            Some((entity, _)) => Ok(*entity),
+            None => bail!("No route is named {:?}. Routes: {}", name, names),
+        },
+        (None, [(entity, _)]) => Ok(*entity),
+        (None, []) => bail!("There are no routes to export"),
+        (None, _) => bail!("Pick a route with --route. Routes: {}", names),
+    }
+}
+
+/// The scenario with the name, or none if no name is given.
+fn find_scenario(world: &mut World, name: Option<&str>) -> eros::Result<Option<Entity>> {
+    let Some(name) = name else {
+        return Ok(None);
+    };
+    let scenarios: Vec<(Entity, String)> = world
+        .query_filtered::<(Entity, &Name), With<Scenario>>()
+        .iter(world)
+        .map(|(entity, name)| (entity, name.to_string()))
+        .collect();
+    match scenarios.iter().find(|(_, it)| it == name) {
+        Some((entity, _)) => Ok(Some(*entity)),
+        None => bail!(
+            "No scenario is named {:?}. Scenarios: {}",
+            name,
+            scenarios
+                .iter()
+                .map(|(_, it)| it.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Issues found by `check`. Entities are described by their names.
+#[derive(Serialize)]
+struct CheckReport {
+    /// Consecutive stops of a trip with no route between them in the graph
+    unroutable: Vec<Segment>,
+    /// Trips with stops that have no estimated times
+    missing_estimates: Vec<MissingEstimates>,
+    graph: GraphMismatch,
+    problems: Vec<ProblemReport>,
+}
+
+/// The part of a trip from one stop to another
+#[derive(Serialize)]
+struct Segment {
+    trip: String,
+    from: String,
+    to: String,
+}
+
+#[derive(Serialize)]
+struct MissingEstimates {
+    trip: String,
+    stops: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct GraphMismatch {
+    stations_not_in_graph: Vec<String>,
+    stations_only_in_graph: Vec<String>,
+    intervals_not_in_graph: Vec<String>,
+    intervals_only_in_graph: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct ProblemReport {
+    kind: String,
+    time: String,
+    segments: Vec<Segment>,
+}
+
+impl CheckReport {
+    fn is_clean(&self) -> bool {
+        self.unroutable.is_empty()
+            && self.missing_estimates.is_empty()
+            && self.graph.stations_not_in_graph.is_empty()
+            && self.graph.stations_only_in_graph.is_empty()
+            && self.graph.intervals_not_in_graph.is_empty()
+            && self.graph.intervals_only_in_graph.is_empty()
+            && self.problems.is_empty()
+    }
+}
+
+fn check(input: &PathBuf, output: Option<&Path>) -> eros::Result<CheckReport> {
+    let mut app = headless_app();
+    load(&mut app, input)?;
+    let world = app.world_mut();
+
+    let mut station_q = world.query::<ParentStationOrStation>();
+    let describe = |world: &World, entity: Entity| match world.get::<Name>(entity) {
+        Some(name) => name.to_string(),
+        None => format!("<??> ({})", entity.index()),
+    };
+    let mut describe_stop = |world: &World, entry: Entity| {
+        let station = world
+            .get::<EntryStop>(entry)
+            .and_then(|stop| station_q.get(world, stop.entity()).ok())
+            .map(|it| it.parent());
+        station.map_or_else(|| describe(world, entry), |it| describe(world, it))
+    };
+
+    let unroutable = world
+        .run_system_cached(find_unroutable_legs)
+        .unwrap()
+        .into_iter()
+        .map(|it| Segment {
+            trip: describe(world, it.trip),
+            from: describe_stop(world, it.from),
+            to: describe_stop(world, it.to),
+        })
+        .collect();
+
+    let mut trip_q = world.query_filtered::<(Entity, &TripSchedule), With<Trip>>();
+    let missing_estimates = trip_q
+        .iter(world)
+        .filter_map(|(trip, schedule)| {
+            let stops: Vec<String> = schedule
+                .iter()
+                .filter(|it| world.get::<EntryEstimate>(**it).is_none())
+                .map(|it| describe_stop(world, *it))
+                .collect();
+            (!stops.is_empty()).then(|| MissingEstimates {
+                trip: describe(world, trip),
+                stops,
+            })
+        })
+        .collect();
+
+    let mismatch = world.run_system_cached(find_graph_mismatch).unwrap();
+    let describe_all = |entities: &[Entity]| -> Vec<String> {
+        entities.iter().map(|it| describe(world, *it)).collect()
+    };
+    let graph = GraphMismatch {
+        stations_not_in_graph: describe_all(&mismatch.stations_not_in_graph),
+        stations_only_in_graph: describe_all(&mismatch.stations_only_in_graph),
+        intervals_not_in_graph: describe_all(&mismatch.intervals_not_in_graph),
+        intervals_only_in_graph: describe_all(&mismatch.intervals_only_in_graph),
+    };
+
+    let problems = world
+        .resource::<Problems>()
+        .iter()
+        .map(|problem| ProblemReport {
+            kind: problem.kind.to_string(),
+            time: problem.time.to_string(),
+            segments: problem
+                .segments
+                .iter()
+                .map(|it| Segment {
+                    trip: describe(world, it.trip),
+                    from: describe_stop(world, it.from),
+                    to: describe_stop(world, it.to),
+                })
+                .collect(),
+        })
+        .collect();
+
+    let report = CheckReport {
+        unroutable,
+        missing_estimates,
+        graph,
+        problems,
+    };
+    match output {
+        Some(path) => {
+            let mut writer = BufWriter::new(File::create(path)?);
+            serde_json::to_writer_pretty(&mut writer, &report)?;
+            writer.flush()?;
+        }
+        None => {
+            serde_json::to_writer_pretty(std::io::stdout().lock(), &report)?;
+            println!();
+        }
+    }
+    Ok(report)
+}
@@ -7,27 +7,37 @@ use paiagram_rw::{read::ReadPlugin, save::SavePlugin};
 use serde::Deserialize;
 use std::path::PathBuf;
 
+#[cfg(not(target_arch = "wasm32"))]
+mod cli;
+
 struct PaiagramApp {
     bevy_app: App,
 }
 
 impl PaiagramApp {
-    fn new(cc: &eframe::CreationContext) -> Self {
+    fn new(cc: &eframe::CreationContext, args: Arguments) -> Self {
         cc.egui_ctx.global_style_mut(|style| {
             style.spacing.window_margin = egui::Margin::same(2);
             style.interaction.selectable_labels = false;
         });
         paiagram_ui::apply_custom_fonts(&cc.egui_ctx);
+        // Without a target format, the diagram and the map are drawn on the CPU
+        let gpu_drawing = cc
+            .wgpu_render_state
+            .as_ref()
+            .is_some_and(|it| is_suitable_adapter(&it.adapter));
         if let Some(render_state) = cc.wgpu_render_state.as_ref() {
             cc.egui_ctx.data_mut(|data| {
                 data.insert_temp(
                     egui::Id::new("wgpu_adapter_info"),
                     eframe::egui_wgpu::adapter_info_summary(&render_state.adapter.get_info()),
                 );
-                data.insert_temp(
-                    egui::Id::new("wgpu_target_format"),
-                    render_state.target_format,
-                );
+                if gpu_drawing {
+                    data.insert_temp(
+                        egui::Id::new("wgpu_target_format"),
+                        render_state.target_format,
+                    );
+                }
                 let msaa_samples = if cfg!(target_arch = "wasm32") {
                     1_u32
                 } else {
@@ -56,10 +66,9 @@ impl PaiagramApp {
             bevy::scene::ScenePlugin,
         ));
         info!("Initialized Bevy App.");
-        #[cfg(not(target_arch = "wasm32"))]
-        let args = Arguments::parse();
-        #[cfg(target_arch = "wasm32")]
-        let args = parse_web_arguments();
+        if !gpu_drawing {
+            info!("No suitable GPU adapter, drawing the diagram and the map on the CPU.");
+        }
         if let Err(e) = app.world_mut().run_system_once_with(handle_args, args) {
             error!("Failed to web arguments: {:?}", e);
         } else {
@@ -69,6 +78,17 @@ impl PaiagramApp {
     }
 }
 
+/// Whether the adapter can run the compute shaders of the diagram at a usable speed. Software
+/// adapters, like those of virtual machines without a GPU, are not.
+fn is_suitable_adapter(adapter: &eframe::egui_wgpu::wgpu::Adapter) -> bool {
+    use eframe::egui_wgpu::wgpu::{DeviceType, DownlevelFlags};
+    adapter.get_info().device_type != DeviceType::Cpu
+        && adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(DownlevelFlags::COMPUTE_SHADERS)
+}
+
 #[cfg(target_arch = "wasm32")]
 fn parse_web_arguments() -> Arguments {
     if let Some(search) =
@@ -99,6 +119,10 @@ impl eframe::App for PaiagramApp {
 #[derive(Parser, Default, Deserialize)]
 #[command(version, about, long_about = None)]
 struct Arguments {
+    #[cfg(not(target_arch = "wasm32"))]
+    #[command(subcommand)]
+    #[serde(skip)]
+    command: Option<cli::Command>,
     #[arg(
         short = 'o',
         long = "open",
@@ -153,12 +177,16 @@ fn handle_args(
 #[cfg(not(target_arch = "wasm32"))]
 fn main() -> eframe::Result<()> {
     i18n::init();
-    let native_options = eframe::NativeOptions {
+    let mut args = Arguments::parse();
+    if let Some(command) = args.command.take() {
+        std::process::exit(cli::run(command));
+    }
+    let native_options = |renderer| eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_title("Drawer")
             .with_app_id("Paiagram")
             .with_inner_size([1280.0, 720.0]),
-        renderer: eframe::Renderer::Wgpu,
+        renderer,
         wgpu_options: eframe::egui_wgpu::WgpuConfiguration {
             desired_maximum_frame_latency: Some(2),
             ..default()
@@ -166,11 +194,35 @@ fn main() -> eframe::Result<()> {
         multisampling: 4,
         ..default()
     };
-    eframe::run_native(
+    // the arguments are taken by whichever renderer starts
+    let args = std::cell::Cell::new(Some(args));
+    let result = eframe::run_native(
         "Paiagram Drawer",
-        native_options,
-        Box::new(|cc| Ok(Box::new(PaiagramApp::new(cc)))),
-    )
+        native_options(eframe::Renderer::Wgpu),
+        Box::new(|cc| {
+            Ok(Box::new(PaiagramApp::new(
+                cc,
+                args.take().unwrap_or_default(),
+            )))
+        }),
+    );
+    match result {
+        // without any wgpu adapter, fall back to OpenGL and draw on the CPU
+        Err(eframe::Error::Wgpu(e)) => {
+            warn!("Failed to start with wgpu, falling back to OpenGL: {e}");
+            eframe::run_native(
+                "Paiagram Drawer",
+                native_options(eframe::Renderer::Glow),
+                Box::new(|cc| {
+                    Ok(Box::new(PaiagramApp::new(
+                        cc,
+                        args.take().unwrap_or_default(),
+                    )))
+                }),
+            )
+        }
+        result => result,
+    }
 }
 
 #[cfg(target_arch = "wasm32")]
@@ -235,7 +287,7 @@ fn main() {
             .start(
                 canvas,
                 web_options,
-                Box::new(|cc| Ok(Box::new(PaiagramApp::new(cc)))),
+                Box::new(|cc| Ok(Box::new(PaiagramApp::new(cc, parse_web_arguments())))),
             )
             .await;
 
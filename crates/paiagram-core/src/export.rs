@@ -1,6 +1,9 @@
 use bevy::prelude::*;
+use std::borrow::Cow;
 
+pub mod diagram;
 pub mod graphviz;
+pub mod gtfs;
 pub mod oudia;
 // pub mod typst_timetable;
 
@@ -16,6 +19,12 @@ pub trait ExportObject {
         filename.push_str(self.extension().as_ref());
         paiagram_rw::write::write_file(filename, move |writer| writer.write_all(&buffer));
     }
+    /// Export contents to the path without asking the user
+    fn export_to_path(&mut self, path: &std::path::Path) -> std::io::Result<()> {
+        let mut buffer = Vec::new();
+        self.export_to_buffer(&mut buffer);
+        std::fs::write(path, buffer)
+    }
     /// the filename
     fn filename(&self) -> impl AsRef<str> {
         "exported_file"
@@ -23,3 +32,12 @@ pub trait ExportObject {
     /// The extension name with the dot
     fn extension(&self) -> impl AsRef<str>;
 }
+
+/// Quote a CSV field if it contains commas, quotes, or line breaks.
+pub fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
@@ -0,0 +1,334 @@
+//! # Diagram
+//! Trips of a route prepared for exported time–distance diagrams.
+
+use crate::entry::{EntryEstimate, EntryQuery};
+use crate::route::{Route, RouteTrips};
+use crate::station::ParentStationOrStation;
+use crate::trip::TripQuery;
+use crate::trip::calendar::ServiceDate;
+use crate::trip::class::DisplayedStroke;
+use crate::units::time::{Duration, TimetableTime};
+use bevy::{ecs::entity::EntityHashMap, prelude::*};
+use smallvec::SmallVec;
+use std::ops::Range;
+
+/// A stop of a trip on a diagram
+#[derive(Debug, Clone, Copy)]
+pub struct DiagramStop {
+    /// Index into the stations of the route
+    pub station_index: usize,
+    pub arr: TimetableTime,
+    pub dep: TimetableTime,
+    pub entry: Entity,
+}
+
+/// A trip drawn on a diagram
+#[derive(Debug, Clone)]
+pub struct DiagramTrip {
+    pub name: String,
+    /// Unmultiplied RGBA
+    pub color: [u8; 4],
+    pub width: f32,
+    /// Parts of the trip drawn as a single line, for every run of the trip
+    pub segments: Vec<Vec<DiagramStop>>,
+}
+
+/// An entry of a trip to split into segments
+#[derive(Clone, Copy)]
+pub struct SegmentEntry {
+    pub entry: Entity,
+    /// The station of the entry's stop
+    pub station: Entity,
+    pub estimate: Option<EntryEstimate>,
+}
+
+/// Splits trips into parts drawn as a single line along the stations of a route. A trip is split
+/// where it leaves the route or its times are unknown. A trip stopping at a station listed more
+/// than once on the route is drawn at each of them.
+pub struct SegmentSplitter {
+    stations: Vec<Entity>,
+    indices: EntityHashMap<SmallVec<[usize; 2]>>,
+}
+
+impl SegmentSplitter {
+    pub fn new(stations: impl IntoIterator<Item = Entity>) -> Self {
+        let stations: Vec<Entity> = stations.into_iter().collect();
+        let mut indices: EntityHashMap<SmallVec<[usize; 2]>> = EntityHashMap::default();
+        for (index, station) in stations.iter().enumerate() {
+            indices.entry(*station).or_default().push(index);
+        }
+        Self { stations, indices }
+    }
+
+    /// Split the entries of a trip. Segments have at least two stops.
+    pub fn split(&self, entries: &[SegmentEntry]) -> Vec<Vec<DiagramStop>> {
+        let mut segments: Vec<Vec<DiagramStop>> = Vec::new();
+        // segments continuing to the current entry
+        let mut open: Vec<Vec<DiagramStop>> = Vec::new();
+        for (position, current) in entries.iter().enumerate() {
+            let next = entries.get(position + 1);
+            let (Some(estimate), Some(indices)) =
+                (current.estimate, self.indices.get(&current.station))
+            else {
+                segments.append(&mut open);
+                continue;
+            };
+            let mut next_open = Vec::new();
+            for index in indices.iter().copied() {
+                let matched = open.iter().position(|it| {
+                    it.last()
+                        .is_some_and(|last| index.abs_diff(last.station_index) <= 1)
+                });
+                let mut segment = match matched {
+                    Some(matched) => open.swap_remove(matched),
+                    None => Vec::new(),
+                };
+                segment.push(DiagramStop {
+                    station_index: index,
+                    arr: estimate.arr,
+                    dep: estimate.dep,
+                    entry: current.entry,
+                });
+                // continue the line only to stations next to this one
+                let continues = next.is_some_and(|next| {
+                    [index.wrapping_sub(1), index, index + 1]
+                        .into_iter()
+                        .any(|it| self.stations.get(it) == Some(&next.station))
+                });
+                if continues {
+                    next_open.push(segment);
+                } else {
+                    segments.push(segment);
+                }
+            }
+            segments.append(&mut open);
+            open = next_open;
+        }
+        segments.append(&mut open);
+        segments.retain(|it| it.len() >= 2);
+        segments
+    }
+}
+
+/// The trips of the route running on the service day, in the scenario if one is given, split
+/// into segments.
+pub fn route_segments(
+    (In(route_entity), In(service_day), In(scenario)): (
+        In<Entity>,
+        In<Option<ServiceDate>>,
+        In<Option<Entity>>,
+    ),
+    route_q: Query<(&Route, &RouteTrips)>,
+    trip_q: Query<TripQuery>,
+    entry_q: Query<EntryQuery>,
+    parent_station_or_station: Query<ParentStationOrStation>,
+) -> Vec<(Entity, Vec<Vec<DiagramStop>>)> {
+    let Ok((route, route_trips)) = route_q.get(route_entity) else {
+        return Vec::new();
+    };
+    let splitter = SegmentSplitter::new(route.stops.iter().copied());
+    trip_q
+        .iter_many(route_trips.iter())
+        .filter(|trip| trip.runs_on(service_day) && trip.is_in_scenario(scenario))
+        .map(|trip| {
+            let entries: Vec<SegmentEntry> = entry_q
+                .iter_many(trip.schedule.iter())
+                .map(|entry| SegmentEntry {
+                    entry: entry.entity,
+                    // stops that are not stations are not on the route
+                    station: parent_station_or_station
+                        .get(entry.stop())
+                        .map_or(entry.stop(), |it| it.parent()),
+                    estimate: entry.estimate.copied(),
+                })
+                .collect();
+            (trip.entity, splitter.split(&entries))
+        })
+        .collect()
+}
+
+/// The trips of the route running on the service day, in the scenario if one is given, as drawn
+/// on exported diagrams.
+pub fn exported_trips(
+    world: &mut World,
+    route_entity: Entity,
+    service_day: Option<ServiceDate>,
+    scenario: Option<Entity>,
+) -> Vec<DiagramTrip> {
+    let segments = world
+        .run_system_cached_with(route_segments, (route_entity, service_day, scenario))
+        .unwrap();
+    world
+        .run_system_cached_with(diagram_trips, segments)
+        .unwrap()
+}
+
+/// Give each trip its name and stroke, and repeat its segments for every run of frequency-based
+/// trips. Trips are sorted by name.
+pub fn diagram_trips(
+    In(segments): In<Vec<(Entity, Vec<Vec<DiagramStop>>)>>,
+    trip_q: Query<TripQuery>,
+    estimate_q: Query<&EntryEstimate>,
+    stroke_q: Query<&DisplayedStroke>,
+) -> Vec<DiagramTrip> {
+    let mut trips: Vec<DiagramTrip> = segments
+        .into_iter()
+        .filter_map(|(trip, segments)| {
+            let trip = trip_q.get(trip).ok()?;
+            let stroke = stroke_q.get(trip.class.entity()).map_or_else(
+                |_| DisplayedStroke::neutral(false),
+                |it| it.egui_stroke(false),
+            );
+            let segments = trip
+                .run_offsets(&estimate_q)
+                .into_iter()
+                .flat_map(|offset| {
+                    segments.iter().map(move |segment| {
+                        segment
+                            .iter()
+                            .map(|stop| DiagramStop {
+                                arr: stop.arr + offset,
+                                dep: stop.dep + offset,
+                                ..*stop
+                            })
+                            .collect()
+                    })
+                })
+                .collect();
+            Some(DiagramTrip {
+                name: trip.name.to_string(),
+                color: stroke.color.to_srgba_unmultiplied(),
+                width: stroke.width,
+                segments,
+            })
+        })
+        .collect();
+    trips.sort_by(|a, b| a.name.cmp(&b.name));
+    trips
+}
+
+/// Offsets of the copies of the segment, repeated every `repeat`, that are drawn within the
+/// times. Exported diagrams draw these copies the same way with the project's repeat frequency.
+/// Only the segment itself is drawn if `repeat` is not positive.
+pub fn repeat_offsets(
+    segment: &[DiagramStop],
+    repeat: Duration,
+    times: Range<TimetableTime>,
+) -> Vec<Duration> {
+    let (Some(first), Some(last)) = (segment.first(), segment.last()) else {
+        return Vec::new();
+    };
+    let (start, end) = (times.start.0, times.end.0);
+    let copies = if repeat.0 > 0 {
+        (start - last.dep.0).div_euclid(repeat.0)..=(end - first.arr.0).div_euclid(repeat.0)
+    } else {
+        0..=0
+    };
+    copies
+        .map(|it| Duration(it * repeat.0))
+        .filter(|it| first.arr.0 + it.0 <= end && last.dep.0 + it.0 >= start)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Split a trip stopping at the given stations, with times unless `None`, and list the
+    /// station indices of each segment.
+    fn split(route: &[Entity], stops: &[(Entity, Option<i32>)]) -> Vec<Vec<usize>> {
+        let mut world = World::new();
+        let entries: Vec<SegmentEntry> = stops
+            .iter()
+            .map(|(station, time)| SegmentEntry {
+                entry: world.spawn_empty().id(),
+                station: *station,
+                estimate: time.map(|it| {
+                    EntryEstimate::new(TimetableTime(it * 60), TimetableTime(it * 60 + 30))
+                }),
+            })
+            .collect();
+        let mut segments: Vec<Vec<usize>> = SegmentSplitter::new(route.iter().copied())
+            .split(&entries)
+            .into_iter()
+            .map(|segment| segment.iter().map(|it| it.station_index).collect())
+            .collect();
+        segments.sort();
+        segments
+    }
+
+    fn stations(count: usize) -> Vec<Entity> {
+        let mut world = World::new();
+        (0..count).map(|_| world.spawn_empty().id()).collect()
+    }
+
+    #[test]
+    fn follows_the_route() {
+        let s = stations(3);
+        let stops = [(s[0], Some(0)), (s[1], Some(5)), (s[2], Some(10))];
+        assert_eq!(split(&s, &stops), vec![vec![0, 1, 2]]);
+        let reversed = [(s[2], Some(0)), (s[1], Some(5)), (s[0], Some(10))];
+        assert_eq!(split(&s, &reversed), vec![vec![2, 1, 0]]);
+    }
+
+    #[test]
+    fn splits_at_unknown_times() {
+        let s = stations(5);
+        let stops = [
+            (s[0], Some(0)),
+            (s[1], Some(5)),
+            (s[2], None),
+            (s[3], Some(15)),
+            (s[4], Some(20)),
+        ];
+        assert_eq!(split(&s, &stops), vec![vec![0, 1], vec![3, 4]]);
+    }
+
+    #[test]
+    fn splits_where_the_trip_leaves_the_route() {
+        let s = stations(5);
+        let stops = [
+            (s[0], Some(0)),
+            (s[1], Some(5)),
+            (s[4], Some(10)),
+            (s[2], Some(15)),
+            (s[3], Some(20)),
+        ];
+        assert_eq!(split(&s[..4], &stops), vec![vec![0, 1], vec![2, 3]]);
+    }
+
+    #[test]
+    fn station_listed_twice() {
+        let s = stations(4);
+        // a branch drawn on both sides of the junction: A, B, C, B, D
+        let route = [s[0], s[1], s[2], s[1], s[3]];
+        let stops = [(s[0], Some(0)), (s[1], Some(5)), (s[3], Some(10))];
+        assert_eq!(split(&route, &stops), vec![vec![0, 1], vec![3, 4]]);
+        let stops = [(s[0], Some(0)), (s[1], Some(5)), (s[2], Some(10))];
+        assert_eq!(split(&route, &stops), vec![vec![0, 1, 2]]);
+    }
+
+    #[test]
+    fn repeated_copies_within_the_times() {
+        let stop = |station_index, time| DiagramStop {
+            station_index,
+            arr: TimetableTime(time),
+            dep: TimetableTime(time),
+            entry: Entity::PLACEHOLDER,
+        };
+        // 23:00 to 01:00 of the next day
+        let segment = [stop(0, 82800), stop(1, 90000)];
+        let day = TimetableTime(0)..TimetableTime(86400);
+        assert_eq!(
+            repeat_offsets(&segment, Duration(86400), day.clone()),
+            vec![Duration(-86400), Duration(0)]
+        );
+        assert_eq!(
+            repeat_offsets(&segment, Duration(0), day),
+            vec![Duration(0)]
+        );
+        let morning = TimetableTime(21600)..TimetableTime(43200);
+        assert!(repeat_offsets(&segment, Duration(86400), morning.clone()).is_empty());
+        assert!(repeat_offsets(&segment, Duration(0), morning).is_empty());
+    }
+}
@@ -0,0 +1,471 @@
+//! # GTFS
+//! Export of the project as a static GTFS feed. Stations and platforms become stops, classes
+//! become routes, and the estimated times of trips become stop times. Vehicles are written as
+//! blocks, and frequency-based trips keep their template times with `frequencies.txt`. Scenarios
+//! are alternatives to each other, so only the trips of one of them are exported.
+
+use super::csv_field;
+use crate::entry::EntryQuery;
+use crate::graph::Node;
+use crate::settings::ProjectSettings;
+use crate::station::{Platform, Platforms, Station};
+use crate::trip::TripQuery;
+use crate::trip::calendar::{DateRange, ServiceCalendar, ServiceDate};
+use crate::trip::class::{Class, DisplayedStroke};
+use crate::trip::scenario::Scenario;
+use crate::units::time::{Duration, TimetableTime};
+use bevy::ecs::entity::EntityHashSet;
+use bevy::prelude::*;
+use std::io::Write;
+
+/// GTFS `route_type` of rail services
+const ROUTE_TYPE_RAIL: &str = "2";
+
+/// `agency_url` written if the project does not set one
+pub const DEFAULT_AGENCY_URL: &str = "https://paiagram.com";
+
+pub struct Gtfs<'a> {
+    pub world: &'a mut World,
+    /// Export only the trips of this scenario, or all trips if `None`
+    pub scenario: Option<Entity>,
+}
+
+impl<'a> super::ExportObject for Gtfs<'a> {
+    fn export_to_buffer(&mut self, buffer: &mut Vec<u8>) {
+        let tables = match self.world.run_system_cached_with(make_feed, self.scenario) {
+            Ok(it) => it,
+            Err(e) => {
+                error!("Failed to make the GTFS feed: {e}");
+                return;
+            }
+        };
+        if let Err(e) = write_zip(&tables, buffer) {
+            error!("Failed to write the GTFS feed: {e}");
+        }
+    }
+    fn extension(&self) -> impl AsRef<str> {
+        ".zip"
+    }
+    fn filename(&self) -> impl AsRef<str> {
+        "gtfs"
+    }
+}
+
+/// A file of the feed
+struct Table {
+    name: &'static str,
+    content: String,
+    rows: usize,
+}
+
+impl Table {
+    fn new(name: &'static str, header: &[&str]) -> Self {
+        let mut table = Self {
+            name,
+            content: String::new(),
+            rows: 0,
+        };
+        table.row(header);
+        table.rows = 0;
+        table
+    }
+    fn row(&mut self, fields: &[&str]) {
+        for (index, field) in fields.iter().enumerate() {
+            if index > 0 {
+                self.content.push(',');
+            }
+            self.content.push_str(&csv_field(field));
+        }
+        self.content.push('\n');
+        self.rows += 1;
+    }
+}
+
+fn write_zip(tables: &[Table], buffer: &mut Vec<u8>) -> zip::result::ZipResult<()> {
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(buffer));
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    for table in tables {
+        writer.start_file(table.name, options)?;
+        writer.write_all(table.content.as_bytes())?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// GTFS ids are the entity indices, which are unique among the entities of the world
+fn id(entity: Entity) -> String {
+    entity.index().to_string()
+}
+
+/// Id of the stop standing for a station that has platforms, for entries stopping at the station
+/// itself. Stations with platforms are written as `location_type` 1, which trips cannot stop at.
+fn station_stop_id(station: Entity) -> String {
+    format!("{}_0", id(station))
+}
+
+/// `HH:MM:SS`, with hours past 24 for times after midnight
+fn time(time: TimetableTime) -> String {
+    let seconds = time.seconds();
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn date(date: ServiceDate) -> String {
+    date.to_naive_date()
+        .map_or_else(String::new, |it| it.format("%Y%m%d").to_string())
+}
+
+/// A trip with the stops written to `stop_times.txt`
+struct FeedTrip<'w, 's> {
+    trip: crate::trip::TripQueryItem<'w, 's>,
+    /// Stop, arrival, and departure
+    stops: Vec<(Entity, TimetableTime, TimetableTime)>,
+    /// Whole days added to the times, since GTFS times cannot be negative. The service dates of
+    /// the trip are moved back by as many days.
+    shift: i32,
+}
+
+fn make_feed(
+    In(scenario): In<Option<Entity>>,
+    settings: Res<ProjectSettings>,
+    trip_q: Query<TripQuery>,
+    scenario_q: Query<(), With<Scenario>>,
+    entry_q: Query<EntryQuery>,
+    station_q: Query<(Entity, &Name, &Node, &Platforms), With<Station>>,
+    platform_q: Query<(Entity, &Name, &Node), (With<Platform>, Without<Station>)>,
+    class_q: Query<(Entity, &Name, &DisplayedStroke), With<Class>>,
+    name_q: Query<&Name>,
+) -> Vec<Table> {
+    if scenario.is_none() && !scenario_q.is_empty() {
+        warn!("The trips of every scenario are exported to the same GTFS feed");
+    }
+    let mut missing_estimates = 0;
+    let mut trips: Vec<FeedTrip> = trip_q
+        .iter()
+        .filter(|trip| trip.is_in_scenario(scenario))
+        .filter_map(|trip| {
+            let mut stops = Vec::with_capacity(trip.schedule.len());
+            // derived entries are passed without stopping
+            for entry in entry_q
+                .iter_many(trip.schedule.iter())
+                .filter(|it| it.is_not_derived())
+            {
+                let Some(estimate) = entry.estimate else {
+                    missing_estimates += 1;
+                    continue;
+                };
+                stops.push((entry.stop(), estimate.arr, estimate.dep));
+            }
+            let first = stops.first()?.1;
+            let days = if first.0 < 0 {
+                first.0.div_euclid(86400).abs()
+            } else {
+                0
+            };
+            (stops.len() >= 2).then_some(FeedTrip {
+                trip,
+                stops,
+                shift: days,
+            })
+        })
+        .collect();
+    if missing_estimates > 0 {
+        warn!("{missing_estimates} entries without estimated times are left out of the GTFS feed");
+    }
+    trips.sort_by_key(|it| (it.stops[0].1, it.trip.entity.index()));
+
+    let agency_name = if settings.authors.is_empty() {
+        "Paiagram".to_string()
+    } else {
+        settings.authors.join(", ")
+    };
+    let time_zone = if settings.time_zone.is_empty() {
+        "Etc/UTC"
+    } else {
+        settings.time_zone.as_str()
+    };
+    let mut agency = Table::new(
+        "agency.txt",
+        &["agency_id", "agency_name", "agency_url", "agency_timezone"],
+    );
+    let agency_url = if settings.agency_url.is_empty() {
+        DEFAULT_AGENCY_URL
+    } else {
+        settings.agency_url.as_str()
+    };
+    agency.row(&["1", &agency_name, agency_url, time_zone]);
+
+    // stops
+    let used_stops: EntityHashSet = trips
+        .iter()
+        .flat_map(|it| it.stops.iter().map(|(stop, ..)| *stop))
+        .collect();
+    let mut stations: Vec<_> = station_q.iter().collect();
+    stations.sort_by_key(|(entity, ..)| entity.index());
+    let mut stations_with_platforms = EntityHashSet::default();
+    let mut stops = Table::new(
+        "stops.txt",
+        &[
+            "stop_id",
+            "stop_name",
+            "stop_lat",
+            "stop_lon",
+            "location_type",
+            "parent_station",
+        ],
+    );
+    for (station, name, node, platforms) in stations {
+        let lat = format!("{:.6}", node.coor.lat);
+        let lon = format!("{:.6}", node.coor.lon);
+        let platforms: Vec<_> = platform_q.iter_many(platforms.iter()).collect();
+        if platforms.is_empty() {
+            stops.row(&[&id(station), name, &lat, &lon, "0", ""]);
+            continue;
+        }
+        stations_with_platforms.insert(station);
+        let station_id = id(station);
+        stops.row(&[&station_id, name, &lat, &lon, "1", ""]);
+        for (platform, platform_name, platform_node) in platforms {
+            // platforms without their own position are placed at the station
+            let coor = if platform_node.coor.lat == 0.0 && platform_node.coor.lon == 0.0 {
+                node.coor
+            } else {
+                platform_node.coor
+            };
+            stops.row(&[
+                &id(platform),
+                platform_name,
+                &format!("{:.6}", coor.lat),
+                &format!("{:.6}", coor.lon),
+                "0",
+                &station_id,
+            ]);
+        }
+        if used_stops.contains(&station) {
+            stops.row(&[
+                &station_stop_id(station),
+                name,
+                &lat,
+                &lon,
+                "0",
+                &station_id,
+            ]);
+        }
+    }
+    let stop_id = |stop: Entity| {
+        if stations_with_platforms.contains(&stop) {
+            station_stop_id(stop)
+        } else {
+            id(stop)
+        }
+    };
+
+    // routes, one for each class
+    let mut routes = Table::new(
+        "routes.txt",
+        &[
+            "route_id",
+            "agency_id",
+            "route_short_name",
+            "route_type",
+            "route_color",
+        ],
+    );
+    let mut classes: Vec<_> = class_q.iter().collect();
+    classes.sort_by_key(|(entity, ..)| entity.index());
+    for (class, name, stroke) in classes {
+        let [r, g, b, _] = stroke.egui_stroke(false).color.to_srgba_unmultiplied();
+        routes.row(&[
+            &id(class),
+            "1",
+            name,
+            ROUTE_TYPE_RAIL,
+            &format!("{r:02X}{g:02X}{b:02X}"),
+        ]);
+    }
+
+    // services, shared by trips with equal calendars. Trips moved to the next day run on the
+    // previous service day, hence their calendars are moved back.
+    let mut services: Vec<Option<ServiceCalendar>> = Vec::new();
+    let mut service_ids = Vec::with_capacity(trips.len());
+    for trip in &trips {
+        let calendar = trip.trip.calendar.map(|it| it.shifted(-trip.shift));
+        let index = match services.iter().position(|it| *it == calendar) {
+            Some(index) => index,
+            None => {
+                services.push(calendar);
+                services.len() - 1
+            }
+        };
+        service_ids.push(format!("S{}", index + 1));
+    }
+    let (calendar, calendar_dates) = make_calendars(&services);
+
+    let mut trips_table = Table::new(
+        "trips.txt",
+        &[
+            "route_id",
+            "service_id",
+            "trip_id",
+            "trip_short_name",
+            "trip_headsign",
+            "block_id",
+        ],
+    );
+    let mut stop_times = Table::new(
+        "stop_times.txt",
+        &[
+            "trip_id",
+            "arrival_time",
+            "departure_time",
+            "stop_id",
+            "stop_sequence",
+        ],
+    );
+    let mut frequencies = Table::new(
+        "frequencies.txt",
+        &[
+            "trip_id",
+            "start_time",
+            "end_time",
+            "headway_secs",
+            "exact_times",
+        ],
+    );
+    for (trip, service_id) in trips.iter().zip(&service_ids) {
+        let trip_id = id(trip.trip.entity);
+        let shift = Duration(trip.shift * 86400);
+        let headsign = trip
+            .stops
+            .last()
+            .and_then(|(stop, ..)| name_q.get(*stop).ok())
+            .map_or_else(String::new, Name::to_string);
+        // a trip has one block in GTFS, hence only the first vehicle is written
+        let block_id = trip
+            .trip
+            .vehicles
+            .first()
+            .map_or_else(String::new, |it| id(*it));
+        trips_table.row(&[
+            &id(trip.trip.class.entity()),
+            service_id,
+            &trip_id,
+            trip.trip.name,
+            &headsign,
+            &block_id,
+        ]);
+        for (sequence, (stop, arr, dep)) in trip.stops.iter().enumerate() {
+            stop_times.row(&[
+                &trip_id,
+                &time(*arr + shift),
+                &time(*dep + shift),
+                &stop_id(*stop),
+                &(sequence + 1).to_string(),
+            ]);
+        }
+        // runs follow the template times exactly
+        for period in trip.trip.frequency.iter().flat_map(|it| &it.periods) {
+            frequencies.row(&[
+                &trip_id,
+                &time(period.start + shift),
+                &time(period.end + shift),
+                &period.headway.0.max(0).to_string(),
+                "1",
+            ]);
+        }
+    }
+    let mut tables = vec![agency, stops, routes, trips_table, stop_times, calendar];
+    tables.extend(
+        [calendar_dates, frequencies]
+            .into_iter()
+            .filter(|it| it.rows > 0),
+    );
+    tables
+}
+
+/// Write `calendar.txt` and `calendar_dates.txt`. Services without a calendar run on every day.
+/// GTFS services have a single date range, so the gaps between the ranges of a calendar are
+/// removed date by date.
+fn make_calendars(services: &[Option<ServiceCalendar>]) -> (Table, Table) {
+    // calendars without a date range run within the range of all other calendars
+    let bounds = services
+        .iter()
+        .flatten()
+        .flat_map(|it| {
+            it.ranges
+                .iter()
+                .flat_map(|range| [range.start, range.end])
+                .chain(it.added.iter().copied())
+        })
+        .fold(None, |bounds: Option<DateRange>, it| {
+            Some(
+                bounds.map_or(DateRange { start: it, end: it }, |bounds| DateRange {
+                    start: bounds.start.min(it),
+                    end: bounds.end.max(it),
+                }),
+            )
+        })
+        .unwrap_or_else(|| DateRange {
+            start: "2000-01-01".parse().unwrap(),
+            end: "2099-12-31".parse().unwrap(),
+        });
+    let mut calendar = Table::new(
+        "calendar.txt",
+        &[
+            "service_id",
+            "monday",
+            "tuesday",
+            "wednesday",
+            "thursday",
+            "friday",
+            "saturday",
+            "sunday",
+            "start_date",
+            "end_date",
+        ],
+    );
+    let mut calendar_dates = Table::new(
+        "calendar_dates.txt",
+        &["service_id", "date", "exception_type"],
+    );
+    for (index, service) in services.iter().enumerate() {
+        let service_id = format!("S{}", index + 1);
+        let default = ServiceCalendar::default();
+        let service = service.as_ref().unwrap_or(&default);
+        let start = service.ranges.iter().map(|it| it.start).min();
+        let end = service.ranges.iter().map(|it| it.end).max();
+        let range = start
+            .zip(end)
+            .map_or(bounds, |(start, end)| DateRange { start, end });
+        let mut fields = vec![service_id.clone()];
+        fields.extend((0..7).map(|it| (service.weekdays.contains(it) as u8).to_string()));
+        fields.extend([date(range.start), date(range.end)]);
+        calendar.row(&fields.iter().map(String::as_str).collect::<Vec<_>>());
+        // removed dates win over added dates
+        for added in service
+            .added
+            .iter()
+            .filter(|it| !service.removed.contains(it))
+        {
+            calendar_dates.row(&[&service_id, &date(*added), "1"]);
+        }
+        let gaps = (range.start.0..=range.end.0).map(ServiceDate).filter(|it| {
+            !service.ranges.is_empty()
+                && service.weekdays.contains(it.weekday())
+                && !service.ranges.iter().any(|range| range.contains(*it))
+                && !service.added.contains(it)
+        });
+        let mut removed: Vec<ServiceDate> = gaps.chain(service.removed.iter().copied()).collect();
+        removed.sort_unstable();
+        removed.dedup();
+        for removed in removed {
+            calendar_dates.row(&[&service_id, &date(removed), "2"]);
+        }
+    }
+    (calendar, calendar_dates)
+}
@@ -1,6 +1,6 @@
 use std::borrow::Cow;
 
-use bevy::ecs::entity::EntityHashMap;
+use bevy::ecs::entity::{EntityHashMap, EntityHashSet};
 use bevy::prelude::*;
 use either::Either;
 use encoding_rs::SHIFT_JIS;
@@ -11,6 +11,7 @@ use crate::class::ClassQuery;
 use crate::entry::{EntryQuery, EntryQueryItem, TravelMode};
 use crate::route::{Route, RouteByDirectionTrips};
 use crate::station::{ParentStationOrStation, Station};
+use crate::trip::scenario::Scenario;
 use crate::trip::{TripQuery, TripQueryItem};
 
 fn make_disp_prop() -> Structure<'static> {
@@ -164,29 +165,53 @@ fn make_diagram(
     route_q: Query<(&Route, &RouteByDirectionTrips)>,
     entry_q: Query<EntryQuery>,
     trip_q: Query<TripQuery>,
+    scenario_q: Query<(Entity, &Name), With<Scenario>>,
     parent_station_or_station: Query<ParentStationOrStation>,
 ) {
     // downward: Nobori, Upward: Kudari
     let (route, RouteByDirectionTrips { downward, upward }) = route_q.get(route_entity).unwrap();
-    let mut dia_buf = Vec::new();
-    dia_buf.push(pair!("DiaName" => "Paiagram Exported"));
-    dia_buf.push(make_trainset_by_direction(
-        true,
-        trip_q.iter_many(downward.as_slice()),
-        route.stops.as_slice(),
-        class_map,
-        &entry_q,
-        &parent_station_or_station,
-    ));
-    dia_buf.push(make_trainset_by_direction(
-        false,
-        trip_q.iter_many(upward.as_slice()),
-        route.stops.as_slice(),
-        class_map,
-        &entry_q,
-        &parent_station_or_station,
-    ));
-    buf.push(structure!("Dia" => ..dia_buf));
+    // each scenario with trips on the route is exported as a separate diagram. Trips without a
+    // scenario are collected into an extra diagram.
+    let mut used_scenarios = EntityHashSet::new();
+    let mut has_unassigned = false;
+    for trip in trip_q.iter_many(downward.iter().chain(upward.iter())) {
+        match trip.scenario {
+            Some(scenario) => {
+                used_scenarios.insert(scenario.0);
+            }
+            None => has_unassigned = true,
+        }
+    }
+    let mut diagrams: Vec<(Option<Entity>, String)> = scenario_q
+        .iter()
+        .filter(|(entity, _)| used_scenarios.contains(entity))
+        .map(|(entity, name)| (Some(entity), name.to_string()))
+        .collect();
+    if has_unassigned || diagrams.is_empty() {
+        diagrams.push((None, "Paiagram Exported".to_string()));
+    }
+    for (scenario, name) in diagrams {
+        let in_diagram = |it: &TripQueryItem| it.scenario.map(|scenario| scenario.0) == scenario;
+        let mut dia_buf = Vec::new();
+        dia_buf.push(pair!("DiaName" => name));
+        dia_buf.push(make_trainset_by_direction(
+            true,
+            trip_q.iter_many(downward.as_slice()).filter(in_diagram),
+            route.stops.as_slice(),
+            class_map,
+            &entry_q,
+            &parent_station_or_station,
+        ));
+        dia_buf.push(make_trainset_by_direction(
+            false,
+            trip_q.iter_many(upward.as_slice()).filter(in_diagram),
+            route.stops.as_slice(),
+            class_map,
+            &entry_q,
+            &parent_station_or_station,
+        ));
+        buf.push(structure!("Dia" => ..dia_buf));
+    }
 }
 
 fn make_trainset_by_direction<'a>(
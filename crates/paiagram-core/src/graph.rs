@@ -5,6 +5,7 @@ pub mod arrange;
 use crate::entry::EntryStop;
 use crate::interval::Interval;
 use crate::interval::IntervalQuery;
+use crate::interval::IntervalShape;
 use crate::route::Route;
 use crate::station::Platforms;
 use crate::station::Station;
@@ -365,15 +366,19 @@ fn mark_graph_interval_spatial_index_dirty(
     graph: Res<Graph>,
     changed_nodes: Query<(), Or<(Added<Node>, Changed<Node>)>>,
     changed_intervals: Query<(), Or<(Added<Interval>, Changed<Interval>)>>,
+    changed_shapes: Query<(), Changed<IntervalShape>>,
     mut removed_nodes: RemovedComponents<Node>,
     mut removed_intervals: RemovedComponents<Interval>,
+    mut removed_shapes: RemovedComponents<IntervalShape>,
 ) {
     if graph.is_added()
         || graph.is_changed()
         || !changed_nodes.is_empty()
         || !changed_intervals.is_empty()
+        || !changed_shapes.is_empty()
         || removed_nodes.read().next().is_some()
         || removed_intervals.read().next().is_some()
+        || removed_shapes.read().next().is_some()
     {
         state.dirty = true;
     }
@@ -405,6 +410,7 @@ fn start_graph_interval_spatial_index_rebuild(
     mut state: ResMut<GraphIntervalSpatialIndexState>,
     graph: Res<Graph>,
     nodes: Query<&Node>,
+    shapes: Query<&IntervalShape>,
 ) {
     if !state.dirty || state.task.is_some() {
         return;
@@ -419,11 +425,18 @@ fn start_graph_interval_spatial_index_rebuild(
         let Ok(target_node) = nodes.get(target) else {
             continue;
         };
-        snapshot.push(IntervalSpatialIndexedEntity {
+        // shaped intervals are indexed as one segment between each pair of points
+        let shape = shapes.get(*interval).map_or(&[][..], |it| it.as_slice());
+        let points: Vec<[f64; 2]> = std::iter::once(source_node.coor)
+            .chain(shape.iter().copied())
+            .chain(std::iter::once(target_node.coor))
+            .map(|it| it.to_xy_arr())
+            .collect();
+        snapshot.extend(points.windows(2).map(|pair| IntervalSpatialIndexedEntity {
             interval: *interval,
-            p0: source_node.coor.to_xy_arr(),
-            p1: target_node.coor.to_xy_arr(),
-        });
+            p0: pair[0],
+            p1: pair[1],
+        }));
     }
 
     state.task = Some(AsyncComputeTaskPool::get().spawn(async move { RTree::bulk_load(snapshot) }));
@@ -507,6 +520,16 @@ impl NodeCoor {
         self.lon += dx;
         self.lat += dy;
     }
+    /// The great-circle distance to `other`.
+    pub fn distance(&self, other: &Self) -> Distance {
+        let d_lat = (other.lat - self.lat).to_radians();
+        let d_lon = (other.lon - self.lon).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2)
+            + self.lat.to_radians().cos()
+                * other.lat.to_radians().cos()
+                * (d_lon / 2.0).sin().powi(2);
+        Distance((2.0 * EARTH_RADIUS_METERS * a.sqrt().asin()).round() as i32)
+    }
     /// Linearly interpolates between `self` and `other` by fraction `t`.
     /// `t` is typically between 0.0 and 1.0.
     pub fn lerp(&self, other: &Self, t: f64) -> Self {
@@ -630,6 +653,49 @@ fn debug_graph_set_diff(
     );
 }
 
+/// Stations and intervals that are in the world but not in the graph, or the other way round.
+/// All lists are empty if the graph is consistent.
+#[derive(Debug, Default)]
+pub struct GraphMismatch {
+    pub stations_not_in_graph: Vec<Entity>,
+    pub stations_only_in_graph: Vec<Entity>,
+    pub intervals_not_in_graph: Vec<Entity>,
+    pub intervals_only_in_graph: Vec<Entity>,
+}
+
+impl GraphMismatch {
+    pub fn is_empty(&self) -> bool {
+        self.stations_not_in_graph.is_empty()
+            && self.stations_only_in_graph.is_empty()
+            && self.intervals_not_in_graph.is_empty()
+            && self.intervals_only_in_graph.is_empty()
+    }
+}
+
+/// Compare the graph with the stations and intervals in the world. This is what
+/// `check_stations_in_graph` asserts in debug builds.
+pub fn find_graph_mismatch(
+    graph: Res<Graph>,
+    stations: Query<Entity, With<Station>>,
+    intervals: Query<Entity, With<Interval>>,
+) -> GraphMismatch {
+    let queried_stations: EntityHashSet = stations.iter().collect();
+    let queried_intervals: EntityHashSet = intervals.iter().collect();
+    let graphed_stations: EntityHashSet = graph.nodes().collect();
+    let graphed_intervals: EntityHashSet = graph.all_edges().map(|(_, _, w)| *w).collect();
+    fn sorted<'a>(it: impl Iterator<Item = &'a Entity>) -> Vec<Entity> {
+        let mut out: Vec<Entity> = it.copied().collect();
+        out.sort_unstable();
+        out
+    }
+    GraphMismatch {
+        stations_not_in_graph: sorted(queried_stations.difference(&graphed_stations)),
+        stations_only_in_graph: sorted(graphed_stations.difference(&queried_stations)),
+        intervals_not_in_graph: sorted(queried_intervals.difference(&graphed_intervals)),
+        intervals_only_in_graph: sorted(graphed_intervals.difference(&queried_intervals)),
+    }
+}
+
 // TODO: instead of merging them, make stations platforms instead
 pub fn merge_station_by_name(
     mut commands: Commands,
@@ -717,15 +783,11 @@ pub struct AddIntervalPair {
 
 fn add_interval_pair(msg: On<AddIntervalPair>, mut graph: ResMut<Graph>, mut commands: Commands) {
     if !graph.contains_edge(msg.source, msg.target) {
-        let e1: Instance<Interval> = commands
-            .spawn_instance(Interval { length: msg.length })
-            .into();
+        let e1: Instance<Interval> = commands.spawn_instance(Interval::new(msg.length)).into();
         graph.add_edge(msg.source, msg.target, e1.entity());
     }
     if !graph.contains_edge(msg.target, msg.source) {
-        let e2: Instance<Interval> = commands
-            .spawn_instance(Interval { length: msg.length })
-            .into();
+        let e2: Instance<Interval> = commands.spawn_instance(Interval::new(msg.length)).into();
         graph.add_edge(msg.target, msg.source, e2.entity());
     }
 }
@@ -139,11 +139,11 @@ pub(crate) fn add_interval_pair(
     length: Distance,
 ) {
     if !graph.contains_edge(from, to) {
-        let e1: Instance<Interval> = commands.spawn_instance(Interval { length }).into();
+        let e1: Instance<Interval> = commands.spawn_instance(Interval::new(length)).into();
         graph.add_edge(from, to, e1.entity());
     }
     if !graph.contains_edge(to, from) {
-        let e2: Instance<Interval> = commands.spawn_instance(Interval { length }).into();
+        let e2: Instance<Interval> = commands.spawn_instance(Interval::new(length)).into();
         graph.add_edge(to, from, e2.entity());
     }
 }
@@ -7,13 +7,20 @@ use crate::{
     colors::{DisplayedColor, PredefinedColor},
     entry::{EntryBundle, TravelMode},
     graph::{Graph, Node, NodeCoor},
+    interval::IntervalShape,
     route::Route,
-    station::{Platform, Station},
+    settings::ProjectSettings,
+    station::{Footpath, Footpaths, MinimumTransferTime, Platform, Station},
     trip::{
         TripBundle, TripClass,
+        calendar::{DateRange, ServiceCalendar, ServiceDate, Weekdays},
         class::{Class, ClassBundle, DisplayedStroke},
+        frequency::{FrequencyPeriod, TripFrequency},
+    },
+    units::{
+        distance::Distance,
+        time::{Duration, TimetableTime},
     },
-    units::{distance::Distance, time::TimetableTime},
     vehicle::Vehicle,
 };
 
@@ -60,10 +67,179 @@ fn stop_display_name(stop: &gtfs_structures::Stop) -> String {
     stop.name.clone().unwrap_or_else(|| stop.id.clone())
 }
 
+fn stop_coor(stop: &gtfs_structures::Stop) -> Option<NodeCoor> {
+    let (lat, lon) = stop.latitude.zip(stop.longitude)?;
+    Some(NodeCoor::new(lon, lat))
+}
+
+/// Stations and platforms spawned for GTFS stops, keyed by `stop_id`.
+#[derive(Default)]
+struct StopEntities {
+    stations: HashMap<String, Entity>,
+    platforms: HashMap<String, Entity>,
+}
+
+impl StopEntities {
+    /// The `stop_id` of the station a stop belongs to. Stops with a parent station are platforms
+    /// of that station.
+    fn station_id(stop: &gtfs_structures::Stop) -> &str {
+        match &stop.parent_station {
+            Some(parent_station_id)
+                if !matches!(stop.location_type, gtfs_structures::LocationType::StopArea) =>
+            {
+                parent_station_id
+            }
+            _ => &stop.id,
+        }
+    }
+    /// The station entity of a stop, if it was spawned.
+    fn station(&self, stop: &gtfs_structures::Stop) -> Option<Entity> {
+        self.stations.get(Self::station_id(stop)).copied()
+    }
+    /// The station entity and the stop entity of a stop, spawning both if needed. The stop
+    /// entity is the station itself for stops without a parent station.
+    fn ensure(
+        &mut self,
+        gtfs: &gtfs_structures::Gtfs,
+        stop: &gtfs_structures::Stop,
+        graph: &mut Graph,
+        commands: &mut Commands,
+    ) -> (Entity, Entity) {
+        let station_id = Self::station_id(stop);
+        let station = match self.stations.get(station_id) {
+            Some(&entity) => entity,
+            None => {
+                // stations without a position of their own are placed at their first platform
+                let station_stop: Option<&gtfs_structures::Stop> =
+                    gtfs.stops.get(station_id).map(AsRef::as_ref);
+                let name = station_stop.map_or_else(|| station_id.to_string(), stop_display_name);
+                let coor = station_stop.and_then(stop_coor).or_else(|| stop_coor(stop));
+                let mut station = commands.spawn((Station::default(), Name::new(name)));
+                if let Some(coor) = coor {
+                    station.insert(Node { coor });
+                }
+                let entity = station.id();
+                graph.add_node(entity);
+                self.stations.insert(station_id.to_string(), entity);
+                entity
+            }
+        };
+        if station_id == stop.id {
+            return (station, station);
+        }
+        if let Some(&platform) = self.platforms.get(&stop.id) {
+            return (station, platform);
+        }
+        let mut platform = commands.spawn((
+            Platform::default(),
+            Name::new(stop_display_name(stop)),
+            ChildOf(station),
+        ));
+        if let Some(coor) = stop_coor(stop) {
+            platform.insert(Node { coor });
+        }
+        let platform = platform.id();
+        self.platforms.insert(stop.id.clone(), platform);
+        (station, platform)
+    }
+}
+
+/// Shape points within this many metres of a stop are taken as passing the stop.
+const SHAPE_SNAP_METERS: i32 = 150;
+
+/// The index of the point of the shape where it passes a stop, searching from `from` onwards.
+///
+/// The shape is cut by distance travelled when both the stop and the shape record it. Otherwise
+/// the search moves forward to the first time the shape passes the stop, so that shapes passing
+/// the same place again later, such as loops, are not cut short.
+fn shape_point(
+    shape: &[gtfs_structures::Shape],
+    from: usize,
+    coor: Option<NodeCoor>,
+    dist_traveled: Option<f32>,
+) -> Option<usize> {
+    if shape.is_empty() {
+        return None;
+    }
+    let from = from.min(shape.len() - 1);
+    if let Some(dist) = dist_traveled
+        && shape.iter().all(|it| it.dist_traveled.is_some())
+    {
+        let index = shape[from..]
+            .iter()
+            .position(|it| it.dist_traveled.is_some_and(|it| it >= dist))
+            .map_or(shape.len() - 1, |index| from + index);
+        return Some(index);
+    }
+    let coor = coor?;
+    let distance = |index: usize| {
+        let point = &shape[index];
+        NodeCoor::new(point.longitude, point.latitude)
+            .distance(&coor)
+            .0
+    };
+    let Some(mut index) = (from..shape.len()).find(|it| distance(*it) <= SHAPE_SNAP_METERS) else {
+        // the shape never comes close to the stop, take its nearest point
+        return (from..shape.len()).min_by_key(|it| distance(*it));
+    };
+    // follow the shape while it keeps getting closer
+    while index + 1 < shape.len() && distance(index + 1) < distance(index) {
+        index += 1;
+    }
+    Some(index)
+}
+
+/// Build the operating calendars from `calendar.txt` and `calendar_dates.txt`, keyed by
+/// `service_id`.
+fn service_calendars(gtfs: &gtfs_structures::Gtfs) -> HashMap<String, ServiceCalendar> {
+    let mut calendars: HashMap<String, ServiceCalendar> = gtfs
+        .calendar
+        .iter()
+        .map(|(service_id, calendar)| {
+            let calendar = ServiceCalendar {
+                weekdays: Weekdays::from_flags([
+                    calendar.monday,
+                    calendar.tuesday,
+                    calendar.wednesday,
+                    calendar.thursday,
+                    calendar.friday,
+                    calendar.saturday,
+                    calendar.sunday,
+                ]),
+                ranges: vec![DateRange {
+                    start: ServiceDate::from_naive_date(calendar.start_date),
+                    end: ServiceDate::from_naive_date(calendar.end_date),
+                }],
+                added: Vec::new(),
+                removed: Vec::new(),
+            };
+            (service_id.clone(), calendar)
+        })
+        .collect();
+    for (service_id, dates) in &gtfs.calendar_dates {
+        // services only defined in calendar_dates.txt run on the added dates only
+        let calendar = calendars
+            .entry(service_id.clone())
+            .or_insert_with(|| ServiceCalendar {
+                weekdays: Weekdays::NONE,
+                ..default()
+            });
+        for date in dates {
+            let service_date = ServiceDate::from_naive_date(date.date);
+            match date.exception_type {
+                gtfs_structures::Exception::Added => calendar.added.push(service_date),
+                gtfs_structures::Exception::Deleted => calendar.removed.push(service_date),
+            }
+        }
+    }
+    calendars
+}
+
 pub fn load_gtfs_static(
     data: On<super::LoadGTFS>,
     mut commands: Commands,
     mut graph: ResMut<Graph>,
+    settings: Res<ProjectSettings>,
 ) {
     info!("Loading GTFS static data...");
     let reader = Cursor::new(data.content.as_slice());
@@ -72,42 +248,13 @@ pub fn load_gtfs_static(
         return;
     };
 
-    let mut station_entities: HashMap<String, Entity> = HashMap::new();
-    let mut platform_entities: HashMap<String, Entity> = HashMap::new();
+    let mut stop_entities = StopEntities::default();
     let mut class_map: HashMap<String, Instance<Class>> = HashMap::new();
     let mut route_built: std::collections::HashSet<String> = std::collections::HashSet::new();
-    let mut block_to_trips: HashMap<String, Vec<Entity>> = HashMap::new();
-
-    let mut ensure_station =
-        |station_id: &str, station_name: &str, graph: &mut Graph, commands: &mut Commands| {
-            if let Some(&entity) = station_entities.get(station_id) {
-                return entity;
-            }
-            let entity = commands
-                .spawn((Station::default(), Name::new(station_name.to_string())))
-                .id();
-            graph.add_node(entity);
-            station_entities.insert(station_id.to_string(), entity);
-            entity
-        };
-
-    let mut ensure_platform = |platform_id: &str,
-                               platform_name: &str,
-                               parent_station: Entity,
-                               commands: &mut Commands| {
-        if let Some(&entity) = platform_entities.get(platform_id) {
-            return entity;
-        }
-        let entity = commands
-            .spawn((
-                Platform::default(),
-                Name::new(platform_name.to_string()),
-                ChildOf(parent_station),
-            ))
-            .id();
-        platform_entities.insert(platform_id.to_string(), entity);
-        entity
-    };
+    // first departure time and trip entity
+    let mut block_to_trips: HashMap<String, Vec<(u32, Entity)>> = HashMap::new();
+    let calendars = service_calendars(&gtfs);
+    let mut shaped_intervals: std::collections::HashSet<Entity> = std::collections::HashSet::new();
 
     for trip in gtfs.trips.values() {
         if trip.stop_times.is_empty() {
@@ -130,36 +277,7 @@ pub fn load_gtfs_static(
             Vec::with_capacity(trip.stop_times.len());
         for stop_time in &trip.stop_times {
             let stop = &stop_time.stop;
-            let stop_name = stop_display_name(stop);
-
-            let (station_entity, platform_entity) =
-                if let Some(parent_station_id) = &stop.parent_station {
-                    let parent_stop = gtfs.stops.get(parent_station_id);
-                    let parent_name = parent_stop
-                        .map(|stop| stop_display_name(stop.as_ref()))
-                        .unwrap_or_else(|| parent_station_id.clone());
-                    let station_entity =
-                        ensure_station(parent_station_id, &parent_name, &mut graph, &mut commands);
-                    let platform_entity =
-                        ensure_platform(&stop.id, &stop_name, station_entity, &mut commands);
-                    (station_entity, platform_entity)
-                } else {
-                    let station_entity =
-                        ensure_station(&stop.id, &stop_name, &mut graph, &mut commands);
-                    (station_entity, station_entity)
-                };
-
-            if let (Some(lat), Some(lon)) = (stop.latitude, stop.longitude) {
-                commands.entity(platform_entity).insert(Node {
-                    coor: NodeCoor::new(lon, lat),
-                });
-                if platform_entity != station_entity {
-                    commands.entity(station_entity).insert(Node {
-                        coor: NodeCoor::new(lon, lat),
-                    });
-                }
-            }
-
+            let (station_entity, _) = stop_entities.ensure(&gtfs, stop, &mut graph, &mut commands);
             stops_for_trip.push((
                 station_entity,
                 stop.latitude,
@@ -174,6 +292,13 @@ pub fn load_gtfs_static(
             let mut prev_station: Option<Entity> = None;
             let mut prev_shape_dist: Option<f32> = None;
             let mut prev_lat_lon: Option<(f64, f64)> = None;
+            // the shape is cut where it passes each stop
+            let shape = trip
+                .shape_id
+                .as_ref()
+                .and_then(|it| gtfs.shapes.get(it))
+                .map_or(&[][..], Vec::as_slice);
+            let mut prev_shape_index: Option<usize> = None;
 
             for (stop, lat, lon, shape_dist) in &stops_for_trip {
                 let curr_station = *stop;
@@ -182,6 +307,12 @@ pub fn load_gtfs_static(
                 }
 
                 route_stops.push(curr_station);
+                let shape_index = shape_point(
+                    shape,
+                    prev_shape_index.unwrap_or(0),
+                    lat.zip(*lon).map(|(lat, lon)| NodeCoor::new(lon, lat)),
+                    *shape_dist,
+                );
                 if let Some(prev) = prev_station {
                     let mut km = match (shape_dist, prev_shape_dist) {
                         (Some(curr), Some(prev)) => (*curr - prev).abs(),
@@ -203,6 +334,25 @@ pub fn load_gtfs_static(
                         curr_station,
                         Distance::from_km(km),
                     );
+                    if let (Some(from), Some(to)) = (prev_shape_index, shape_index)
+                        && to > from + 1
+                    {
+                        let points: Vec<NodeCoor> = shape[from + 1..to]
+                            .iter()
+                            .map(|it| NodeCoor::new(it.longitude, it.latitude))
+                            .collect();
+                        // intervals shared by several routes keep the first shape
+                        for (source, target, points) in [
+                            (prev, curr_station, points.clone()),
+                            (curr_station, prev, points.into_iter().rev().collect()),
+                        ] {
+                            if let Some(&interval) = graph.edge_weight(source, target)
+                                && shaped_intervals.insert(interval)
+                            {
+                                commands.entity(interval).insert(IntervalShape(points));
+                            }
+                        }
+                    }
                     lengths.push(km);
                 } else {
                     lengths.push(0.0);
@@ -211,6 +361,7 @@ pub fn load_gtfs_static(
                 prev_station = Some(curr_station);
                 prev_shape_dist = *shape_dist;
                 prev_lat_lon = lat.zip(*lon);
+                prev_shape_index = shape_index;
             }
 
             if route_stops.len() >= 2 {
@@ -235,26 +386,8 @@ pub fn load_gtfs_static(
             Vec::with_capacity(trip.stop_times.len());
         let mut previous_arrival: Option<TimetableTime> = None;
         for stop_time in &trip.stop_times {
-            let stop = &stop_time.stop;
-            let stop_name = stop_display_name(stop);
-
-            let stop_entity = if let Some(parent_station_id) = &stop.parent_station {
-                let parent_stop = gtfs.stops.get(parent_station_id);
-                let parent_name = parent_stop
-                    .map(|stop| stop_display_name(stop.as_ref()))
-                    .unwrap_or_else(|| parent_station_id.clone());
-                let parent_station =
-                    ensure_station(parent_station_id, &parent_name, &mut graph, &mut commands);
-                ensure_platform(&stop.id, &stop_name, parent_station, &mut commands)
-            } else {
-                ensure_station(&stop.id, &stop_name, &mut graph, &mut commands)
-            };
-
-            if let (Some(lat), Some(lon)) = (stop.latitude, stop.longitude) {
-                commands.entity(stop_entity).insert(Node {
-                    coor: NodeCoor::new(lon, lat),
-                });
-            }
+            let (_, stop_entity) =
+                stop_entities.ensure(&gtfs, &stop_time.stop, &mut graph, &mut commands);
 
             let arr = stop_time
                 .arrival_time
@@ -304,31 +437,276 @@ pub fn load_gtfs_static(
             ))
             .id();
 
+        if let Some(calendar) = calendars.get(&trip.service_id) {
+            commands.entity(trip_entity).insert(calendar.clone());
+        }
+
+        // frequency-based trips are kept as a single template trip
+        if !trip.frequencies.is_empty() {
+            let periods = trip
+                .frequencies
+                .iter()
+                .map(|it| FrequencyPeriod {
+                    start: TimetableTime(it.start_time as i32),
+                    end: TimetableTime(it.end_time as i32),
+                    headway: Duration(it.headway_secs as i32),
+                })
+                .collect();
+            commands
+                .entity(trip_entity)
+                .insert(TripFrequency { periods });
+        }
+
         if let Some(block_id) = &trip.block_id {
+            let first_departure = trip
+                .stop_times
+                .iter()
+                .find_map(|it| it.departure_time.or(it.arrival_time))
+                .unwrap_or(0);
             block_to_trips
                 .entry(block_id.clone())
                 .or_default()
-                .push(trip_entity);
+                .push((first_departure, trip_entity));
+        }
+    }
+
+    // platforms of the imported stations that no trip stops at
+    for stop in gtfs.stops.values() {
+        if matches!(stop.location_type, gtfs_structures::LocationType::StopPoint)
+            && stop.parent_station.is_some()
+            && stop_entities.station(stop).is_some()
+        {
+            stop_entities.ensure(&gtfs, stop, &mut graph, &mut commands);
+        }
+    }
+
+    // transfers within a station set its minimum transfer time, and transfers between stations
+    // become footpaths
+    let mut transfer_times: HashMap<Entity, Duration> = HashMap::new();
+    let mut footpaths: HashMap<Entity, Vec<Footpath>> = HashMap::new();
+    let mut walked: std::collections::HashSet<(Entity, Entity)> = std::collections::HashSet::new();
+    for from_stop in gtfs.stops.values() {
+        let Some(from) = stop_entities.station(from_stop) else {
+            continue;
+        };
+        for transfer in &from_stop.transfers {
+            if matches!(
+                transfer.transfer_type,
+                gtfs_structures::TransferType::Impossible
+            ) {
+                continue;
+            }
+            let Some(to_stop) = gtfs.stops.get(&transfer.to_stop_id) else {
+                continue;
+            };
+            let Some(to) = stop_entities.station(to_stop) else {
+                continue;
+            };
+            let min_transfer_time = transfer.min_transfer_time.map(|it| Duration(it as i32));
+            if from == to {
+                if let Some(time) = min_transfer_time {
+                    let current = transfer_times.entry(from).or_insert(time);
+                    *current = (*current).max(time);
+                }
+                continue;
+            }
+            // footpaths can be walked in both directions
+            if !walked.insert((from.min(to), from.max(to))) {
+                continue;
+            }
+            let walking_time = match min_transfer_time {
+                Some(time) => time,
+                None => {
+                    let (Some(a), Some(b)) = (stop_coor(from_stop), stop_coor(to_stop)) else {
+                        continue;
+                    };
+                    a.distance(&b) / settings.walking_speed
+                }
+            };
+            footpaths
+                .entry(from)
+                .or_default()
+                .push(Footpath { to, walking_time });
         }
     }
+    for (station, time) in &transfer_times {
+        commands.entity(*station).insert(MinimumTransferTime(*time));
+    }
+    let footpath_count: usize = footpaths.values().map(Vec::len).sum();
+    for (station, footpaths) in footpaths {
+        commands.entity(station).insert(Footpaths(footpaths));
+    }
 
-    for (block_id, trips) in block_to_trips {
+    for (block_id, mut trips) in block_to_trips {
+        // vehicles run their trips in order
+        trips.sort_unstable();
         commands.spawn((
             Name::new(format!("GTFS block {block_id}")),
-            Vehicle { trips },
+            Vehicle {
+                trips: trips.into_iter().map(|(_, it)| it).collect(),
+            },
         ));
     }
 
     info!(
-        "GTFS import completed: stations={}, classes={}, routes={}, vehicles={}",
-        station_entities.len(),
+        "GTFS import completed: stations={}, platforms={}, classes={}, routes={}, calendars={}, \
+         vehicles={}, shaped intervals={}, transfer times={}, footpaths={}",
+        stop_entities.stations.len(),
+        stop_entities.platforms.len(),
         class_map.len(),
         route_built.len(),
+        calendars.len(),
         gtfs.trips
             .values()
             .filter(|t| t.block_id.is_some())
             .map(|t| t.block_id.as_ref().unwrap())
             .collect::<std::collections::HashSet<_>>()
-            .len()
+            .len(),
+        shaped_intervals.len(),
+        transfer_times.len(),
+        footpath_count,
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// A feed with a looped line from Alpha through Beta to Gamma. Alpha has a platform no trip
+    /// stops at, and the line loops north after Beta and passes it again before reaching Gamma.
+    const FEED: [(&str, &str); 8] = [
+        (
+            "agency.txt",
+            "agency_name,agency_url,agency_timezone\nTest,https://example.com,UTC\n",
+        ),
+        (
+            "stops.txt",
+            "stop_id,stop_name,stop_lat,stop_lon,location_type,parent_station\n\
+             S1,Alpha,0,0,1,\n\
+             S1P1,Alpha 1,0,0.0001,0,S1\n\
+             S1P2,Alpha 2,0,-0.0001,0,S1\n\
+             S2,Beta,0,0.01,0,\n\
+             S3,Gamma,0,0.02,0,\n",
+        ),
+        (
+            "routes.txt",
+            "route_id,route_short_name,route_long_name,route_type\nR1,1,Loop,2\n",
+        ),
+        (
+            "trips.txt",
+            "route_id,service_id,trip_id,shape_id\nR1,WK,T1,SH1\n",
+        ),
+        (
+            "stop_times.txt",
+            "trip_id,arrival_time,departure_time,stop_id,stop_sequence\n\
+             T1,08:00:00,08:00:00,S1P1,1\n\
+             T1,08:05:00,08:06:00,S2,2\n\
+             T1,08:15:00,08:15:00,S3,3\n",
+        ),
+        (
+            "calendar.txt",
+            "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,\
+             end_date\nWK,1,1,1,1,1,0,0,20250101,20251231\n",
+        ),
+        (
+            "shapes.txt",
+            "shape_id,shape_pt_lat,shape_pt_lon,shape_pt_sequence\n\
+             SH1,0,0.0001,1\n\
+             SH1,0,0.005,2\n\
+             SH1,0.0003,0.01,3\n\
+             SH1,0.01,0.01,4\n\
+             SH1,0.01,0,5\n\
+             SH1,0,0.01,6\n\
+             SH1,0,0.015,7\n\
+             SH1,0,0.02,8\n",
+        ),
+        (
+            "transfers.txt",
+            "from_stop_id,to_stop_id,transfer_type,min_transfer_time\n\
+             S1P1,S1P2,2,180\n\
+             S2,S3,2,600\n",
+        ),
+    ];
+
+    fn load() -> World {
+        let mut content = Vec::new();
+        let mut writer = zip::ZipWriter::new(Cursor::new(&mut content));
+        for (name, table) in FEED {
+            writer
+                .start_file(name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(table.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut world = World::new();
+        world.init_resource::<Graph>();
+        world.init_resource::<ProjectSettings>();
+        world.add_observer(load_gtfs_static);
+        world.trigger(crate::import::LoadGTFS { content });
+        world.flush();
+        world
+    }
+
+    fn station(world: &mut World, name: &str) -> Entity {
+        world
+            .query_filtered::<(Entity, &Name), With<Station>>()
+            .iter(world)
+            .find(|(_, it)| it.as_str() == name)
+            .unwrap()
+            .0
+    }
+
+    fn shape(world: &World, from: Entity, to: Entity) -> Vec<NodeCoor> {
+        let interval = *world.resource::<Graph>().edge_weight(from, to).unwrap();
+        world
+            .get::<IntervalShape>(interval)
+            .map_or_else(Vec::new, |it| it.0.clone())
+    }
+
+    #[test]
+    fn parent_station_keeps_unused_platform() {
+        let mut world = load();
+        let alpha = station(&mut world, "Alpha");
+        let mut platforms: Vec<String> = world
+            .query_filtered::<(&Name, &ChildOf), With<Platform>>()
+            .iter(&world)
+            .filter(|(_, parent)| parent.parent() == alpha)
+            .map(|(name, _)| name.to_string())
+            .collect();
+        platforms.sort();
+        assert_eq!(platforms, ["Alpha 1", "Alpha 2"]);
+    }
+
+    #[test]
+    fn transfers_within_and_between_stations() {
+        let mut world = load();
+        let alpha = station(&mut world, "Alpha");
+        let beta = station(&mut world, "Beta");
+        let gamma = station(&mut world, "Gamma");
+        assert_eq!(
+            world.get::<MinimumTransferTime>(alpha).map(|it| it.0),
+            Some(Duration(180))
+        );
+        assert!(world.get::<Footpaths>(alpha).is_none());
+        let footpaths = world.get::<Footpaths>(beta).unwrap();
+        assert_eq!(footpaths.len(), 1);
+        assert_eq!(footpaths[0].to, gamma);
+        assert_eq!(footpaths[0].walking_time, Duration(600));
+        assert!(world.get::<MinimumTransferTime>(beta).is_none());
+    }
+
+    #[test]
+    fn looped_shape_is_cut_where_it_first_passes_a_stop() {
+        let mut world = load();
+        let alpha = station(&mut world, "Alpha");
+        let beta = station(&mut world, "Beta");
+        let gamma = station(&mut world, "Gamma");
+        // the loop lies between Beta and Gamma, even though the shape passes closer to Beta
+        // on its way back
+        assert_eq!(shape(&world, alpha, beta).len(), 1);
+        assert_eq!(shape(&world, beta, gamma).len(), 4);
+        assert_eq!(shape(&world, gamma, beta).len(), 4);
+    }
+}
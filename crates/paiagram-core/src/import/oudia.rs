@@ -6,8 +6,9 @@ use crate::{
     route::Route,
     station::Station as StationComponent,
     trip::{
-        TripBundle, TripClass,
+        TripBundle, TripClass, TripScenario,
         class::{Class as ClassComponent, ClassBundle, DisplayedStroke},
+        scenario::Scenario,
     },
     units::{distance::Distance, time::TimetableTime},
 };
@@ -72,9 +73,26 @@ pub fn load_oud(msg: On<super::LoadOuDia>, mut commands: Commands, mut graph: Re
         })
         .collect();
 
-    let travel_durations: Vec<Option<OuDiaTime>> = route.diagrams[0]
-        .minimum_interval_durations(&route.stations)
-        .collect();
+    // take the minimum over all diagrams, so that the interval lengths do not depend on which
+    // diagram happens to come first
+    let travel_durations: Vec<Option<OuDiaTime>> = route
+        .diagrams
+        .iter()
+        .map(|diagram| {
+            diagram
+                .minimum_interval_durations(&route.stations)
+                .collect::<Vec<_>>()
+        })
+        .reduce(|acc, durations| {
+            acc.into_iter()
+                .zip(durations)
+                .map(|(a, b)| match (a, b) {
+                    (Some(a), Some(b)) => Some(if a.seconds() <= b.seconds() { a } else { b }),
+                    (a, b) => a.or(b),
+                })
+                .collect()
+        })
+        .unwrap_or_else(|| route.stations.iter().map(|_| None).collect());
 
     commands.spawn((
         Name::new(route.name),
@@ -106,8 +124,11 @@ pub fn load_oud(msg: On<super::LoadOuDia>, mut commands: Commands, mut graph: Re
         );
     }
 
-    // TODO: find a method to support multiple diagrams
-    for diagram in route.diagrams.into_iter().take(1) {
+    // each diagram becomes a scenario
+    for diagram in route.diagrams {
+        let scenario = commands
+            .spawn((Scenario::default(), Name::new(diagram.name)))
+            .id();
         for trip in diagram.trips {
             let times: Vec<TimetableEntry> = trip
                 .times
@@ -171,10 +192,13 @@ pub fn load_oud(msg: On<super::LoadOuDia>, mut commands: Commands, mut graph: Re
             commands
                 .spawn_empty()
                 .add_children(&nominal_entries)
-                .insert(TripBundle::new(
-                    &trip.name.unwrap_or("<??>".to_string()),
-                    TripClass(trip_class.entity()),
-                    nominal_entries,
+                .insert((
+                    TripBundle::new(
+                        &trip.name.unwrap_or("<??>".to_string()),
+                        TripClass(trip_class.entity()),
+                        nominal_entries,
+                    ),
+                    TripScenario(scenario),
                 ));
         }
     }
@@ -1,14 +1,53 @@
 use bevy::{ecs::query::QueryData, prelude::*};
 
-use crate::units::distance::Distance;
+use crate::{
+    graph::NodeCoor,
+    trip::running_time::Leg,
+    units::{distance::Distance, speed::Velocity},
+};
 
 /// Intervals
 #[derive(Reflect, Component)]
 #[reflect(Component)]
 pub struct Interval {
     pub length: Distance,
+    /// Total number of tracks of the interval, shared by both directions. Opposing trips may only
+    /// meet at stations on single-track intervals.
+    #[reflect(default = "default_tracks")]
+    pub tracks: u8,
+    /// The maximum speed allowed on this interval
+    #[reflect(default)]
+    pub speed_limit: Option<Velocity>,
+    /// Gradient in per mille in this direction. Positive values are uphill.
+    #[reflect(default)]
+    pub gradient: f32,
 }
 
+fn default_tracks() -> u8 {
+    Interval::DEFAULT_TRACKS
+}
+
+impl Interval {
+    pub const DEFAULT_TRACKS: u8 = 2;
+    pub fn new(length: Distance) -> Self {
+        Self {
+            length,
+            tracks: Self::DEFAULT_TRACKS,
+            speed_limit: None,
+            gradient: 0.0,
+        }
+    }
+    pub fn is_single_track(&self) -> bool {
+        self.tracks <= 1
+    }
+}
+
+/// The points the interval passes through between its two stations, in the interval's
+/// direction. Intervals without this component are drawn as straight lines on the map.
+#[derive(Reflect, Component, Default, Clone, Debug, Deref, DerefMut)]
+#[reflect(Component)]
+pub struct IntervalShape(pub Vec<NodeCoor>);
+
 #[derive(QueryData)]
 pub struct IntervalQuery {
     distance: &'static Interval,
@@ -18,6 +57,17 @@ impl<'w, 's> IntervalQueryItem<'w, 's> {
     pub fn distance(&self) -> Distance {
         self.distance.length
     }
+    pub fn tracks(&self) -> u8 {
+        self.distance.tracks
+    }
+    /// The interval as a leg for running time calculations
+    pub fn leg(&self) -> Leg {
+        Leg {
+            length: self.distance.length,
+            speed_limit: self.distance.speed_limit,
+            gradient: self.distance.gradient,
+        }
+    }
 }
 
 #[derive(EntityEvent)]
@@ -1,11 +1,527 @@
+//! # Plugin
+//! User supplied WebAssembly modules that read the timetable and queue changes to it.
+//!
+//! A plugin exports its `memory` and a `run` function taking no arguments. While `run` is
+//! called, the plugin can use the host functions in the `paiagram` module:
+//!
+//! - `snapshot_len() -> i32` returns the length of the CBOR encoded [`Snapshot`].
+//! - `read_snapshot(ptr: i32)` copies the snapshot into the plugin's memory.
+//! - `push_command(ptr: i32, len: i32) -> i32` queues a CBOR encoded [`WasmCommand`]. It returns
+//!   0, or -1 if the command cannot be decoded.
+//! - `log(ptr: i32, len: i32)` adds a UTF-8 message to the plugin's log.
+//!
+//! Plugins run in the background. Queued commands are applied after `run` returns, and only if
+//! all of them are valid and the stations and trips they add entries to still exist.
+
+use crate::{
+    entry::{EntryBundle, EntryQuery, TravelMode},
+    graph::Graph,
+    station::{ParentStationOrStation, Station},
+    trip::{
+        Trip, TripBundle, TripClass, TripSchedule,
+        class::{Class, ClassBundle, DisplayedStroke},
+        routing::AddEntryToTrip,
+    },
+    units::time::TimetableTime,
+};
+use bevy::{
+    ecs::entity::EntityHashMap,
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+    tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future::poll_once},
+};
+use eros::bail;
+use moonshine_core::kind::*;
+use serde::{Deserialize, Serialize};
+use wasmi::{
+    Caller, Config, Engine, Extern, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+};
+
+/// The number of instructions a plugin can run before it is stopped, roughly
+const FUEL: u64 = 500_000_000;
+/// The largest memory a plugin can grow to, in bytes
+const MAX_MEMORY: usize = 256 * 1024 * 1024;
+/// The longest command or log message a plugin can pass, in bytes
+const MAX_MESSAGE_LEN: usize = 1024 * 1024;
+
+pub struct WasmPluginPlugin;
+
+impl Plugin for WasmPluginPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WasmPlugins>()
+            .add_observer(load_plugin)
+            .add_systems(Update, poll_plugin_runs);
+    }
+}
+
+/// A change requested by a plugin. Stations and trips are referred to by their IDs in the
+/// [`Snapshot`], or by the IDs given when the plugin created them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum WasmCommand {
-    CreateStation { name: String, id: u32 },
-    CreateEntry { station_id: u32 },
+    CreateStation {
+        name: String,
+        id: u32,
+    },
+    /// Create a trip without entries. The class is created if no class has the name.
+    CreateTrip {
+        name: String,
+        class: String,
+        id: u32,
+    },
+    /// Append an entry to the trip. Times are seconds after midnight, and the entry passes
+    /// the station without a fixed time if the departure is not given.
+    CreateEntry {
+        trip_id: u32,
+        station_id: u32,
+        arrival: Option<i32>,
+        departure: Option<i32>,
+    },
+}
+
+/// The timetable as seen by plugins. IDs are the indices into the lists.
+#[derive(Debug, Serialize)]
+pub struct Snapshot {
+    pub stations: Vec<SnapshotStation>,
+    pub trips: Vec<SnapshotTrip>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SnapshotStation {
+    pub id: u32,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SnapshotTrip {
+    pub id: u32,
+    pub name: String,
+    pub class: String,
+    pub entries: Vec<SnapshotEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SnapshotEntry {
+    pub station_id: u32,
+    /// Estimated times in seconds after midnight
+    pub arrival: Option<i32>,
+    pub departure: Option<i32>,
+    /// Whether the entry was derived from the route rather than entered
+    pub derived: bool,
 }
 
 pub struct PluginState {
+    snapshot: Vec<u8>,
     /// Wasm pushes events here via host functions
     pub command_queue: Vec<WasmCommand>,
+    pub log: Vec<String>,
+    limits: StoreLimits,
+}
+
+/// A compiled plugin
+pub struct LoadedPlugin {
+    /// Stays the same when other plugins are removed
+    id: u64,
+    pub name: String,
+    module: Module,
+    /// Messages logged by the plugin and the results of its runs
+    pub log: Vec<String>,
+    /// Whether the plugin is running in the background
+    pub running: bool,
+}
+
+/// All loaded plugins. Plugins are not saved with the project.
+#[derive(Resource)]
+pub struct WasmPlugins {
+    engine: Engine,
+    next_id: u64,
+    pub items: Vec<LoadedPlugin>,
+}
+
+impl Default for WasmPlugins {
+    fn default() -> Self {
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        Self {
+            engine: Engine::new(&config),
+            next_id: 0,
+            items: Vec::new(),
+        }
+    }
+}
+
+/// A run of a plugin in the background, with the entities of the snapshot's IDs
+#[derive(Component)]
+struct PluginRun {
+    plugin: u64,
+    task: Task<PluginOutcome>,
+    station_ids: Vec<Entity>,
+    trip_ids: Vec<Entity>,
 }
 
-// TODO: add wasm plugin support. Pass in a &[u8], pass out a cbor
+/// The result of a run, the queued commands, and the logged messages
+type PluginOutcome = (Result<(), String>, Vec<WasmCommand>, Vec<String>);
+
+/// Compile the module and add it to [`WasmPlugins`].
+#[derive(Event)]
+pub struct LoadPlugin {
+    pub name: String,
+    pub bytes: Vec<u8>,
+}
+
+fn load_plugin(event: On<LoadPlugin>, mut plugins: ResMut<WasmPlugins>) {
+    match Module::new(&plugins.engine, &event.bytes) {
+        Ok(module) => {
+            info!("Loaded plugin {}", event.name);
+            let id = plugins.next_id;
+            plugins.next_id += 1;
+            plugins.items.push(LoadedPlugin {
+                id,
+                name: event.name.clone(),
+                module,
+                log: Vec::new(),
+                running: false,
+            });
+        }
+        Err(e) => warn!("Failed to load plugin {}: {e}", event.name),
+    }
+}
+
+/// Start running the plugin at the index of [`WasmPlugins`] in the background. Its commands are
+/// applied when it finishes.
+pub fn run_plugin(world: &mut World, index: usize) -> eros::Result<()> {
+    let (snapshot, station_ids, trip_ids) = world.run_system_cached(take_snapshot).unwrap();
+    let state = PluginState {
+        snapshot: cbor4ii::serde::to_vec(Vec::new(), &snapshot)?,
+        command_queue: Vec::new(),
+        log: Vec::new(),
+        limits: StoreLimitsBuilder::new().memory_size(MAX_MEMORY).build(),
+    };
+    let mut plugins = world.resource_mut::<WasmPlugins>();
+    let engine = plugins.engine.clone();
+    let Some(plugin) = plugins.items.get_mut(index) else {
+        bail!("There is no plugin at index {}", index);
+    };
+    if plugin.running {
+        bail!("Plugin {} is already running", plugin.name);
+    }
+    plugin.running = true;
+    let id = plugin.id;
+    let module = plugin.module.clone();
+    let (stations, trips) = (snapshot.stations.len(), snapshot.trips.len());
+    let task = AsyncComputeTaskPool::get().spawn(async move {
+        let mut store = Store::new(&engine, state);
+        store.limiter(|state| &mut state.limits);
+        let result = execute(&mut store, &module)
+            .and_then(|()| validate(&store.data().command_queue, stations, trips))
+            .map_err(|e| e.to_string());
+        let PluginState {
+            command_queue, log, ..
+        } = store.into_data();
+        (result, command_queue, log)
+    });
+    world.spawn(PluginRun {
+        plugin: id,
+        task,
+        station_ids,
+        trip_ids,
+    });
+    Ok(())
+}
+
+fn poll_plugin_runs(mut commands: Commands, runs: Populated<(Entity, &mut PluginRun)>) {
+    for (entity, mut run) in runs {
+        let Some((result, command_queue, mut log)) = block_on(poll_once(&mut run.task)) else {
+            continue;
+        };
+        commands.entity(entity).despawn();
+        let id = run.plugin;
+        let station_ids = std::mem::take(&mut run.station_ids);
+        let trip_ids = std::mem::take(&mut run.trip_ids);
+        commands.queue(move |world: &mut World| {
+            let count = command_queue.len();
+            let result =
+                result.and_then(|()| check_targets(world, &command_queue, &station_ids, &trip_ids));
+            if result.is_ok() {
+                world
+                    .run_system_cached_with(apply_commands, (command_queue, station_ids, trip_ids))
+                    .unwrap();
+            }
+            match &result {
+                Ok(()) => log.push(format!("Applied {count} commands")),
+                Err(e) => log.push(format!("Failed: {e}")),
+            }
+            let mut plugins = world.resource_mut::<WasmPlugins>();
+            if let Some(plugin) = plugins.items.iter_mut().find(|it| it.id == id) {
+                plugin.running = false;
+                plugin.log.append(&mut log);
+                if let Err(e) = &result {
+                    warn!("Plugin {} failed: {e}", plugin.name);
+                }
+            }
+        });
+    }
+}
+
+fn execute(store: &mut Store<PluginState>, module: &Module) -> eros::Result<()> {
+    store.set_fuel(FUEL)?;
+    let mut linker = <Linker<PluginState>>::new(store.engine());
+    linker.func_wrap(
+        "paiagram",
+        "snapshot_len",
+        |caller: Caller<'_, PluginState>| caller.data().snapshot.len() as i32,
+    )?;
+    linker.func_wrap(
+        "paiagram",
+        "read_snapshot",
+        |mut caller: Caller<'_, PluginState>, ptr: i32| -> Result<(), wasmi::Error> {
+            let memory = memory(&caller)?;
+            let (data, state) = memory.data_and_store_mut(&mut caller);
+            let target = (ptr as u32 as usize)
+                .checked_add(state.snapshot.len())
+                .and_then(|end| data.get_mut(ptr as u32 as usize..end))
+                .ok_or_else(|| wasmi::Error::new("snapshot does not fit into the memory"))?;
+            target.copy_from_slice(&state.snapshot);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        "paiagram",
+        "push_command",
+        |mut caller: Caller<'_, PluginState>, ptr: i32, len: i32| -> Result<i32, wasmi::Error> {
+            let bytes = read_bytes(&caller, ptr, len)?;
+            let Ok(command) = cbor4ii::serde::from_slice::<WasmCommand>(&bytes) else {
+                return Ok(-1);
+            };
+            caller.data_mut().command_queue.push(command);
+            Ok(0)
+        },
+    )?;
+    linker.func_wrap(
+        "paiagram",
+        "log",
+        |mut caller: Caller<'_, PluginState>, ptr: i32, len: i32| -> Result<(), wasmi::Error> {
+            let bytes = read_bytes(&caller, ptr, len)?;
+            let message = String::from_utf8_lossy(&bytes).into_owned();
+            caller.data_mut().log.push(message);
+            Ok(())
+        },
+    )?;
+    let instance = linker.instantiate_and_start(&mut *store, module)?;
+    let run = instance.get_typed_func::<(), ()>(&*store, "run")?;
+    run.call(&mut *store, ())?;
+    Ok(())
+}
+
+fn memory(caller: &Caller<'_, PluginState>) -> Result<Memory, wasmi::Error> {
+    caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| wasmi::Error::new("the plugin does not export its memory"))
+}
+
+fn read_bytes(
+    caller: &Caller<'_, PluginState>,
+    ptr: i32,
+    len: i32,
+) -> Result<Vec<u8>, wasmi::Error> {
+    let memory = memory(caller)?;
+    let start = ptr as u32 as usize;
+    let len = usize::try_from(len)
+        .ok()
+        .filter(|it| *it <= MAX_MESSAGE_LEN)
+        .ok_or_else(|| wasmi::Error::new("invalid message length"))?;
+    // check the bounds before allocating the length the plugin asked for
+    if start
+        .checked_add(len)
+        .is_none_or(|end| end > memory.data_size(caller))
+    {
+        return Err(wasmi::Error::new("message does not fit into the memory"));
+    }
+    let mut bytes = vec![0; len];
+    memory
+        .read(caller, ptr as u32 as usize, &mut bytes)
+        .map_err(|e| wasmi::Error::new(e.to_string()))?;
+    Ok(bytes)
+}
+
+fn take_snapshot(
+    station_q: Query<(Entity, &Name), With<Station>>,
+    trip_q: Query<(Entity, &Name, &TripClass, &TripSchedule), With<Trip>>,
+    entry_q: Query<EntryQuery>,
+    parent_station_or_station: Query<ParentStationOrStation>,
+    name_q: Query<&Name>,
+) -> (Snapshot, Vec<Entity>, Vec<Entity>) {
+    let stations: Vec<_> = station_q.iter().collect();
+    let trips: Vec<_> = trip_q.iter().collect();
+    let station_ids: EntityHashMap<u32> = (0..)
+        .zip(&stations)
+        .map(|(id, (entity, _))| (*entity, id))
+        .collect();
+    let snapshot = Snapshot {
+        stations: (0..)
+            .zip(&stations)
+            .map(|(id, (_, name))| SnapshotStation {
+                id,
+                name: name.to_string(),
+            })
+            .collect(),
+        trips: (0..)
+            .zip(&trips)
+            .map(|(id, (_, name, class, schedule))| SnapshotTrip {
+                id,
+                name: name.to_string(),
+                class: name_q
+                    .get(class.entity())
+                    .map_or_else(|_| String::new(), Name::to_string),
+                entries: entry_q
+                    .iter_many(schedule.iter())
+                    .filter_map(|entry| {
+                        let station = parent_station_or_station.get(entry.stop()).ok()?;
+                        Some(SnapshotEntry {
+                            station_id: *station_ids.get(&station.parent())?,
+                            arrival: entry.estimate.map(|it| it.arr.0),
+                            departure: entry.estimate.map(|it| it.dep.0),
+                            derived: entry.is_derived(),
+                        })
+                    })
+                    .collect(),
+            })
+            .collect(),
+    };
+    (
+        snapshot,
+        stations.into_iter().map(|(entity, _)| entity).collect(),
+        trips.into_iter().map(|(entity, ..)| entity).collect(),
+    )
+}
+
+/// Check that every ID refers to a station or trip, and that created IDs are not taken.
+fn validate(commands: &[WasmCommand], stations: usize, trips: usize) -> eros::Result<()> {
+    let mut station_ids: HashSet<u32> = (0..stations as u32).collect();
+    let mut trip_ids: HashSet<u32> = (0..trips as u32).collect();
+    for command in commands {
+        match command {
+            WasmCommand::CreateStation { id, .. } => {
+                if !station_ids.insert(*id) {
+                    bail!("Station ID {} is already taken", id);
+                }
+            }
+            WasmCommand::CreateTrip { id, .. } => {
+                if !trip_ids.insert(*id) {
+                    bail!("Trip ID {} is already taken", id);
+                }
+            }
+            WasmCommand::CreateEntry {
+                trip_id,
+                station_id,
+                ..
+            } => {
+                if !trip_ids.contains(trip_id) {
+                    bail!("There is no trip with ID {}", trip_id);
+                }
+                if !station_ids.contains(station_id) {
+                    bail!("There is no station with ID {}", station_id);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check that the stations and trips from the snapshot that entries are added to still exist.
+/// They may have been removed while the plugin was running.
+fn check_targets(
+    world: &World,
+    commands: &[WasmCommand],
+    stations: &[Entity],
+    trips: &[Entity],
+) -> Result<(), String> {
+    for command in commands {
+        let WasmCommand::CreateEntry {
+            trip_id,
+            station_id,
+            ..
+        } = command
+        else {
+            continue;
+        };
+        if let Some(&station) = stations.get(*station_id as usize)
+            && world.get_entity(station).is_err()
+        {
+            return Err(format!(
+                "Station ID {station_id} was removed while the plugin was running"
+            ));
+        }
+        if let Some(&trip) = trips.get(*trip_id as usize)
+            && world.get_entity(trip).is_err()
+        {
+            return Err(format!(
+                "Trip ID {trip_id} was removed while the plugin was running"
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn apply_commands(
+    In((queue, stations, trips)): In<(Vec<WasmCommand>, Vec<Entity>, Vec<Entity>)>,
+    classes: Query<(Instance<Class>, &Name)>,
+    mut graph: ResMut<Graph>,
+    mut commands: Commands,
+    mut add_entries: MessageWriter<AddEntryToTrip>,
+) {
+    let mut station_ids: HashMap<u32, Entity> = (0..).zip(stations).collect();
+    let mut trip_ids: HashMap<u32, Entity> = (0..).zip(trips).collect();
+    let mut class_map: HashMap<String, Instance<Class>> = classes
+        .iter()
+        .map(|(class, name)| (name.to_string(), class))
+        .collect();
+    for command in queue {
+        match command {
+            WasmCommand::CreateStation { name, id } => {
+                let entity = commands.spawn((Station::default(), Name::new(name))).id();
+                graph.add_node(entity);
+                station_ids.insert(id, entity);
+            }
+            WasmCommand::CreateTrip {
+                name,
+                class: class_name,
+                id,
+            } => {
+                let class =
+                    crate::import::make_class(&class_name, &mut class_map, &mut commands, || {
+                        ClassBundle {
+                            class: Class::default(),
+                            name: Name::new(class_name.clone()),
+                            stroke: DisplayedStroke::from_seed(&class_name),
+                        }
+                    });
+                let entity = commands
+                    .spawn(TripBundle::new(
+                        &name,
+                        TripClass(class.entity()),
+                        Vec::new(),
+                    ))
+                    .id();
+                trip_ids.insert(id, entity);
+            }
+            WasmCommand::CreateEntry {
+                trip_id,
+                station_id,
+                arrival,
+                departure,
+            } => {
+                let arr = arrival.map(|it| TravelMode::At(TimetableTime(it)));
+                let dep =
+                    departure.map_or(TravelMode::Flexible, |it| TravelMode::At(TimetableTime(it)));
+                let entry = commands
+                    .spawn(EntryBundle::new(arr, dep, station_ids[&station_id]))
+                    .id();
+                add_entries.write(AddEntryToTrip {
+                    trip: trip_ids[&trip_id],
+                    entry,
+                });
+            }
+        }
+    }
+}
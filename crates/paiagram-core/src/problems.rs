@@ -1,11 +1,545 @@
 //! # Problems
 //! Scan entities and display their problems
 
-use bevy::prelude::*;
+use crate::{
+    entry::{EntryEstimate, EntryQuery},
+    graph::Graph,
+    settings::ProjectSettings,
+    station::ParentStationOrStation,
+    trip::{Trip, TripQuery, TripScenario, TripSchedule, TripService, calendar::ServiceCalendar},
+    units::time::TimetableTime,
+};
+use bevy::tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future::poll_once};
+use bevy::{platform::collections::HashMap, prelude::*};
+use smallvec::{SmallVec, smallvec};
+
+mod circulation;
+mod connection;
+mod running_time;
+mod single_track;
 
 pub struct ProblemsPlugin;
 impl Plugin for ProblemsPlugin {
-    fn build(&self, app: &mut App) {}
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Problems>()
+            .init_resource::<IntervalCheckState>()
+            .init_resource::<single_track::SingleTrackCheckState>()
+            .init_resource::<circulation::CirculationCheckState>()
+            .init_resource::<connection::ConnectionCheckState>()
+            .init_resource::<running_time::RunningTimeCheckState>()
+            .add_systems(
+                Update,
+                (
+                    (
+                        mark_interval_check_dirty,
+                        start_interval_check,
+                        apply_interval_check_task,
+                    )
+                        .chain(),
+                    (
+                        single_track::mark_single_track_check_dirty,
+                        single_track::start_single_track_check,
+                        single_track::apply_single_track_check_task,
+                    )
+                        .chain(),
+                    (
+                        circulation::mark_circulation_check_dirty,
+                        circulation::check_circulations,
+                    )
+                        .chain(),
+                    (
+                        connection::mark_connection_check_dirty,
+                        connection::check_connections,
+                    )
+                        .chain(),
+                    (
+                        running_time::mark_running_time_check_dirty,
+                        running_time::start_running_time_check,
+                        running_time::apply_running_time_check_task,
+                    )
+                        .chain(),
+                ),
+            );
+    }
+}
+
+/// The kind of a problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ProblemKind {
+    /// Two trips running in the same direction follow each other closer than the minimum
+    /// headway.
+    Headway,
+    /// A trip overtakes another trip between two stations.
+    Overtaking,
+    /// Two trips running in opposite directions meet on a single-track section.
+    HeadOnMeet,
+    /// More trips stop at a station at the same time than the station can hold.
+    StationCapacity,
+    /// A vehicle's trip starts at a station that the vehicle cannot reach after its previous
+    /// trip.
+    VehicleConnection,
+    /// A vehicle has less time than the minimum turnaround time between two trips.
+    Turnaround,
+    /// A vehicle's trip starts before its previous trip ends.
+    VehicleOverlap,
+    /// A trip departs before passengers from a guaranteed connection have had time to transfer,
+    /// or either trip of the connection no longer stops at its station.
+    Connection,
+    /// A trip runs between two stops faster than its rolling stock allows.
+    RunningTime,
+}
+
+impl std::fmt::Display for ProblemKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Headway => "Headway",
+            Self::Overtaking => "Overtaking",
+            Self::HeadOnMeet => "Head-on meet",
+            Self::StationCapacity => "Station capacity",
+            Self::VehicleConnection => "Vehicle connection",
+            Self::Turnaround => "Turnaround",
+            Self::VehicleOverlap => "Vehicle overlap",
+            Self::Connection => "Missed connection",
+            Self::RunningTime => "Running time",
+        })
+    }
+}
+
+/// The part of a trip involved in a problem, from one entry to another. Both entries are the
+/// same if the problem happens at a station. If the trip does not stop at the station of the
+/// problem, e.g. for a broken connection, both are the station itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProblemSegment {
+    pub trip: Entity,
+    pub from: Entity,
+    pub to: Entity,
+}
+
+/// A problem in the timetable
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub kind: ProblemKind,
+    pub segments: SmallVec<[ProblemSegment; 2]>,
+    /// When the problem happens
+    pub time: TimetableTime,
+}
+
+impl Problem {
+    pub fn involves_trip(&self, trip: Entity) -> bool {
+        self.segments.iter().any(|it| it.trip == trip)
+    }
+}
+
+/// All problems found in the world. Problems are recalculated in the background, hence they
+/// might be slightly out of date.
+#[derive(Resource, Default)]
+pub struct Problems {
+    items: Vec<Problem>,
+}
+
+impl Problems {
+    pub fn iter(&self) -> impl Iterator<Item = &Problem> {
+        self.items.iter()
+    }
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+    /// Replace all problems of the given kinds with the new ones.
+    pub fn replace(&mut self, kinds: &[ProblemKind], problems: impl IntoIterator<Item = Problem>) {
+        self.items.retain(|it| !kinds.contains(&it.kind));
+        self.items.extend(problems);
+        self.items.sort_by_key(|it| (it.kind, it.time));
+    }
+}
+
+/// Whether problems are still being checked in the background, or are waiting to be checked.
+/// This includes the [`TripSpatialIndex`](crate::trip::TripSpatialIndex) the single-track check
+/// reads from.
+pub fn is_checking(world: &World) -> bool {
+    world
+        .get_resource::<IntervalCheckState>()
+        .is_some_and(|it| it.dirty || it.task.is_some())
+        || world
+            .get_resource::<single_track::SingleTrackCheckState>()
+            .is_some_and(single_track::SingleTrackCheckState::is_pending)
+        || world
+            .get_resource::<running_time::RunningTimeCheckState>()
+            .is_some_and(running_time::RunningTimeCheckState::is_pending)
+        || crate::trip::is_indexing(world)
+}
+
+/// A trip running from one station to an adjacent one
+#[derive(Debug, Clone, Copy)]
+struct Traversal {
+    trip: Entity,
+    /// Index into the list of services
+    service: usize,
+    from: Entity,
+    to: Entity,
+    source: Entity,
+    target: Entity,
+    dep: i32,
+    arr: i32,
+}
+
+impl Traversal {
+    fn segment(&self) -> ProblemSegment {
+        ProblemSegment {
+            trip: self.trip,
+            from: self.from,
+            to: self.to,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct IntervalCheckState {
+    dirty: bool,
+    task: Option<Task<Vec<Problem>>>,
+}
+
+impl Default for IntervalCheckState {
+    fn default() -> Self {
+        Self {
+            dirty: true,
+            task: None,
+        }
+    }
 }
 
-// TODO: do troubleshooting
+const INTERVAL_PROBLEMS: [ProblemKind; 2] = [ProblemKind::Headway, ProblemKind::Overtaking];
+
+fn mark_interval_check_dirty(
+    mut state: ResMut<IntervalCheckState>,
+    changed: Query<
+        (),
+        Or<(
+            Changed<EntryEstimate>,
+            Changed<TripSchedule>,
+            Changed<TripScenario>,
+            Changed<ServiceCalendar>,
+        )>,
+    >,
+    mut removed_estimate: RemovedComponents<EntryEstimate>,
+    mut removed_trips: RemovedComponents<Trip>,
+    settings: Res<ProjectSettings>,
+    graph: Res<Graph>,
+) {
+    if !changed.is_empty()
+        || removed_estimate.read().next().is_some()
+        || removed_trips.read().next().is_some()
+        || settings.is_changed()
+        || graph.is_changed()
+    {
+        state.dirty = true;
+    }
+}
+
+fn start_interval_check(
+    mut state: ResMut<IntervalCheckState>,
+    graph: Res<Graph>,
+    trip_q: Query<TripQuery>,
+    entry_q: Query<EntryQuery>,
+    parent_station_or_station: Query<ParentStationOrStation>,
+    settings: Res<ProjectSettings>,
+) {
+    if !state.dirty || state.task.is_some() {
+        return;
+    }
+    state.dirty = false;
+
+    let mut services = Vec::new();
+    let mut traversals = Vec::new();
+    for trip in trip_q.iter() {
+        let service = services.len();
+        services.push(trip.service());
+        // entry, station, departure time
+        let mut previous: Option<(Entity, Entity, TimetableTime)> = None;
+        for entry in entry_q.iter_many(trip.schedule.iter()) {
+            let (Some(estimate), Ok(station)) =
+                (entry.estimate, parent_station_or_station.get(entry.stop()))
+            else {
+                previous = None;
+                continue;
+            };
+            let station = station.parent();
+            if let Some((from, source, dep)) = previous
+                && source != station
+                && graph.contains_edge(source, station)
+            {
+                traversals.push(Traversal {
+                    trip: trip.entity,
+                    service,
+                    from,
+                    to: entry.entity,
+                    source,
+                    target: station,
+                    dep: dep.0,
+                    arr: estimate.arr.0,
+                });
+            }
+            previous = Some((entry.entity, station, estimate.dep));
+        }
+    }
+
+    let period = settings.repeat_frequency.0;
+    let minimum_headway = settings.minimum_headway.0;
+    state.task = Some(
+        AsyncComputeTaskPool::get()
+            .spawn(async move { check_intervals(traversals, &services, period, minimum_headway) }),
+    );
+}
+
+fn apply_interval_check_task(
+    mut state: ResMut<IntervalCheckState>,
+    mut problems: ResMut<Problems>,
+) {
+    let Some(task) = state.task.as_mut() else {
+        return;
+    };
+    let Some(found) = block_on(poll_once(task)) else {
+        return;
+    };
+    problems.replace(&INTERVAL_PROBLEMS, found);
+    state.task = None;
+}
+
+/// Check all trips running on the same interval pairwise. Trips are sorted by their departure
+/// times, so only trips departing within a window have to be compared.
+fn check_intervals(
+    traversals: Vec<Traversal>,
+    services: &[TripService],
+    period: i32,
+    minimum_headway: i32,
+) -> Vec<Problem> {
+    let mut by_interval: HashMap<(Entity, Entity), Vec<Traversal>> = HashMap::new();
+    for it in traversals {
+        let key = if it.source < it.target {
+            (it.source, it.target)
+        } else {
+            (it.target, it.source)
+        };
+        by_interval.entry(key).or_default().push(it);
+    }
+
+    let mut problems = Vec::new();
+    for (_, mut items) in by_interval {
+        if period > 0 {
+            for it in items.iter_mut() {
+                let shift = it.dep.rem_euclid(period) - it.dep;
+                it.dep += shift;
+                it.arr += shift;
+            }
+        }
+        items.sort_unstable_by_key(|it| it.dep);
+        let window = items
+            .iter()
+            .map(|it| it.arr - it.dep)
+            .max()
+            .unwrap_or(0)
+            .max(0)
+            + minimum_headway;
+        let len = items.len();
+        // repeat the beginning of the cycle at the end, so that trips around the end of the
+        // cycle are compared as well
+        if period > 0 {
+            for idx in 0..len {
+                let it = items[idx];
+                if it.dep > window {
+                    break;
+                }
+                items.push(Traversal {
+                    dep: it.dep + period,
+                    arr: it.arr + period,
+                    ..it
+                });
+            }
+        }
+        for idx in 0..len {
+            let a = &items[idx];
+            for b in &items[idx + 1..] {
+                if b.dep - a.dep > window {
+                    break;
+                }
+                if a.trip == b.trip || !services[a.service].overlaps(&services[b.service]) {
+                    continue;
+                }
+                let Some(kind) = find_conflict(a, b, minimum_headway) else {
+                    continue;
+                };
+                let time = if period > 0 {
+                    b.dep.rem_euclid(period)
+                } else {
+                    b.dep
+                };
+                problems.push(Problem {
+                    kind,
+                    segments: smallvec![a.segment(), b.segment()],
+                    time: TimetableTime(time),
+                });
+            }
+        }
+    }
+    problems
+}
+
+/// Compare two trips on the same interval, where `a` departs no later than `b`. Trips running
+/// in opposite directions are checked per single-track section instead.
+fn find_conflict(a: &Traversal, b: &Traversal, minimum_headway: i32) -> Option<ProblemKind> {
+    if a.source != b.source {
+        return None;
+    }
+    if (b.dep - a.dep).signum() * (b.arr - a.arr).signum() < 0 {
+        return Some(ProblemKind::Overtaking);
+    }
+    if b.dep - a.dep < minimum_headway || (b.arr - a.arr).abs() < minimum_headway {
+        return Some(ProblemKind::Headway);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trip::calendar::Weekdays;
+
+    const HEADWAY: i32 = 180;
+
+    fn time(h: i32, m: i32) -> i32 {
+        TimetableTime::from_hms(h, m, 0).0
+    }
+
+    /// Stations A and B, and the given number of trips.
+    fn entities(trips: usize) -> (Entity, Entity, Vec<Entity>) {
+        let mut world = World::new();
+        let a = world.spawn_empty().id();
+        let b = world.spawn_empty().id();
+        let trips = (0..trips).map(|_| world.spawn_empty().id()).collect();
+        (a, b, trips)
+    }
+
+    /// A trip running from `source` to `target`, using the trip itself as its entries.
+    fn traversal(
+        trip: Entity,
+        service: usize,
+        (source, target): (Entity, Entity),
+        dep: i32,
+        arr: i32,
+    ) -> Traversal {
+        Traversal {
+            trip,
+            service,
+            from: trip,
+            to: trip,
+            source,
+            target,
+            dep,
+            arr,
+        }
+    }
+
+    fn kinds(problems: &[Problem]) -> Vec<ProblemKind> {
+        problems.iter().map(|it| it.kind).collect()
+    }
+
+    #[test]
+    fn trips_following_too_closely() {
+        let (a, b, trips) = entities(3);
+        let traversals = vec![
+            traversal(trips[0], 0, (a, b), time(8, 0), time(8, 10)),
+            traversal(trips[1], 0, (a, b), time(8, 2), time(8, 12)),
+            traversal(trips[2], 0, (a, b), time(8, 30), time(8, 40)),
+        ];
+        let problems = check_intervals(traversals, &[TripService::default()], 0, HEADWAY);
+        assert_eq!(kinds(&problems), [ProblemKind::Headway]);
+        assert!(problems[0].involves_trip(trips[0]) && problems[0].involves_trip(trips[1]));
+        assert_eq!(problems[0].time, TimetableTime(time(8, 2)));
+    }
+
+    #[test]
+    fn trip_overtaking_between_stations() {
+        let (a, b, trips) = entities(2);
+        let traversals = vec![
+            traversal(trips[0], 0, (a, b), time(8, 0), time(8, 20)),
+            traversal(trips[1], 0, (a, b), time(8, 5), time(8, 15)),
+        ];
+        let problems = check_intervals(traversals, &[TripService::default()], 0, HEADWAY);
+        assert_eq!(kinds(&problems), [ProblemKind::Overtaking]);
+    }
+
+    #[test]
+    fn trips_in_opposite_directions_are_not_compared() {
+        let (a, b, trips) = entities(2);
+        let traversals = vec![
+            traversal(trips[0], 0, (a, b), time(8, 0), time(8, 10)),
+            traversal(trips[1], 0, (b, a), time(8, 1), time(8, 11)),
+        ];
+        let problems = check_intervals(traversals, &[TripService::default()], 0, HEADWAY);
+        assert!(problems.is_empty());
+    }
+
+    #[test]
+    fn headway_across_the_repeat_frequency() {
+        let (a, b, trips) = entities(2);
+        let traversals = vec![
+            traversal(trips[0], 0, (a, b), time(8, 59), time(9, 9)),
+            traversal(trips[1], 0, (a, b), time(10, 0), time(10, 10)),
+        ];
+        let services = [TripService::default()];
+        assert!(check_intervals(traversals.clone(), &services, 0, HEADWAY).is_empty());
+        // repeated every hour, the second trip departs a minute after the first one
+        let problems = check_intervals(traversals, &services, time(1, 0), HEADWAY);
+        assert_eq!(kinds(&problems), [ProblemKind::Headway]);
+        assert_eq!(problems[0].time, TimetableTime(0));
+    }
+
+    #[test]
+    fn trips_never_running_together() {
+        let (a, b, trips) = entities(2);
+        let traversals = vec![
+            traversal(trips[0], 0, (a, b), time(8, 0), time(8, 10)),
+            traversal(trips[1], 1, (a, b), time(8, 1), time(8, 11)),
+        ];
+        let calendar = |weekdays| ServiceCalendar {
+            weekdays,
+            ..default()
+        };
+        let calendars = [
+            TripService {
+                scenario: None,
+                calendar: Some(calendar(Weekdays::WEEKDAYS)),
+            },
+            TripService {
+                scenario: None,
+                calendar: Some(calendar(Weekdays::WEEKENDS)),
+            },
+        ];
+        assert!(check_intervals(traversals.clone(), &calendars, 0, HEADWAY).is_empty());
+        let scenarios = [
+            TripService {
+                scenario: Some(a),
+                calendar: None,
+            },
+            TripService {
+                scenario: Some(b),
+                calendar: None,
+            },
+        ];
+        assert!(check_intervals(traversals.clone(), &scenarios, 0, HEADWAY).is_empty());
+        let overlapping = [
+            TripService {
+                scenario: Some(a),
+                calendar: Some(calendar(Weekdays::WEEKDAYS)),
+            },
+            TripService {
+                scenario: None,
+                calendar: Some(calendar(Weekdays::ALL)),
+            },
+        ];
+        let problems = check_intervals(traversals, &overlapping, 0, HEADWAY);
+        assert_eq!(kinds(&problems), [ProblemKind::Headway]);
+    }
+}
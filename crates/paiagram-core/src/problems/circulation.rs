@@ -0,0 +1,180 @@
+//! # Circulation
+//! Check that the trips of each [`Vehicle`] can actually be run one after another.
+//!
+//! Trips are checked in the order they are listed in the vehicle. Trips that never run on the
+//! same day, e.g. trips of different scenarios, are not compared.
+
+use super::{Problem, ProblemKind, ProblemSegment, Problems};
+use crate::{
+    entry::{EntryEstimate, EntryQuery},
+    graph::Graph,
+    interval::IntervalQuery,
+    settings::ProjectSettings,
+    station::{MinimumTurnaround, ParentStationOrStation},
+    trip::{TripQuery, TripScenario, TripSchedule, TripService, calendar::ServiceCalendar},
+    units::time::TimetableTime,
+    vehicle::{Vehicle, scheduling::deadhead_time},
+};
+use bevy::{platform::collections::HashMap, prelude::*};
+use smallvec::smallvec;
+
+pub(super) const CIRCULATION_PROBLEMS: [ProblemKind; 3] = [
+    ProblemKind::VehicleConnection,
+    ProblemKind::Turnaround,
+    ProblemKind::VehicleOverlap,
+];
+
+#[derive(Resource)]
+pub(super) struct CirculationCheckState {
+    dirty: bool,
+}
+
+impl Default for CirculationCheckState {
+    fn default() -> Self {
+        Self { dirty: true }
+    }
+}
+
+pub(super) fn mark_circulation_check_dirty(
+    mut state: ResMut<CirculationCheckState>,
+    changed: Query<
+        (),
+        Or<(
+            Changed<Vehicle>,
+            Changed<EntryEstimate>,
+            Changed<TripSchedule>,
+            Changed<TripScenario>,
+            Changed<ServiceCalendar>,
+            Changed<MinimumTurnaround>,
+        )>,
+    >,
+    mut removed_vehicles: RemovedComponents<Vehicle>,
+    mut removed_turnaround: RemovedComponents<MinimumTurnaround>,
+    settings: Res<ProjectSettings>,
+    graph: Res<Graph>,
+) {
+    if !changed.is_empty()
+        || removed_vehicles.read().next().is_some()
+        || removed_turnaround.read().next().is_some()
+        || settings.is_changed()
+        || graph.is_changed()
+    {
+        state.dirty = true;
+    }
+}
+
+/// The first or last stop of a trip
+#[derive(Clone, Copy)]
+struct TripEnd {
+    entry: Entity,
+    station: Entity,
+    time: TimetableTime,
+}
+
+pub(super) fn check_circulations(
+    mut state: ResMut<CirculationCheckState>,
+    mut problems: ResMut<Problems>,
+    vehicle_q: Query<&Vehicle>,
+    trip_q: Query<TripQuery>,
+    entry_q: Query<EntryQuery>,
+    parent_station_or_station: Query<ParentStationOrStation>,
+    turnaround_q: Query<&MinimumTurnaround>,
+    interval_q: Query<IntervalQuery>,
+    graph: Res<Graph>,
+    settings: Res<ProjectSettings>,
+) {
+    if !state.dirty {
+        return;
+    }
+    state.dirty = false;
+
+    let trip_end = |entry: Entity, departure: bool| -> Option<TripEnd> {
+        let item = entry_q.get(entry).ok()?;
+        let estimate = item.estimate?;
+        Some(TripEnd {
+            entry,
+            station: parent_station_or_station.get(item.stop()).ok()?.parent(),
+            time: if departure {
+                estimate.dep
+            } else {
+                estimate.arr
+            },
+        })
+    };
+    let mut deadheads: HashMap<(Entity, Entity), Option<i32>> = HashMap::new();
+
+    let mut found = Vec::new();
+    for vehicle in &vehicle_q {
+        // trip, service, first stop, last stop
+        let trips: Vec<(Entity, TripService, TripEnd, TripEnd)> = trip_q
+            .iter_many(vehicle.trips.iter())
+            .filter_map(|trip| {
+                let first = trip_end(*trip.schedule.first()?, true)?;
+                let last = trip_end(*trip.schedule.last()?, false)?;
+                Some((trip.entity, trip.service(), first, last))
+            })
+            .collect();
+        for (idx, (trip, service, first, _)) in trips.iter().enumerate() {
+            // the previous trip that runs on the same day
+            let Some((previous, _, _, last)) = trips[..idx]
+                .iter()
+                .rev()
+                .find(|(_, other, ..)| other.overlaps(service))
+            else {
+                continue;
+            };
+            let segments = smallvec![
+                ProblemSegment {
+                    trip: *previous,
+                    from: last.entry,
+                    to: last.entry,
+                },
+                ProblemSegment {
+                    trip: *trip,
+                    from: first.entry,
+                    to: first.entry,
+                },
+            ];
+            if first.time < last.time {
+                found.push(Problem {
+                    kind: ProblemKind::VehicleOverlap,
+                    segments,
+                    time: first.time,
+                });
+                continue;
+            }
+            let Some(deadhead) = *deadheads
+                .entry((last.station, first.station))
+                .or_insert_with(|| {
+                    deadhead_time(
+                        &graph,
+                        &interval_q,
+                        settings.deadhead_speed,
+                        last.station,
+                        first.station,
+                    )
+                    .map(|it| it.0)
+                })
+            else {
+                found.push(Problem {
+                    kind: ProblemKind::VehicleConnection,
+                    segments,
+                    time: last.time,
+                });
+                continue;
+            };
+            let turnaround = turnaround_q
+                .get(last.station)
+                .map_or(settings.minimum_turnaround, |it| it.0);
+            if (first.time - last.time).0 < turnaround.0 + deadhead {
+                found.push(Problem {
+                    kind: ProblemKind::Turnaround,
+                    segments,
+                    time: last.time,
+                });
+            }
+        }
+    }
+
+    problems.replace(&CIRCULATION_PROBLEMS, found);
+}
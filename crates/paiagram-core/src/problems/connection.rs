@@ -0,0 +1,115 @@
+//! # Connection
+//! Check that every [`TripConnection`] leaves passengers enough time to transfer. Connections
+//! where either trip no longer stops at the station are reported as well.
+
+use super::{Problem, ProblemKind, ProblemSegment, Problems};
+use crate::{
+    entry::{EntryEstimate, EntryQuery, EntryStop},
+    station::ParentStationOrStation,
+    trip::{TripSchedule, connection::TripConnection},
+    units::time::TimetableTime,
+};
+use bevy::prelude::*;
+use smallvec::smallvec;
+
+pub(super) const CONNECTION_PROBLEMS: [ProblemKind; 1] = [ProblemKind::Connection];
+
+#[derive(Resource)]
+pub(super) struct ConnectionCheckState {
+    dirty: bool,
+}
+
+impl Default for ConnectionCheckState {
+    fn default() -> Self {
+        Self { dirty: true }
+    }
+}
+
+pub(super) fn mark_connection_check_dirty(
+    mut state: ResMut<ConnectionCheckState>,
+    changed: Query<
+        (),
+        Or<(
+            Changed<TripConnection>,
+            Changed<EntryEstimate>,
+            Changed<EntryStop>,
+            Changed<TripSchedule>,
+        )>,
+    >,
+    mut removed_connections: RemovedComponents<TripConnection>,
+    mut removed_estimates: RemovedComponents<EntryEstimate>,
+) {
+    if !changed.is_empty()
+        || removed_connections.read().next().is_some()
+        || removed_estimates.read().next().is_some()
+    {
+        state.dirty = true;
+    }
+}
+
+pub(super) fn check_connections(
+    mut state: ResMut<ConnectionCheckState>,
+    mut problems: ResMut<Problems>,
+    connection_q: Query<&TripConnection>,
+    schedule_q: Query<&TripSchedule>,
+    entry_q: Query<EntryQuery>,
+    parent_station_or_station: Query<ParentStationOrStation>,
+) {
+    if !state.dirty {
+        return;
+    }
+    state.dirty = false;
+
+    let mut found = Vec::new();
+    for connection in &connection_q {
+        let feeder = connection.feeder_entry(&schedule_q, &entry_q, &parent_station_or_station);
+        let connecting =
+            connection.connecting_entry(&schedule_q, &entry_q, &parent_station_or_station);
+        let estimate = |entry: Option<Entity>| entry.and_then(|it| entry_q.get(it).ok()?.estimate);
+        let (Some(feeder), Some(connecting)) = (feeder, connecting) else {
+            // trips that do not stop at the station are shown at the station itself
+            let segment = |trip: Entity, entry: Option<Entity>| ProblemSegment {
+                trip,
+                from: entry.unwrap_or(connection.station),
+                to: entry.unwrap_or(connection.station),
+            };
+            let time = estimate(feeder)
+                .map(|it| it.arr)
+                .or_else(|| estimate(connecting).map(|it| it.dep))
+                .unwrap_or(TimetableTime(0));
+            found.push(Problem {
+                kind: ProblemKind::Connection,
+                segments: smallvec![
+                    segment(connection.feeder, feeder),
+                    segment(connection.connecting, connecting),
+                ],
+                time,
+            });
+            continue;
+        };
+        let (Some(arrival), Some(departure)) = (estimate(Some(feeder)), estimate(Some(connecting)))
+        else {
+            continue;
+        };
+        if arrival.arr + connection.transfer_time > departure.dep {
+            found.push(Problem {
+                kind: ProblemKind::Connection,
+                segments: smallvec![
+                    ProblemSegment {
+                        trip: connection.feeder,
+                        from: feeder,
+                        to: feeder,
+                    },
+                    ProblemSegment {
+                        trip: connection.connecting,
+                        from: connecting,
+                        to: connecting,
+                    },
+                ],
+                time: arrival.arr,
+            });
+        }
+    }
+
+    problems.replace(&CONNECTION_PROBLEMS, found);
+}
@@ -0,0 +1,195 @@
+//! # Running Time
+//! Check that trips are not timed faster than the minimum running time of their
+//! [`RollingStock`].
+//!
+//! Each run between two stops is checked as a whole, since trains pass the stations in between
+//! without stopping. Trips without rolling stock are not checked.
+
+use super::{Problem, ProblemKind, ProblemSegment, Problems};
+use crate::{
+    entry::{EntryEstimate, EntryQuery},
+    graph::Graph,
+    interval::{Interval, IntervalQuery},
+    settings::ProjectSettings,
+    station::ParentStationOrStation,
+    trip::{
+        Trip, TripQuery, TripSchedule, TripVehicles,
+        running_time::{Leg, RollingStock},
+    },
+    units::time::{Duration, TimetableTime},
+};
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future::poll_once};
+use smallvec::smallvec;
+
+pub(super) const RUNNING_TIME_PROBLEMS: [ProblemKind; 1] = [ProblemKind::RunningTime];
+
+#[derive(Resource)]
+pub(super) struct RunningTimeCheckState {
+    dirty: bool,
+    task: Option<Task<Vec<Problem>>>,
+}
+
+impl Default for RunningTimeCheckState {
+    fn default() -> Self {
+        Self {
+            dirty: true,
+            task: None,
+        }
+    }
+}
+
+impl RunningTimeCheckState {
+    pub(super) fn is_pending(&self) -> bool {
+        self.dirty || self.task.is_some()
+    }
+}
+
+pub(super) fn mark_running_time_check_dirty(
+    mut state: ResMut<RunningTimeCheckState>,
+    changed: Query<
+        (),
+        Or<(
+            Changed<EntryEstimate>,
+            Changed<TripSchedule>,
+            Changed<TripVehicles>,
+            Changed<RollingStock>,
+            Changed<Interval>,
+        )>,
+    >,
+    mut removed_estimate: RemovedComponents<EntryEstimate>,
+    mut removed_trips: RemovedComponents<Trip>,
+    mut removed_stock: RemovedComponents<RollingStock>,
+    settings: Res<ProjectSettings>,
+    graph: Res<Graph>,
+) {
+    if !changed.is_empty()
+        || removed_estimate.read().next().is_some()
+        || removed_trips.read().next().is_some()
+        || removed_stock.read().next().is_some()
+        || settings.is_changed()
+        || graph.is_changed()
+    {
+        state.dirty = true;
+    }
+}
+
+/// A trip running from one stop to the next
+struct Run {
+    trip: Entity,
+    from: Entity,
+    to: Entity,
+    dep: TimetableTime,
+    scheduled: Duration,
+    legs: Vec<Leg>,
+    stock: RollingStock,
+}
+
+pub(super) fn start_running_time_check(
+    mut state: ResMut<RunningTimeCheckState>,
+    graph: Res<Graph>,
+    trip_q: Query<TripQuery>,
+    entry_q: Query<EntryQuery>,
+    stock_q: Query<&RollingStock>,
+    interval_q: Query<IntervalQuery>,
+    parent_station_or_station: Query<ParentStationOrStation>,
+    settings: Res<ProjectSettings>,
+) {
+    if !state.dirty || state.task.is_some() {
+        return;
+    }
+    state.dirty = false;
+
+    let mut runs = Vec::new();
+    for trip in trip_q.iter() {
+        // the performance of a vehicle takes precedence over the performance of the class
+        let Some(stock) = trip
+            .vehicles
+            .iter()
+            .find_map(|it| stock_q.get(*it).ok())
+            .or_else(|| stock_q.get(trip.class.entity()).ok())
+        else {
+            continue;
+        };
+        let entries: Vec<_> = entry_q.iter_many(trip.schedule.iter()).collect();
+        // the entry and the departure where the run starts, the legs so far, and the station
+        let mut current: Option<(Entity, TimetableTime, Vec<Leg>, Entity)> = None;
+        for (idx, entry) in entries.iter().enumerate() {
+            let (Some(estimate), Ok(station)) =
+                (entry.estimate, parent_station_or_station.get(entry.stop()))
+            else {
+                current = None;
+                continue;
+            };
+            let station = station.parent();
+            let leg = current.as_ref().and_then(|(.., previous)| {
+                let interval = graph.edge_weight(*previous, station)?;
+                interval_q.get(*interval).ok().map(|it| it.leg())
+            });
+            // start again where the run cannot be followed through the graph
+            let Some(leg) = leg else {
+                current = Some((entry.entity, estimate.dep, Vec::new(), station));
+                continue;
+            };
+            if let Some((_, _, legs, previous)) = current.as_mut() {
+                legs.push(leg);
+                *previous = station;
+            }
+            // derived entries and entries without an arrival are passed without stopping, even
+            // where a stop has no dwell time
+            let passes = entry.is_derived() || entry.mode.arr.is_none();
+            if passes && idx + 1 < entries.len() {
+                continue;
+            }
+            if let Some((from, dep, legs, _)) = current.take() {
+                runs.push(Run {
+                    trip: trip.entity,
+                    from,
+                    to: entry.entity,
+                    dep,
+                    scheduled: estimate.arr - dep,
+                    legs,
+                    stock: *stock,
+                });
+            }
+            current = Some((entry.entity, estimate.dep, Vec::new(), station));
+        }
+    }
+
+    let period = settings.repeat_frequency.0;
+    state.task =
+        Some(AsyncComputeTaskPool::get().spawn(async move { check_running_times(runs, period) }));
+}
+
+pub(super) fn apply_running_time_check_task(
+    mut state: ResMut<RunningTimeCheckState>,
+    mut problems: ResMut<Problems>,
+) {
+    let Some(task) = state.task.as_mut() else {
+        return;
+    };
+    let Some(found) = block_on(poll_once(task)) else {
+        return;
+    };
+    problems.replace(&RUNNING_TIME_PROBLEMS, found);
+    state.task = None;
+}
+
+fn check_running_times(runs: Vec<Run>, period: i32) -> Vec<Problem> {
+    runs.into_iter()
+        .filter(|run| run.scheduled < run.stock.running_time(&run.legs))
+        .map(|run| Problem {
+            kind: ProblemKind::RunningTime,
+            segments: smallvec![ProblemSegment {
+                trip: run.trip,
+                from: run.from,
+                to: run.to,
+            }],
+            time: if period > 0 {
+                TimetableTime(run.dep.0.rem_euclid(period))
+            } else {
+                run.dep
+            },
+        })
+        .collect()
+}
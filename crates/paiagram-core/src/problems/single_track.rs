@@ -0,0 +1,508 @@
+//! # Single Track
+//! Check single-track sections and station capacities with the [`TripSpatialIndex`].
+//!
+//! A section is a chain of single-track intervals between stations where opposing trips can
+//! meet. Stations with a [`StationCapacity`] of 1 do not split sections.
+
+use super::{Problem, ProblemKind, ProblemSegment, Problems};
+use crate::{
+    entry::EntryQuery,
+    graph::{Graph, Node},
+    interval::Interval,
+    settings::ProjectSettings,
+    station::{ParentStationOrStation, StationCapacity},
+    trip::{
+        TripQuery, TripScenario, TripService, TripSpatialIndex, TripSpatialIndexItem,
+        calendar::ServiceCalendar,
+    },
+    units::time::TimetableTime,
+};
+use bevy::tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future::poll_once};
+use bevy::{
+    ecs::entity::{EntityHashMap, EntityHashSet},
+    platform::collections::HashSet,
+    prelude::*,
+};
+use smallvec::{SmallVec, smallvec};
+
+pub(super) const SINGLE_TRACK_PROBLEMS: [ProblemKind; 2] =
+    [ProblemKind::HeadOnMeet, ProblemKind::StationCapacity];
+
+/// Times closer than this are treated as equal, in seconds.
+const EPSILON: f64 = 0.5;
+
+#[derive(Resource)]
+pub(super) struct SingleTrackCheckState {
+    dirty: bool,
+    task: Option<Task<Vec<Problem>>>,
+}
+
+impl Default for SingleTrackCheckState {
+    fn default() -> Self {
+        Self {
+            dirty: true,
+            task: None,
+        }
+    }
+}
+
+impl SingleTrackCheckState {
+    pub(super) fn is_pending(&self) -> bool {
+        self.dirty || self.task.is_some()
+    }
+}
+
+pub(super) fn mark_single_track_check_dirty(
+    mut state: ResMut<SingleTrackCheckState>,
+    index: Res<TripSpatialIndex>,
+    changed: Query<
+        (),
+        Or<(
+            Changed<Interval>,
+            Changed<StationCapacity>,
+            Changed<TripScenario>,
+            Changed<ServiceCalendar>,
+        )>,
+    >,
+    mut removed_capacity: RemovedComponents<StationCapacity>,
+    settings: Res<ProjectSettings>,
+    graph: Res<Graph>,
+) {
+    if index.is_changed()
+        || !changed.is_empty()
+        || removed_capacity.read().next().is_some()
+        || settings.is_changed()
+        || graph.is_changed()
+    {
+        state.dirty = true;
+    }
+}
+
+/// A single-track section
+#[derive(Default)]
+struct Section {
+    /// Unordered station pairs of the intervals in this section
+    intervals: HashSet<(Entity, Entity)>,
+    /// Position of each station along the section
+    positions: EntityHashMap<usize>,
+}
+
+/// A trip running through a section without leaving it
+struct Occupation {
+    trip: Entity,
+    from: Entity,
+    to: Entity,
+    start: f64,
+    end: f64,
+    /// Whether the trip runs towards the stations with higher positions
+    forward: bool,
+}
+
+/// The stops at a station with a limited capacity, as trip, entry, arrival and departure
+struct StationStops {
+    capacity: StationCapacity,
+    stops: Vec<(Entity, Entity, f64, f64)>,
+}
+
+pub(super) fn start_single_track_check(
+    mut state: ResMut<SingleTrackCheckState>,
+    index: Res<TripSpatialIndex>,
+    graph: Res<Graph>,
+    interval_q: Query<&Interval>,
+    capacity_q: Query<(Entity, &StationCapacity)>,
+    node_q: Query<&Node>,
+    trip_q: Query<TripQuery>,
+    entry_q: Query<EntryQuery>,
+    parent_station_or_station: Query<ParentStationOrStation>,
+    settings: Res<ProjectSettings>,
+) {
+    if !state.dirty || state.task.is_some() {
+        return;
+    }
+    state.dirty = false;
+
+    let station_of = |entry: Entity| -> Option<Entity> {
+        let entry = entry_q.get(entry).ok()?;
+        Some(parent_station_or_station.get(entry.stop()).ok()?.parent())
+    };
+    let mut services: EntityHashMap<TripService> = EntityHashMap::default();
+    let mut add_service = |trip: Entity| {
+        if !services.contains_key(&trip)
+            && let Ok(it) = trip_q.get(trip)
+        {
+            services.insert(trip, it.service());
+        }
+    };
+
+    // traversals of each section, and whether they run towards higher positions
+    let mut sections: Vec<Vec<(TripSpatialIndexItem, bool)>> = Vec::new();
+    for section in find_sections(&graph, &interval_q, &capacity_q) {
+        let coordinates: Vec<[f64; 2]> = section
+            .positions
+            .keys()
+            .filter_map(|it| node_q.get(*it).ok())
+            .map(|it| it.coor.to_xy_arr())
+            .collect();
+        let Some((x_range, y_range)) = bounding_box(&coordinates) else {
+            continue;
+        };
+        let mut items: Vec<(TripSpatialIndexItem, bool)> = index
+            .query_xy_time(x_range, y_range, f64::MIN..=f64::MAX)
+            .filter_map(|it| {
+                let source = station_of(it.entry0)?;
+                let target = station_of(it.entry1)?;
+                section
+                    .intervals
+                    .contains(&unordered(source, target))
+                    .then(|| (it, section.positions[&target] > section.positions[&source]))
+            })
+            .collect();
+        items.sort_unstable_by(|(a, _), (b, _)| a.trip.cmp(&b.trip).then(a.t0.total_cmp(&b.t0)));
+        for (item, _) in &items {
+            add_service(item.trip);
+        }
+        sections.push(items);
+    }
+
+    let mut stations = Vec::new();
+    for (station, capacity) in &capacity_q {
+        let Ok(node) = node_q.get(station) else {
+            continue;
+        };
+        let [x, y] = node.coor.to_xy_arr();
+        let mut stops: Vec<(Entity, Entity, f64, f64)> = index
+            .query_xy_time(x..=x, y..=y, f64::MIN..=f64::MAX)
+            .filter(|it| station_of(it.entry0) == Some(station))
+            .map(|it| (it.trip, it.entry0, it.t0, it.t1))
+            .collect();
+        stops.sort_unstable_by(|a, b| a.2.total_cmp(&b.2));
+        stops.dedup_by(|a, b| a.0 == b.0 && a.1 == b.1 && (a.2 - b.2).abs() < EPSILON);
+        for stop in &stops {
+            add_service(stop.0);
+        }
+        stations.push(StationStops {
+            capacity: *capacity,
+            stops,
+        });
+    }
+
+    let period = settings.repeat_frequency.0 as f64;
+    state.task = Some(
+        AsyncComputeTaskPool::get()
+            .spawn(async move { check_single_track(sections, stations, services, period) }),
+    );
+}
+
+pub(super) fn apply_single_track_check_task(
+    mut state: ResMut<SingleTrackCheckState>,
+    mut problems: ResMut<Problems>,
+) {
+    let Some(task) = state.task.as_mut() else {
+        return;
+    };
+    let Some(found) = block_on(poll_once(task)) else {
+        return;
+    };
+    problems.replace(&SINGLE_TRACK_PROBLEMS, found);
+    state.task = None;
+}
+
+fn check_single_track(
+    sections: Vec<Vec<(TripSpatialIndexItem, bool)>>,
+    stations: Vec<StationStops>,
+    services: EntityHashMap<TripService>,
+    period: f64,
+) -> Vec<Problem> {
+    let normalize = |t: f64| {
+        if period > 0.0 {
+            t.rem_euclid(period)
+        } else {
+            t
+        }
+    };
+
+    let mut found = Vec::new();
+
+    for items in sections {
+        // join consecutive traversals of the same trip
+        let mut occupations: Vec<Occupation> = Vec::new();
+        let mut previous: Option<TripSpatialIndexItem> = None;
+        for (item, forward) in items {
+            if let Some(last) = occupations.last_mut()
+                && let Some(prev) = previous
+                && prev.trip == item.trip
+                && prev.entry1 == item.entry0
+                && (item.t0 - prev.t2).abs() < EPSILON
+                && last.forward == forward
+            {
+                last.to = item.entry1;
+                last.end = item.t2;
+            } else {
+                occupations.push(Occupation {
+                    trip: item.trip,
+                    from: item.entry0,
+                    to: item.entry1,
+                    start: item.t1,
+                    end: item.t2,
+                    forward,
+                });
+            }
+            previous = Some(item);
+        }
+        occupations.sort_unstable_by(|a, b| a.start.total_cmp(&b.start));
+
+        let mut reported = HashSet::new();
+        for (idx, a) in occupations.iter().enumerate() {
+            for b in &occupations[idx + 1..] {
+                if b.start >= a.end {
+                    break;
+                }
+                if a.trip == b.trip || a.forward == b.forward {
+                    continue;
+                }
+                let (Some(service_a), Some(service_b)) =
+                    (services.get(&a.trip), services.get(&b.trip))
+                else {
+                    continue;
+                };
+                if !service_a.overlaps(service_b) {
+                    continue;
+                }
+                let time = normalize(b.start);
+                if !reported.insert((a.trip, b.trip, time as i32)) {
+                    continue;
+                }
+                found.push(Problem {
+                    kind: ProblemKind::HeadOnMeet,
+                    segments: smallvec![
+                        ProblemSegment {
+                            trip: a.trip,
+                            from: a.from,
+                            to: a.to,
+                        },
+                        ProblemSegment {
+                            trip: b.trip,
+                            from: b.from,
+                            to: b.to,
+                        },
+                    ],
+                    time: TimetableTime(time as i32),
+                });
+            }
+        }
+    }
+
+    for StationStops { capacity, stops } in stations {
+        let mut active: Vec<(Entity, Entity, f64, f64)> = Vec::new();
+        for stop in stops {
+            active.retain(|it| it.3 >= stop.2);
+            let Some(service) = services.get(&stop.0) else {
+                continue;
+            };
+            let occupying: SmallVec<[ProblemSegment; 2]> = active
+                .iter()
+                .filter(|it| {
+                    it.0 != stop.0
+                        && services
+                            .get(&it.0)
+                            .is_some_and(|other| other.overlaps(service))
+                })
+                .chain(std::iter::once(&stop))
+                .map(|it| ProblemSegment {
+                    trip: it.0,
+                    from: it.1,
+                    to: it.1,
+                })
+                .collect();
+            if occupying.len() > capacity.0 as usize {
+                found.push(Problem {
+                    kind: ProblemKind::StationCapacity,
+                    segments: occupying,
+                    time: TimetableTime(normalize(stop.2) as i32),
+                });
+            }
+            active.push(stop);
+        }
+    }
+
+    found
+}
+
+/// Split the single-track intervals of the graph into sections.
+fn find_sections(
+    graph: &Graph,
+    interval_q: &Query<&Interval>,
+    capacity_q: &Query<(Entity, &StationCapacity)>,
+) -> Vec<Section> {
+    let mut adjacency: EntityHashMap<Vec<Entity>> = EntityHashMap::default();
+    let mut single_track = HashSet::new();
+    for (source, target, interval) in graph.all_edges() {
+        let Ok(interval) = interval_q.get(*interval) else {
+            continue;
+        };
+        if interval.is_single_track() && single_track.insert(unordered(source, target)) {
+            adjacency.entry(source).or_default().push(target);
+            adjacency.entry(target).or_default().push(source);
+        }
+    }
+    let allows_meeting = |station: Entity| {
+        capacity_q
+            .get(station)
+            .map_or(true, |(_, capacity)| capacity.allows_meeting())
+    };
+
+    let mut visited: HashSet<(Entity, Entity)> = HashSet::new();
+    let mut sections = Vec::new();
+    // start from section ends, so that positions follow the line
+    let mut starts: Vec<Entity> = adjacency.keys().copied().collect();
+    starts.sort_by_key(|it| allows_meeting(*it) || adjacency[it].len() == 1);
+    for start in starts.into_iter().rev() {
+        for next in adjacency[&start].clone() {
+            if visited.contains(&unordered(start, next)) {
+                continue;
+            }
+            let mut section = Section::default();
+            section.positions.insert(start, 0);
+            let mut stack = vec![(start, next)];
+            let mut seen = EntityHashSet::default();
+            seen.insert(start);
+            while let Some((from, to)) = stack.pop() {
+                if !visited.insert(unordered(from, to)) {
+                    continue;
+                }
+                section.intervals.insert(unordered(from, to));
+                let position = section.positions[&from] + 1;
+                section.positions.entry(to).or_insert(position);
+                if allows_meeting(to) || !seen.insert(to) {
+                    continue;
+                }
+                for further in &adjacency[&to] {
+                    if *further != from {
+                        stack.push((to, *further));
+                    }
+                }
+            }
+            sections.push(section);
+        }
+    }
+    sections
+}
+
+fn unordered(a: Entity, b: Entity) -> (Entity, Entity) {
+    if a < b { (a, b) } else { (b, a) }
+}
+
+fn bounding_box(
+    coordinates: &[[f64; 2]],
+) -> Option<(std::ops::RangeInclusive<f64>, std::ops::RangeInclusive<f64>)> {
+    let first = coordinates.first()?;
+    let (mut x0, mut y0, mut x1, mut y1) = (first[0], first[1], first[0], first[1]);
+    for [x, y] in coordinates {
+        x0 = x0.min(*x);
+        y0 = y0.min(*y);
+        x1 = x1.max(*x);
+        y1 = y1.max(*y);
+    }
+    Some((x0..=x1, y0..=y1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::distance::Distance;
+
+    /// Stations on a line, joined by intervals with the given numbers of tracks.
+    fn line(world: &mut World, tracks: &[u8]) -> Vec<Entity> {
+        let stations: Vec<Entity> = (0..=tracks.len())
+            .map(|_| world.spawn_empty().id())
+            .collect();
+        let mut graph = Graph::default();
+        for (pair, tracks) in stations.windows(2).zip(tracks) {
+            for (source, target) in [(pair[0], pair[1]), (pair[1], pair[0])] {
+                let interval = world
+                    .spawn(Interval {
+                        tracks: *tracks,
+                        ..Interval::new(Distance(1000))
+                    })
+                    .id();
+                graph.add_edge(source, target, interval);
+            }
+        }
+        world.insert_resource(graph);
+        stations
+    }
+
+    /// Find the sections of the graph in the world.
+    fn find(world: &mut World) -> Vec<Section> {
+        world
+            .run_system_cached(
+                |graph: Res<Graph>,
+                 interval_q: Query<&Interval>,
+                 capacity_q: Query<(Entity, &StationCapacity)>| {
+                    find_sections(&graph, &interval_q, &capacity_q)
+                },
+            )
+            .unwrap()
+    }
+
+    /// Sections as their station pairs, sorted.
+    fn sections(world: &mut World) -> Vec<Vec<(Entity, Entity)>> {
+        let mut sections: Vec<Vec<(Entity, Entity)>> = find(world)
+            .into_iter()
+            .map(|section| {
+                let mut intervals: Vec<_> = section.intervals.into_iter().collect();
+                intervals.sort();
+                intervals
+            })
+            .collect();
+        sections.sort();
+        sections
+    }
+
+    #[test]
+    fn stations_split_sections() {
+        let mut world = World::new();
+        let s = line(&mut world, &[1, 1, 1]);
+        let mut expected = vec![
+            vec![unordered(s[0], s[1])],
+            vec![unordered(s[1], s[2])],
+            vec![unordered(s[2], s[3])],
+        ];
+        expected.sort();
+        assert_eq!(sections(&mut world), expected);
+    }
+
+    #[test]
+    fn stations_without_passing_loops_join_sections() {
+        let mut world = World::new();
+        let s = line(&mut world, &[1, 1, 1]);
+        world.entity_mut(s[1]).insert(StationCapacity(1));
+        world.entity_mut(s[3]).insert(StationCapacity(1));
+        let mut first = vec![unordered(s[0], s[1]), unordered(s[1], s[2])];
+        first.sort();
+        let mut expected = vec![first, vec![unordered(s[2], s[3])]];
+        expected.sort();
+        assert_eq!(sections(&mut world), expected);
+    }
+
+    #[test]
+    fn positions_follow_the_line() {
+        let mut world = World::new();
+        let s = line(&mut world, &[1, 1]);
+        world.entity_mut(s[1]).insert(StationCapacity(1));
+        let sections = find(&mut world);
+        assert_eq!(sections.len(), 1);
+        let positions = &sections[0].positions;
+        assert_eq!(positions[&s[1]], 1);
+        assert_eq!(positions[&s[0]].abs_diff(positions[&s[2]]), 2);
+    }
+
+    #[test]
+    fn double_track_intervals_are_skipped() {
+        let mut world = World::new();
+        let s = line(&mut world, &[2, 1, 2]);
+        world.entity_mut(s[1]).insert(StationCapacity(1));
+        world.entity_mut(s[2]).insert(StationCapacity(1));
+        assert_eq!(sections(&mut world), [vec![unordered(s[1], s[2])]]);
+    }
+}
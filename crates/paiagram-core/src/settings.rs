@@ -1,7 +1,10 @@
 //! # Settings
 //! Module for user preferences and project settings.
 
-use crate::{i18n::Language, units::time::Duration};
+use crate::{
+    i18n::Language,
+    units::{distance::Distance, speed::Velocity, time::Duration},
+};
 use bevy::prelude::*;
 
 #[derive(Reflect, Copy, Clone, Debug, PartialEq, Eq)]
@@ -92,6 +95,52 @@ pub struct ProjectSettings {
     pub remarks: String,
     pub authors: Vec<String>,
     pub repeat_frequency: Duration,
+    /// The minimum time between two trips running in the same direction on an interval.
+    #[reflect(default = "default_minimum_headway")]
+    pub minimum_headway: Duration,
+    /// The minimum time between a vehicle arriving and departing again. Stations may override
+    /// this with [`crate::station::MinimumTurnaround`].
+    #[reflect(default = "default_minimum_turnaround")]
+    pub minimum_turnaround: Duration,
+    /// The speed of empty runs between stations. Vehicles may only continue from the station
+    /// where their previous trip ends if this is `None`.
+    #[reflect(default)]
+    pub deadhead_speed: Option<Velocity>,
+    /// The minimum time passengers need to change between trips. Stations may override this
+    /// with [`crate::station::MinimumTransferTime`].
+    #[reflect(default = "default_minimum_transfer_time")]
+    pub minimum_transfer_time: Duration,
+    /// Stations closer to each other than this distance are linked by footpaths in route
+    /// finding, in addition to [`crate::station::Footpaths`].
+    #[reflect(default)]
+    pub walking_distance: Option<Distance>,
+    /// The speed passengers walk along footpaths found by [`Self::walking_distance`].
+    #[reflect(default = "default_walking_speed")]
+    pub walking_speed: Velocity,
+    /// The IANA time zone of the timetable, e.g. `Europe/Berlin`, written to exported GTFS feeds.
+    /// UTC is used if empty.
+    #[reflect(default)]
+    pub time_zone: String,
+    /// The website of the operator, written to exported GTFS feeds. The Paiagram website is used
+    /// if empty.
+    #[reflect(default)]
+    pub agency_url: String,
+}
+
+fn default_minimum_headway() -> Duration {
+    Duration::from_secs(120)
+}
+
+fn default_minimum_turnaround() -> Duration {
+    Duration::from_secs(300)
+}
+
+fn default_minimum_transfer_time() -> Duration {
+    Duration::from_secs(120)
+}
+
+fn default_walking_speed() -> Velocity {
+    Velocity::from_kmh(4.5)
 }
 
 impl Default for ProjectSettings {
@@ -100,6 +149,14 @@ impl Default for ProjectSettings {
             remarks: String::new(),
             authors: Vec::new(),
             repeat_frequency: Duration::from_secs(86400),
+            minimum_headway: default_minimum_headway(),
+            minimum_turnaround: default_minimum_turnaround(),
+            deadhead_speed: None,
+            minimum_transfer_time: default_minimum_transfer_time(),
+            walking_distance: None,
+            walking_speed: default_walking_speed(),
+            time_zone: String::new(),
+            agency_url: String::new(),
         }
     }
 }
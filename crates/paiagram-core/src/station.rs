@@ -3,6 +3,7 @@ use std::borrow::Cow;
 use crate::{
     graph::{Graph, Node, NodeCoor},
     trip::class::DisplayedStroke,
+    units::time::Duration,
 };
 use bevy::{ecs::query::QueryData, prelude::*};
 use moonshine_core::prelude::{MapEntities, ReflectMapEntities};
@@ -47,6 +48,45 @@ pub struct IsDepot;
 #[require(Name, Platform, PlatformEntries, Platforms, DisplayedStroke)]
 pub struct Station;
 
+/// Number of trips a station can hold at the same time. Stations without this component are
+/// treated as having unlimited capacity. Opposing trips on single-track intervals can only meet
+/// at stations with a capacity of at least 2.
+#[derive(Reflect, Component, Clone, Copy, Debug, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct StationCapacity(pub u8);
+
+impl StationCapacity {
+    pub fn allows_meeting(&self) -> bool {
+        self.0 >= 2
+    }
+}
+
+/// The minimum time between a vehicle arriving at this station and departing again. This
+/// overrides [`crate::settings::ProjectSettings::minimum_turnaround`].
+#[derive(Reflect, Component, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct MinimumTurnaround(pub Duration);
+
+/// The minimum time passengers need to change between trips at this station. This overrides
+/// [`crate::settings::ProjectSettings::minimum_transfer_time`].
+#[derive(Reflect, Component, Clone, Copy, Debug)]
+#[reflect(Component)]
+pub struct MinimumTransferTime(pub Duration);
+
+/// A walking link to another station.
+#[derive(Reflect, MapEntities, Clone, Copy, Debug)]
+pub struct Footpath {
+    #[entities]
+    pub to: Entity,
+    pub walking_time: Duration,
+}
+
+/// Walking links from this station to other stations. Links can be walked in both directions.
+#[derive(Default, Reflect, Component, MapEntities, Deref, DerefMut, Debug)]
+#[component(map_entities)]
+#[reflect(Component, MapEntities)]
+pub struct Footpaths(#[entities] pub Vec<Footpath>);
+
 /// Spawn with [`with_children()``]
 #[derive(Bundle)]
 pub struct StationBundle {
@@ -3,7 +3,10 @@ use crate::{
     graph::Node,
     settings::ProjectSettings,
     station::Station,
-    trip::class::{Class, DisplayedStroke},
+    trip::{
+        calendar::{ServiceCalendar, ServiceDate},
+        class::{Class, DisplayedStroke},
+    },
     units::time::Duration,
     vehicle::Vehicle,
 };
@@ -14,8 +17,14 @@ use rstar::{AABB, RTree, RTreeObject};
 use smallvec::SmallVec;
 use std::ops::RangeInclusive;
 
+pub mod calendar;
 pub mod class;
+pub mod connection;
+pub mod frequency;
+pub mod generator;
 pub mod routing;
+pub mod running_time;
+pub mod scenario;
 
 pub struct TripPlugin;
 impl Plugin for TripPlugin {
@@ -32,11 +41,15 @@ impl Plugin for TripPlugin {
                 )
                     .chain(),
             )
+            .add_systems(Update, generator::regenerate_trips)
             .add_observer(update_nominal_schedule)
             .add_observer(convert_derived_entry_to_explicit)
             .add_observer(update_add_trip_vehicles)
             .add_observer(update_remove_trip_vehicles)
-            .add_observer(update_remove_vehicle_trips);
+            .add_observer(update_remove_vehicle_trips)
+            .add_observer(crate::vehicle::scheduling::schedule_vehicles)
+            .add_observer(generator::generate_trips)
+            .add_observer(connection::remove_trip_connections);
     }
 }
 
@@ -120,6 +133,13 @@ impl Default for TripSpatialIndexState {
     }
 }
 
+/// Whether the [`TripSpatialIndex`] is being rebuilt in the background, or is waiting to be rebuilt.
+pub fn is_indexing(world: &World) -> bool {
+    world
+        .get_resource::<TripSpatialIndexState>()
+        .is_some_and(|it| it.dirty || it.task.is_some())
+}
+
 // TODO: replace the dirty method with specific updates
 fn mark_trip_spatial_index_dirty(
     mut state: ResMut<TripSpatialIndexState>,
@@ -337,6 +357,13 @@ pub struct TripVehicles(#[entities] pub SmallVec<[Entity; 1]>);
 #[require(Name)]
 pub struct TripClass(#[entities] pub Entity);
 
+/// The scenario the trip belongs to
+#[derive(Reflect, Component, MapEntities, Deref, DerefMut)]
+#[component(map_entities)]
+#[reflect(Component, MapEntities)]
+#[relationship(relationship_target = scenario::Scenario)]
+pub struct TripScenario(#[entities] pub Entity);
+
 #[derive(Reflect, Component, MapEntities, Deref, DerefMut)]
 #[component(map_entities)]
 #[reflect(Component, MapEntities)]
@@ -361,6 +388,9 @@ pub struct TripQuery {
     pub name: &'static Name,
     pub class: &'static TripClass,
     pub schedule: &'static TripSchedule,
+    pub calendar: Option<&'static ServiceCalendar>,
+    pub scenario: Option<&'static TripScenario>,
+    pub frequency: Option<&'static frequency::TripFrequency>,
 }
 
 impl<'w, 's> TripQueryItem<'w, 's> {
@@ -377,6 +407,61 @@ impl<'w, 's> TripQueryItem<'w, 's> {
     pub fn stroke<'a>(&self, q: &Query<'a, 'a, &DisplayedStroke, With<Class>>) -> DisplayedStroke {
         q.get(self.class.entity()).unwrap().clone()
     }
+    /// Whether the trip runs on the given service day. Trips without a calendar run on every
+    /// day, and every trip passes when no service day is given.
+    pub fn runs_on(&self, service_day: Option<ServiceDate>) -> bool {
+        match (service_day, self.calendar) {
+            (Some(date), Some(calendar)) => calendar.runs_on(date),
+            _ => true,
+        }
+    }
+    /// Offsets of all runs of the trip from its own times. Trips without a
+    /// [`frequency::TripFrequency`] run once, at their own times.
+    pub fn run_offsets(&self, q: &Query<&entry::EntryEstimate>) -> SmallVec<[Duration; 1]> {
+        let Some(frequency) = self.frequency else {
+            return SmallVec::from_buf([Duration::ZERO]);
+        };
+        let Some(first) = self.schedule.first().and_then(|it| q.get(*it).ok()) else {
+            return SmallVec::new();
+        };
+        frequency.offsets(first.dep).collect()
+    }
+    /// The scenario and calendar of the trip.
+    pub fn service(&self) -> TripService {
+        TripService {
+            scenario: self.scenario.map(|it| it.0),
+            calendar: self.calendar.cloned(),
+        }
+    }
+    /// Whether the trip belongs to the given scenario. Every trip passes when no scenario is
+    /// given.
+    pub fn is_in_scenario(&self, scenario: Option<Entity>) -> bool {
+        scenario.is_none_or(|scenario| self.scenario.is_some_and(|it| it.0 == scenario))
+    }
+}
+
+/// The scenario and calendar of a trip, detached from the world so that it could be moved to
+/// background tasks.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TripService {
+    pub scenario: Option<Entity>,
+    pub calendar: Option<ServiceCalendar>,
+}
+
+impl TripService {
+    /// Whether two trips may run at the same time, i.e. they are not in different scenarios,
+    /// and their calendars may overlap.
+    pub fn overlaps(&self, other: &Self) -> bool {
+        if let (Some(a), Some(b)) = (self.scenario, other.scenario)
+            && a != b
+        {
+            return false;
+        }
+        match (&self.calendar, &other.calendar) {
+            (Some(a), Some(b)) => a.may_overlap(b),
+            _ => true,
+        }
+    }
 }
 
 fn update_nominal_schedule(
@@ -0,0 +1,328 @@
+//! # Calendar
+//! Operating calendars (service days) of trips. A trip without a [`ServiceCalendar`] is treated
+//! as running on every day.
+
+use bevy::prelude::*;
+use chrono::{Datelike, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+/// Number of days between 0001-01-01 (CE) and 1970-01-01.
+const UNIX_EPOCH_DAYS_FROM_CE: i32 = 719_163;
+
+/// A calendar date, stored as the number of days since 1970-01-01.
+#[derive(
+    Reflect,
+    Debug,
+    Deserialize,
+    Serialize,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
+pub struct ServiceDate(pub i32);
+
+impl ServiceDate {
+    pub fn from_naive_date(date: NaiveDate) -> Self {
+        Self(date.num_days_from_ce() - UNIX_EPOCH_DAYS_FROM_CE)
+    }
+    pub fn to_naive_date(self) -> Option<NaiveDate> {
+        NaiveDate::from_num_days_from_ce_opt(self.0 + UNIX_EPOCH_DAYS_FROM_CE)
+    }
+    /// The day of week, where Monday is 0 and Sunday is 6.
+    #[inline]
+    pub fn weekday(self) -> u8 {
+        // 1970-01-01 is a Thursday
+        (self.0 + 3).rem_euclid(7) as u8
+    }
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64
+    }
+    pub fn from_f64(v: f64) -> Self {
+        Self(v.round() as i32)
+    }
+}
+
+impl std::str::FromStr for ServiceDate {
+    type Err = chrono::ParseError;
+    /// Parse a date in `YYYY-MM-DD` or `YYYYMMDD` format.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .or_else(|_| NaiveDate::parse_from_str(s, "%Y%m%d"))
+            .map(Self::from_naive_date)
+    }
+}
+
+impl std::fmt::Display for ServiceDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.to_naive_date() {
+            Some(date) => write!(f, "{}", date.format("%Y-%m-%d")),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+/// A set of weekdays. Bit 0 is Monday and bit 6 is Sunday.
+#[derive(Reflect, Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct Weekdays(pub u8);
+
+impl Default for Weekdays {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl Weekdays {
+    pub const NONE: Self = Self(0);
+    pub const ALL: Self = Self(0b111_1111);
+    pub const WEEKDAYS: Self = Self(0b001_1111);
+    pub const WEEKENDS: Self = Self(0b110_0000);
+
+    /// Build the set from the Monday to Sunday flags.
+    pub fn from_flags(flags: [bool; 7]) -> Self {
+        Self(
+            flags
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (idx, set)| acc | ((*set as u8) << idx)),
+        )
+    }
+    #[inline]
+    pub fn contains(self, weekday: u8) -> bool {
+        self.0 & (1 << weekday) != 0
+    }
+    pub fn set(&mut self, weekday: u8, value: bool) {
+        if value {
+            self.0 |= 1 << weekday;
+        } else {
+            self.0 &= !(1 << weekday);
+        }
+    }
+    /// The weekdays the given number of days later.
+    pub fn shifted(self, days: i32) -> Self {
+        let mut shifted = Self::NONE;
+        for weekday in (0..7).filter(|it| self.contains(*it)) {
+            shifted.set((weekday as i32 + days).rem_euclid(7) as u8, true);
+        }
+        shifted
+    }
+}
+
+impl std::fmt::Display for Weekdays {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const NAMES: [&str; 7] = ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"];
+        for (idx, name) in NAMES.iter().enumerate() {
+            if self.contains(idx as u8) {
+                f.write_str(name)?;
+            } else {
+                f.write_str("--")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An inclusive range of dates.
+#[derive(Reflect, Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct DateRange {
+    pub start: ServiceDate,
+    pub end: ServiceDate,
+}
+
+impl DateRange {
+    #[inline]
+    pub fn contains(&self, date: ServiceDate) -> bool {
+        self.start <= date && date <= self.end
+    }
+}
+
+/// The operating calendar of a trip.
+///
+/// A trip runs on a date if the date is explicitly added, or if the date's weekday is in
+/// [`Self::weekdays`] and the date falls into one of [`Self::ranges`] (an empty list of ranges
+/// means the calendar is unbounded), unless the date is explicitly removed.
+#[derive(Reflect, Component, Debug, Default, Clone, PartialEq)]
+#[reflect(Component)]
+pub struct ServiceCalendar {
+    pub weekdays: Weekdays,
+    pub ranges: Vec<DateRange>,
+    /// Dates on which the trip runs regardless of the weekday mask and ranges.
+    pub added: Vec<ServiceDate>,
+    /// Dates on which the trip does not run.
+    pub removed: Vec<ServiceDate>,
+}
+
+impl ServiceCalendar {
+    /// Whether the calendar is active on the given date.
+    pub fn runs_on(&self, date: ServiceDate) -> bool {
+        if self.removed.contains(&date) {
+            return false;
+        }
+        if self.added.contains(&date) {
+            return true;
+        }
+        self.weekdays.contains(date.weekday())
+            && (self.ranges.is_empty() || self.ranges.iter().any(|it| it.contains(date)))
+    }
+    /// The calendar with every date moved by the given number of days.
+    pub fn shifted(&self, days: i32) -> Self {
+        let shift = |date: &ServiceDate| ServiceDate(date.0 + days);
+        Self {
+            weekdays: self.weekdays.shifted(days),
+            ranges: self
+                .ranges
+                .iter()
+                .map(|it| DateRange {
+                    start: shift(&it.start),
+                    end: shift(&it.end),
+                })
+                .collect(),
+            added: self.added.iter().map(shift).collect(),
+            removed: self.removed.iter().map(shift).collect(),
+        }
+    }
+    /// Whether the two calendars may be active on the same day. Removed dates are not taken
+    /// into account, hence this may return `true` for calendars that never overlap.
+    pub fn may_overlap(&self, other: &Self) -> bool {
+        if self.added.iter().any(|it| other.runs_on(*it))
+            || other.added.iter().any(|it| self.runs_on(*it))
+        {
+            return true;
+        }
+        if self.weekdays.0 & other.weekdays.0 == 0 {
+            return false;
+        }
+        if self.ranges.is_empty() || other.ranges.is_empty() {
+            return true;
+        }
+        self.ranges.iter().any(|a| {
+            other
+                .ranges
+                .iter()
+                .any(|b| a.start <= b.end && b.start <= a.end)
+        })
+    }
+}
+
+impl std::fmt::Display for ServiceCalendar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.weekdays)?;
+        for range in &self.ranges {
+            write!(f, ", {}–{}", range.start, range.end)?;
+        }
+        if !self.added.is_empty() {
+            write!(f, ", +{}", self.added.len())?;
+        }
+        if !self.removed.is_empty() {
+            write!(f, ", −{}", self.removed.len())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> ServiceDate {
+        s.parse().unwrap()
+    }
+
+    fn range(start: &str, end: &str) -> DateRange {
+        DateRange {
+            start: date(start),
+            end: date(end),
+        }
+    }
+
+    /// Weekdays in the first half of 2025
+    fn weekdays() -> ServiceCalendar {
+        ServiceCalendar {
+            weekdays: Weekdays::WEEKDAYS,
+            ranges: vec![range("2025-01-01", "2025-06-30")],
+            ..default()
+        }
+    }
+
+    #[test]
+    fn weekday_of_date() {
+        // 1970-01-01 is a Thursday, and 2025-01-06 is a Monday
+        assert_eq!(ServiceDate(0).weekday(), 3);
+        assert_eq!(date("2025-01-06").weekday(), 0);
+        assert_eq!(date("20250112").weekday(), 6);
+    }
+
+    #[test]
+    fn runs_on_weekdays_within_ranges() {
+        let calendar = weekdays();
+        assert!(calendar.runs_on(date("2025-01-06")));
+        // Sunday
+        assert!(!calendar.runs_on(date("2025-01-12")));
+        // out of range
+        assert!(!calendar.runs_on(date("2025-07-07")));
+        // an empty list of ranges is unbounded
+        let unbounded = ServiceCalendar {
+            ranges: Vec::new(),
+            ..calendar
+        };
+        assert!(unbounded.runs_on(date("2030-07-08")));
+    }
+
+    #[test]
+    fn added_and_removed_dates() {
+        let calendar = ServiceCalendar {
+            added: vec![date("2025-01-12"), date("2025-08-01")],
+            removed: vec![date("2025-01-06"), date("2025-08-01")],
+            ..weekdays()
+        };
+        assert!(calendar.runs_on(date("2025-01-12")));
+        assert!(!calendar.runs_on(date("2025-01-06")));
+        // removed dates win over added dates
+        assert!(!calendar.runs_on(date("2025-08-01")));
+    }
+
+    #[test]
+    fn shifted_calendar_runs_a_day_later() {
+        let calendar = weekdays().shifted(1);
+        // Tuesday to Saturday
+        assert!(!calendar.runs_on(date("2025-01-06")));
+        assert!(calendar.runs_on(date("2025-01-11")));
+        assert!(calendar.runs_on(date("2025-07-01")));
+    }
+
+    #[test]
+    fn calendars_overlap_on_shared_weekdays_and_dates() {
+        let weekends = ServiceCalendar {
+            weekdays: Weekdays::WEEKENDS,
+            ..weekdays()
+        };
+        assert!(!weekdays().may_overlap(&weekends));
+        assert!(weekdays().may_overlap(&ServiceCalendar::default()));
+        // a weekend date added to the weekday calendar
+        let with_holiday = ServiceCalendar {
+            added: vec![date("2025-01-12")],
+            ..weekdays()
+        };
+        assert!(with_holiday.may_overlap(&weekends));
+        assert!(weekends.may_overlap(&with_holiday));
+    }
+
+    #[test]
+    fn calendars_in_different_ranges_never_overlap() {
+        let second_half = ServiceCalendar {
+            ranges: vec![range("2025-07-01", "2025-12-31")],
+            ..weekdays()
+        };
+        assert!(!weekdays().may_overlap(&second_half));
+        let summer = ServiceCalendar {
+            ranges: vec![range("2025-06-30", "2025-08-31")],
+            ..weekdays()
+        };
+        assert!(weekdays().may_overlap(&summer));
+    }
+}
@@ -0,0 +1,103 @@
+//! # Connection
+//! Guaranteed connections between two trips at a station.
+//!
+//! A connection is its own entity. The connecting trip is expected to wait for the feeder trip,
+//! so that passengers always have at least the transfer time between the feeder's arrival and
+//! the connecting departure.
+
+use crate::{
+    entry::EntryQuery,
+    station::ParentStationOrStation,
+    trip::{Trip, TripSchedule},
+    units::time::Duration,
+};
+use bevy::prelude::*;
+use moonshine_core::prelude::{MapEntities, ReflectMapEntities};
+
+/// The connecting trip waits for the feeder trip at the station.
+#[derive(Reflect, Component, MapEntities, Debug, Clone, PartialEq)]
+#[component(map_entities)]
+#[reflect(Component, MapEntities)]
+pub struct TripConnection {
+    /// The trip passengers transfer from
+    #[entities]
+    pub feeder: Entity,
+    /// The trip passengers transfer to
+    #[entities]
+    pub connecting: Entity,
+    /// The station where passengers transfer. Platforms of the station are also accepted.
+    #[entities]
+    pub station: Entity,
+    /// The minimum time between the feeder's arrival and the connecting departure
+    pub transfer_time: Duration,
+}
+
+impl TripConnection {
+    /// Whether the entry stops at the station of the connection.
+    fn is_at_station(
+        &self,
+        entry: Entity,
+        entry_q: &Query<EntryQuery>,
+        parent_station_or_station: &Query<ParentStationOrStation>,
+    ) -> bool {
+        entry_q
+            .get(entry)
+            .ok()
+            .and_then(|it| parent_station_or_station.get(it.stop()).ok())
+            .is_some_and(|it| it.parent() == self.station)
+    }
+    /// The feeder's last entry at the station, if the feeder stops there.
+    pub fn feeder_entry(
+        &self,
+        schedule_q: &Query<&TripSchedule>,
+        entry_q: &Query<EntryQuery>,
+        parent_station_or_station: &Query<ParentStationOrStation>,
+    ) -> Option<Entity> {
+        schedule_q
+            .get(self.feeder)
+            .ok()?
+            .iter()
+            .rfind(|it| self.is_at_station(**it, entry_q, parent_station_or_station))
+            .copied()
+    }
+    /// The connecting trip's first entry at the station, if the connecting trip stops there.
+    pub fn connecting_entry(
+        &self,
+        schedule_q: &Query<&TripSchedule>,
+        entry_q: &Query<EntryQuery>,
+        parent_station_or_station: &Query<ParentStationOrStation>,
+    ) -> Option<Entity> {
+        schedule_q
+            .get(self.connecting)
+            .ok()?
+            .iter()
+            .find(|it| self.is_at_station(**it, entry_q, parent_station_or_station))
+            .copied()
+    }
+    /// The feeder's last entry and the connecting trip's first entry at the station, if both
+    /// trips stop there.
+    pub fn entries(
+        &self,
+        schedule_q: &Query<&TripSchedule>,
+        entry_q: &Query<EntryQuery>,
+        parent_station_or_station: &Query<ParentStationOrStation>,
+    ) -> Option<(Entity, Entity)> {
+        Some((
+            self.feeder_entry(schedule_q, entry_q, parent_station_or_station)?,
+            self.connecting_entry(schedule_q, entry_q, parent_station_or_station)?,
+        ))
+    }
+}
+
+/// Remove the connections of deleted trips.
+pub fn remove_trip_connections(
+    event: On<Remove, Trip>,
+    connection_q: Query<(Entity, &TripConnection)>,
+    mut commands: Commands,
+) {
+    for (entity, connection) in &connection_q {
+        if connection.feeder == event.entity || connection.connecting == event.entity {
+            commands.entity(entity).despawn();
+        }
+    }
+}
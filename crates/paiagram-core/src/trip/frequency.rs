@@ -0,0 +1,80 @@
+//! # Frequency
+//! Trips that run repeatedly at a fixed headway, as in GTFS `frequencies.txt`.
+//!
+//! A frequency-based trip is stored once. Its own times act as a template, and each run is the
+//! template shifted so that it departs from the first stop at the run's departure time. Runs are
+//! expanded virtually wherever they are displayed or searched, without spawning entities.
+
+use crate::units::time::{Duration, TimetableTime};
+use bevy::prelude::*;
+
+/// A time window in which a trip departs from its first stop at a fixed headway.
+#[derive(Reflect, Debug, Clone, Copy, PartialEq)]
+pub struct FrequencyPeriod {
+    /// The first departure
+    pub start: TimetableTime,
+    /// Runs depart before this time
+    pub end: TimetableTime,
+    /// Time between two consecutive departures. Periods without a positive headway only run
+    /// once, at [`Self::start`].
+    pub headway: Duration,
+}
+
+impl FrequencyPeriod {
+    fn departures(self) -> impl Iterator<Item = TimetableTime> {
+        let mut next = Some(self.start);
+        std::iter::from_fn(move || {
+            let current = next?;
+            next = (self.headway.0 > 0)
+                .then(|| current + self.headway)
+                .filter(|it| *it < self.end);
+            Some(current)
+        })
+    }
+    /// The earliest departure no earlier than `at`.
+    fn earliest_departure(self, at: TimetableTime) -> Option<TimetableTime> {
+        let headway = self.headway.0;
+        let departure = if at <= self.start {
+            self.start.0
+        } else if headway > 0 {
+            self.start.0 + (at.0 - self.start.0 + headway - 1) / headway * headway
+        } else {
+            return None;
+        };
+        (departure == self.start.0 || departure < self.end.0).then_some(TimetableTime(departure))
+    }
+}
+
+/// Runs the trip at the departure times of the periods, instead of once at its own times.
+#[derive(Reflect, Component, Debug, Clone, Default, PartialEq)]
+#[reflect(Component)]
+pub struct TripFrequency {
+    pub periods: Vec<FrequencyPeriod>,
+}
+
+impl TripFrequency {
+    /// Departure times of all runs from the first stop.
+    pub fn departures(&self) -> impl Iterator<Item = TimetableTime> + '_ {
+        self.periods.iter().flat_map(|it| it.departures())
+    }
+    /// Offsets of all runs from the template, which departs from its first stop at `template`.
+    pub fn offsets(&self, template: TimetableTime) -> impl Iterator<Item = Duration> + '_ {
+        self.departures().map(move |it| it - template)
+    }
+    /// Offset of the earliest run passing a stop no earlier than `at`. The template departs from
+    /// its first stop at `template`, and passes the stop at `time`.
+    pub fn earliest_offset(
+        &self,
+        template: TimetableTime,
+        time: TimetableTime,
+        at: TimetableTime,
+    ) -> Option<Duration> {
+        // the departure from the first stop needed to pass the stop at `at`
+        let needed = at - (time - template);
+        self.periods
+            .iter()
+            .filter_map(|it| it.earliest_departure(needed))
+            .min()
+            .map(|it| it - template)
+    }
+}
@@ -0,0 +1,242 @@
+//! # Generator
+//! Generate clock-face services from a template trip.
+//!
+//! The copies are shifted from the template by multiples of a fixed headway, and only copies
+//! departing within a time window are kept. Fixed times are shifted, while durations and
+//! flexible times are kept as is. Copies edited by hand are kept when the copies are generated
+//! again, and no new copy is generated in their place.
+
+use super::{
+    TripBundle, TripClass, TripNominalSchedule, TripQuery, TripScenario, calendar::ServiceCalendar,
+};
+use crate::{
+    entry::{EntryBundle, EntryEstimate, EntryMode, EntryStop, TravelMode},
+    units::time::{Duration, TimetableTime},
+};
+use bevy::{ecs::entity::EntityHashSet, prelude::*};
+use moonshine_core::prelude::{MapEntities, ReflectMapEntities};
+
+/// Hard limit of copies generated from one template
+const MAX_COPIES: i32 = 1000;
+
+/// Settings for generating copies of the trip. Add this to the template trip.
+#[derive(Reflect, Component, Debug, Clone, PartialEq)]
+#[reflect(Component)]
+pub struct TripGenerator {
+    /// Time between two consecutive copies
+    pub headway: Duration,
+    /// Copies departing from their first stop before this time are not generated
+    pub window_start: TimetableTime,
+    /// Copies departing from their first stop after this time are not generated
+    pub window_end: TimetableTime,
+    /// Name of the copies. `{name}` is replaced by the template's name, and `{n}` is replaced by
+    /// the copy's number.
+    pub name_pattern: String,
+    /// Number of the first copy
+    pub first_number: u32,
+    /// Difference between the numbers of consecutive copies
+    pub number_step: u32,
+    /// Generate the copies again whenever the template changes
+    pub regenerate: bool,
+}
+
+impl TripGenerator {
+    /// A generator covering one repeat cycle, as set in [`crate::settings::ProjectSettings`].
+    pub fn new(repeat_frequency: Duration) -> Self {
+        Self {
+            headway: Duration::from_secs(900),
+            window_start: TimetableTime(0),
+            window_end: TimetableTime(repeat_frequency.0),
+            name_pattern: "{name}-{n}".to_string(),
+            first_number: 1,
+            number_step: 1,
+            regenerate: true,
+        }
+    }
+    fn name(&self, template: &str, number: u32) -> String {
+        self.name_pattern
+            .replace("{name}", template)
+            .replace("{n}", &number.to_string())
+    }
+}
+
+/// The template trip a trip was generated from.
+#[derive(Reflect, Component, MapEntities, Deref, DerefMut)]
+#[component(map_entities)]
+#[reflect(Component, MapEntities)]
+#[relationship(relationship_target = GeneratedTrips)]
+pub struct GeneratedFrom(#[entities] pub Entity);
+
+/// Trips generated from this template trip.
+#[derive(Default, Reflect, Component, MapEntities)]
+#[reflect(Component, MapEntities)]
+#[relationship_target(relationship = GeneratedFrom)]
+pub struct GeneratedTrips {
+    #[relationship]
+    #[entities]
+    trips: Vec<Entity>,
+}
+
+impl GeneratedTrips {
+    pub fn as_trips(&self) -> &[Entity] {
+        self.trips.as_slice()
+    }
+}
+
+/// The number of headways a generated trip is shifted from its template.
+#[derive(Reflect, Component, Debug, Clone, Copy, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct CopyIndex(pub i32);
+
+/// Marker component for generated trips edited by hand. Edited copies are not replaced when
+/// generating the copies again.
+#[derive(Reflect, Component, Default)]
+#[reflect(Component)]
+pub struct IsEditedCopy;
+
+/// Replace the trips generated from the template trip with new copies, following the template's
+/// [`TripGenerator`]. Copies marked with [`IsEditedCopy`] are kept.
+#[derive(Debug, EntityEvent)]
+pub struct GenerateTrips {
+    pub entity: Entity,
+}
+
+pub fn generate_trips(
+    event: On<GenerateTrips>,
+    template_q: Query<(
+        TripQuery,
+        &TripGenerator,
+        &TripNominalSchedule,
+        Option<&GeneratedTrips>,
+    )>,
+    entry_q: Query<(&EntryMode, &EntryStop, Option<&EntryEstimate>)>,
+    copy_q: Query<(Has<IsEditedCopy>, Option<&CopyIndex>)>,
+    mut commands: Commands,
+) {
+    let Ok((template, generator, nominal, generated)) = template_q.get(event.entity) else {
+        return;
+    };
+    // positions of the copies that are kept
+    let mut kept: Vec<i32> = Vec::new();
+    for copy in generated.iter().flat_map(|it| it.as_trips()) {
+        match copy_q.get(*copy) {
+            Ok((true, index)) => kept.extend(index.map(|it| it.0)),
+            _ => commands.entity(*copy).despawn(),
+        }
+    }
+    let headway = generator.headway.0;
+    if headway <= 0 {
+        return;
+    }
+    // the departure from the first stop
+    let Some(departure) = nominal.first().and_then(|it| {
+        let (mode, _, estimate) = entry_q.get(*it).ok()?;
+        match (estimate, mode.dep) {
+            (Some(estimate), _) => Some(estimate.dep),
+            (None, TravelMode::At(t)) => Some(t),
+            (None, _) => None,
+        }
+    }) else {
+        warn!("Cannot generate trips from a template without a departure time");
+        return;
+    };
+    let first = (generator.window_start.0 - departure.0).div_euclid(headway)
+        + ((generator.window_start.0 - departure.0).rem_euclid(headway) != 0) as i32;
+    let last = (generator.window_end.0 - departure.0).div_euclid(headway);
+    let last = last.min(first.saturating_add(MAX_COPIES - 1));
+
+    let mut number = generator.first_number;
+    for k in (first..=last).filter(|it| *it != 0) {
+        if kept.contains(&k) {
+            number = number.saturating_add(generator.number_step);
+            continue;
+        }
+        let offset = Duration(k * headway);
+        let entries: Vec<Entity> = entry_q
+            .iter_many(nominal.iter())
+            .map(|(mode, stop, _)| {
+                let mut mode = *mode;
+                if matches!(mode.arr, Some(TravelMode::At(_))) {
+                    mode.shift_arr(offset);
+                }
+                if matches!(mode.dep, TravelMode::At(_)) {
+                    mode.shift_dep(offset);
+                }
+                commands
+                    .spawn(EntryBundle::new(mode.arr, mode.dep, stop.0))
+                    .id()
+            })
+            .collect();
+        let mut copy = commands.spawn_empty();
+        copy.add_children(&entries).insert((
+            TripBundle::new(
+                &generator.name(template.name.as_str(), number),
+                TripClass(template.class.0),
+                entries,
+            ),
+            GeneratedFrom(template.entity),
+            CopyIndex(k),
+        ));
+        if let Some(calendar) = template.calendar {
+            copy.insert(calendar.clone());
+        }
+        if let Some(scenario) = template.scenario {
+            copy.insert(TripScenario(scenario.0));
+        }
+        number = number.saturating_add(generator.number_step);
+    }
+}
+
+/// Regenerate the copies of templates that have been edited, and mark copies that have been edited
+/// so that they are kept.
+pub fn regenerate_trips(
+    changed_templates: Query<
+        (Entity, &TripGenerator),
+        Or<(
+            Changed<TripGenerator>,
+            Changed<TripNominalSchedule>,
+            Changed<TripClass>,
+            Changed<ServiceCalendar>,
+            Changed<TripScenario>,
+            Changed<Name>,
+        )>,
+    >,
+    changed_entries: Query<
+        (&ChildOf, Ref<EntryMode>, Ref<EntryStop>),
+        Or<(Changed<EntryMode>, Changed<EntryStop>)>,
+    >,
+    renamed_copies: Query<(Entity, Ref<Name>), (With<GeneratedFrom>, Changed<Name>)>,
+    generator_q: Query<(Entity, Ref<TripGenerator>)>,
+    copy_q: Query<(), (With<GeneratedFrom>, Without<IsEditedCopy>)>,
+    mut commands: Commands,
+) {
+    let mut pending: EntityHashSet = changed_templates
+        .iter()
+        .filter(|(_, generator)| generator.regenerate)
+        .map(|(entity, _)| entity)
+        .collect();
+    // entries of newly loaded or generated trips are not edits
+    for (parent, mode, stop) in &changed_entries {
+        if !((mode.is_changed() && !mode.is_added()) || (stop.is_changed() && !stop.is_added())) {
+            continue;
+        }
+        let trip = parent.parent();
+        if let Ok((template, generator)) = generator_q.get(trip)
+            && generator.regenerate
+        {
+            pending.insert(template);
+        } else if copy_q.contains(trip) {
+            commands.entity(trip).insert(IsEditedCopy);
+        }
+    }
+    for (copy, name) in &renamed_copies {
+        if !name.is_added() && copy_q.contains(copy) {
+            commands.entity(copy).insert(IsEditedCopy);
+        }
+    }
+    // templates that have just been loaded are not edits either
+    pending.retain(|it| generator_q.get(*it).is_ok_and(|(_, it)| !it.is_added()));
+    for template in pending {
+        commands.trigger(GenerateTrips { entity: template });
+    }
+}
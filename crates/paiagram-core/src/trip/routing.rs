@@ -1,16 +1,18 @@
-use bevy::prelude::*;
+use bevy::{platform::collections::HashSet, prelude::*};
 use itertools::Itertools;
 
 use crate::{
     entry::{DerivedEntryBundle, EntryEstimate, EntryMode, EntryStop, IsDerivedEntry, TravelMode},
     graph::Graph,
-    interval::IntervalQuery,
+    interval::{Interval, IntervalQuery},
     station::ParentStationOrStation,
-    trip::{TripClass, TripNominalSchedule, TripQuery, TripSchedule},
-    units::{
-        distance::Distance,
-        time::{Duration, TimetableTime},
+    trip::{
+        Trip, TripClass, TripNominalSchedule, TripQuery, TripSchedule,
+        class::Class,
+        running_time::{Leg, RollingStock},
     },
+    units::time::{Duration, TimetableTime},
+    vehicle::Vehicle,
 };
 
 pub struct RoutingPlugin;
@@ -116,6 +118,43 @@ fn recalculate_inner(
     }
 }
 
+/// Two consecutive entries of a trip whose stations are not connected in the graph
+#[derive(Debug, Clone, Copy)]
+pub struct UnroutableLeg {
+    pub trip: Entity,
+    pub from: Entity,
+    pub to: Entity,
+}
+
+/// Find the legs that [`recalculate_route`] cannot fill with derived entries.
+pub fn find_unroutable_legs(
+    trips: Query<(Entity, &TripNominalSchedule)>,
+    entry_q: Query<(Entity, &EntryStop)>,
+    graph: Res<Graph>,
+    parent_station_or_station: Query<ParentStationOrStation>,
+    interval_q: Query<IntervalQuery>,
+) -> Vec<UnroutableLeg> {
+    let mut legs = Vec::new();
+    for (trip, schedule) in &trips {
+        let stops = entry_q
+            .iter_many(schedule.iter())
+            .filter_map(|(entry, stop)| {
+                let station = parent_station_or_station.get(stop.entity()).ok()?;
+                Some((entry, station.parent()))
+            });
+        for ((from, source), (to, target)) in stops.tuple_windows() {
+            if source == target
+                || graph.contains_edge(source, target)
+                || graph.route_between(source, target, &interval_q).is_some()
+            {
+                continue;
+            }
+            legs.push(UnroutableLeg { trip, from, to });
+        }
+    }
+    legs
+}
+
 /// Parameters used for unwinding the flexible stack.
 enum UnwindParams {
     At(TimetableTime),
@@ -123,26 +162,90 @@ enum UnwindParams {
     ForFor(Duration, Duration),
 }
 
+/// The legs between consecutive stations, or `None` if two of them are not adjacent.
+fn legs_between(
+    stops: impl Iterator<Item = Entity>,
+    graph: &Graph,
+    parent_station_or_station: &Query<ParentStationOrStation>,
+    interval_q: &Query<IntervalQuery>,
+) -> Option<Vec<Leg>> {
+    stops
+        .map(|e| parent_station_or_station.get(e).unwrap().parent())
+        .tuple_windows()
+        .map(|(ps, cs)| {
+            let weight = graph.edge_weight(ps, cs).copied()?;
+            interval_q.get(weight).ok().map(|it| it.leg())
+        })
+        .collect()
+}
+
 /// Recalculate the estimates for updated routes.
 /// This should always run after [`recalculate_route`].
+///
+/// Flexible entries share the time between the surrounding stable entries. The time is split by
+/// the minimum running times if the trip has [`RollingStock`], or by distance otherwise. Running
+/// durations entered by the user are kept as they are, even if shorter than the minimum running
+/// time. Flexible entries after the last stable entry are timed by the minimum running times if
+/// the trip has [`RollingStock`], and have no estimates otherwise.
 fn recalculate_estimate(
     changed_trips: Query<Entity, (Changed<TripSchedule>, With<TripClass>)>,
     changed_entries: Query<&ChildOf, Changed<EntryMode>>,
+    changed_stock: Query<(Option<&Class>, Option<&Vehicle>), Changed<RollingStock>>,
+    changed_intervals: Query<Entity, Changed<Interval>>,
+    all_trips: Query<(Entity, &TripSchedule), (With<Trip>, With<TripClass>)>,
     trip_q: Query<TripQuery>,
     entry_q: Query<(Entity, &EntryMode, &EntryStop)>,
+    stock_q: Query<&RollingStock>,
     parent_station_or_station: Query<ParentStationOrStation>,
     interval_q: Query<IntervalQuery>,
     mut commands: Commands,
     graph: Res<Graph>,
 ) {
+    // only trips running over the changed intervals are affected by them
+    let changed_pairs: HashSet<(Entity, Entity)> = if changed_intervals.is_empty() {
+        HashSet::new()
+    } else {
+        graph
+            .all_edges()
+            .filter(|(_, _, interval)| changed_intervals.contains(**interval))
+            .map(|(source, target, _)| (source, target))
+            .collect()
+    };
+    let interval_trips = all_trips.iter().filter_map(|(trip, schedule)| {
+        if changed_pairs.is_empty() {
+            return None;
+        }
+        entry_q
+            .iter_many(schedule.iter())
+            .filter_map(|(_, _, stop)| {
+                parent_station_or_station
+                    .get(stop.entity())
+                    .ok()
+                    .map(|it| it.parent())
+            })
+            .tuple_windows()
+            .any(|pair| changed_pairs.contains(&pair))
+            .then_some(trip)
+    });
     let mut to_recalculate = changed_entries
         .iter()
         .map(|c| c.parent())
         .chain(changed_trips.iter())
+        .chain(changed_stock.iter().flat_map(|(class, vehicle)| {
+            let class_trips = class.map(Class::as_trips).unwrap_or_default();
+            let vehicle_trips = vehicle.map_or(&[][..], |it| it.trips.as_slice());
+            class_trips.iter().chain(vehicle_trips).copied()
+        }))
+        .chain(interval_trips)
         .collect::<Vec<_>>();
     to_recalculate.sort_unstable();
     to_recalculate.dedup();
     for q in trip_q.iter_many(to_recalculate) {
+        let stock = q
+            .vehicles
+            .iter()
+            .find_map(|it| stock_q.get(*it).ok())
+            .or_else(|| stock_q.get(q.class.entity()).ok());
         let mut flexible_stack: Vec<(Entity, Entity, Duration)> = Vec::new();
         let mut last_stable: Option<(TimetableTime, Entity)> = None;
         let mut next_stable: Option<(TimetableTime, Entity)> = None;
@@ -211,6 +314,28 @@ fn recalculate_estimate(
             };
             let initial_t = last_t;
             let total_stop_dur: Duration = flexible_stack.iter().map(|(_, _, d)| *d).sum();
+            let Some(leg_stack) = legs_between(
+                std::iter::once(last_s)
+                    .chain(flexible_stack.iter().map(|(_, s, _)| *s))
+                    .chain(std::iter::once(stop.entity())),
+                &graph,
+                &parent_station_or_station,
+                &interval_q,
+            ) else {
+                for (e, _, _) in flexible_stack.drain(..) {
+                    commands.entity(e).remove::<EntryEstimate>();
+                }
+                continue 'iter_entries;
+            };
+            debug_assert_eq!(leg_stack.len(), flexible_stack.len() + 1);
+            let leg_times = stock.map(|stock| {
+                // the train stops wherever it dwells
+                let stops: Vec<bool> = std::iter::once(true)
+                    .chain(flexible_stack.iter().map(|(_, _, d)| d.0 > 0))
+                    .chain(std::iter::once(true))
+                    .collect();
+                stock.leg_times(&leg_stack, &stops)
+            });
             let total_dur = match params {
                 UnwindParams::ForAt(d, _t) => d,
                 UnwindParams::ForFor(ad, _dd) => ad,
@@ -218,40 +343,20 @@ fn recalculate_estimate(
             };
             // stopping time should not be counted while average velocity
             let travel_dur = total_dur - total_stop_dur;
-            let mut distance_stack = Vec::with_capacity(flexible_stack.len());
-
-            for (ps, cs) in std::iter::once(last_s)
-                .chain(flexible_stack.iter().map(|(_, s, _)| *s))
-                .chain(std::iter::once(stop.entity()))
-                .map(|e| parent_station_or_station.get(e).unwrap().parent())
-                .tuple_windows()
-            {
-                let Some(weight) = graph
-                    .edge_weight(ps, cs)
-                    .copied()
-                    .map(|w| interval_q.get(w).ok())
-                    .flatten()
-                else {
-                    for (e, _, _) in flexible_stack.drain(..) {
-                        commands.entity(e).remove::<EntryEstimate>();
-                    }
-                    continue 'iter_entries;
-                };
-                distance_stack.push(weight.distance())
-            }
-            debug_assert_eq!(distance_stack.len(), flexible_stack.len() + 1);
-            let total_dis = distance_stack.iter().cloned().sum::<Distance>();
+            let weights: Vec<f64> = match leg_times {
+                Some(times) => times.into_iter().map(f64::from).collect(),
+                None => leg_stack.iter().map(|it| it.length.0 as f64).collect(),
+            };
+            let total_weight = weights.iter().sum::<f64>();
             let mut fi = flexible_stack.drain(..);
-            let mut di = distance_stack.drain(..);
-            let total_dis_m = total_dis.0 as f64;
+            let mut wi = weights.into_iter();
             let travel_dur_s = travel_dur.0 as f64;
             let mut last_t_f = last_t.0 as f64;
-            while let (Some((e, _, dur)), Some(dis)) = (fi.next(), di.next()) {
-                let dis_m = dis.0 as f64;
-                let travel_leg_s = if total_dis_m == 0.0 {
+            while let (Some((e, _, dur)), Some(weight)) = (fi.next(), wi.next()) {
+                let travel_leg_s = if total_weight == 0.0 {
                     0.0
                 } else {
-                    travel_dur_s * (dis_m / total_dis_m)
+                    travel_dur_s * (weight / total_weight)
                 };
                 last_t_f += travel_leg_s;
                 let arr = TimetableTime(last_t_f.round() as i32);
@@ -263,23 +368,67 @@ fn recalculate_estimate(
             }
             match params {
                 UnwindParams::At(_) => {}
-                UnwindParams::ForAt(d, t) => {
+                UnwindParams::ForAt(_, t) => {
                     commands.entity(entry_entity).insert(EntryEstimate {
-                        arr: initial_t + d,
+                        arr: (initial_t + total_dur).min(t),
                         dep: t,
                     });
                 }
-                UnwindParams::ForFor(ad, dd) => {
+                UnwindParams::ForFor(_, dd) => {
                     commands.entity(entry_entity).insert(EntryEstimate {
-                        arr: initial_t + ad,
-                        dep: initial_t + ad + dd,
+                        arr: initial_t + total_dur,
+                        dep: initial_t + total_dur + dd,
                     });
-                    next_stable = Some((initial_t + ad + dd, stop.entity()))
+                    next_stable = Some((initial_t + total_dur + dd, stop.entity()))
                 }
             }
         }
-        for (e, _, _) in flexible_stack {
-            commands.entity(e).remove::<EntryEstimate>();
+        if flexible_stack.is_empty() {
+            continue;
+        }
+        if let Some(v) = next_stable.take() {
+            last_stable = Some(v);
+        }
+        // entries after the last stable entry run as fast as the rolling stock allows
+        let trailing = stock
+            .zip(last_stable)
+            .and_then(|(stock, (last_t, last_s))| {
+                let legs = legs_between(
+                    std::iter::once(last_s).chain(flexible_stack.iter().map(|(_, s, _)| *s)),
+                    &graph,
+                    &parent_station_or_station,
+                    &interval_q,
+                )?;
+                let stops: Vec<bool> = std::iter::once(true)
+                    .chain(
+                        flexible_stack
+                            .iter()
+                            .take(flexible_stack.len().saturating_sub(1))
+                            .map(|(_, _, d)| d.0 > 0),
+                    )
+                    .chain(std::iter::once(true))
+                    .collect();
+                let times = stock.leg_times(&legs, &stops);
+                Some((last_t, times))
+            });
+        match trailing {
+            Some((last_t, times)) => {
+                let mut last_t_f = last_t.0 as f64;
+                for ((e, _, dur), time) in flexible_stack.into_iter().zip(times) {
+                    last_t_f += time as f64;
+                    let arr = TimetableTime(last_t_f.round() as i32);
+                    commands.entity(e).insert(EntryEstimate {
+                        arr,
+                        dep: arr + dur,
+                    });
+                    last_t_f += dur.0 as f64;
+                }
+            }
+            None => {
+                for (e, _, _) in flexible_stack {
+                    commands.entity(e).remove::<EntryEstimate>();
+                }
+            }
         }
     }
 }
@@ -0,0 +1,128 @@
+//! # Running Time
+//! Minimum technical running times from rolling stock performance, interval speed limits, and
+//! gradients.
+//!
+//! The calculator walks the route in small steps. It accelerates as hard as possible from every
+//! stop, then brakes as late as possible before every stop and speed restriction, and takes the
+//! lower of both speeds at every step.
+
+use crate::units::{distance::Distance, speed::Velocity, time::Duration};
+use bevy::prelude::*;
+
+/// Standard gravity in m/s²
+const GRAVITY: f32 = 9.81;
+/// Maximum length of a step, in meters
+const STEP_LENGTH: f32 = 10.0;
+/// Trains slower than this are assumed to crawl at this speed, in m/s
+const CRAWLING_SPEED: f32 = 0.5;
+
+/// Performance of rolling stock. Add this to a [`crate::vehicle::Vehicle`] or a
+/// [`crate::trip::class::Class`]. The performance of a vehicle takes precedence over the
+/// performance of the class.
+#[derive(Reflect, Component, Debug, Clone, Copy, PartialEq)]
+#[reflect(Component)]
+pub struct RollingStock {
+    pub max_speed: Velocity,
+    /// Maximum acceleration in m/s²
+    pub acceleration: f32,
+    /// Service braking deceleration in m/s²
+    pub braking: f32,
+    /// Mass in tonnes
+    pub mass: f32,
+    /// Traction power in kW. The acceleration is only limited by [`Self::acceleration`] if this
+    /// is zero.
+    pub power: f32,
+}
+
+impl Default for RollingStock {
+    fn default() -> Self {
+        Self {
+            max_speed: Velocity::from_kmh(120.0),
+            acceleration: 0.8,
+            braking: 0.8,
+            mass: 300.0,
+            power: 3000.0,
+        }
+    }
+}
+
+/// A part of the route with uniform conditions.
+#[derive(Debug, Clone, Copy)]
+pub struct Leg {
+    pub length: Distance,
+    pub speed_limit: Option<Velocity>,
+    /// Gradient in per mille. Positive values are uphill.
+    pub gradient: f32,
+}
+
+impl RollingStock {
+    /// Acceleration available at the given speed on level track.
+    fn traction(&self, speed: f32) -> f32 {
+        if self.power <= 0.0 || self.mass <= 0.0 {
+            return self.acceleration;
+        }
+        // kW / t = W / kg
+        let by_power = self.power / (self.mass * speed.max(CRAWLING_SPEED));
+        self.acceleration.min(by_power)
+    }
+    /// Minimum running time over consecutive legs without stopping, starting and ending at a
+    /// standstill.
+    pub fn running_time(&self, legs: &[Leg]) -> Duration {
+        let stops = vec![false; legs.len() + 1];
+        let times = self.leg_times(legs, &stops);
+        Duration(times.iter().sum::<f32>().round() as i32)
+    }
+    /// Minimum running time of each leg in seconds. `stops` tells whether the train stops at the
+    /// start of each leg and at the end of the last leg, and should contain one more item than
+    /// `legs`. The train always starts and ends at a standstill.
+    pub fn leg_times(&self, legs: &[Leg], stops: &[bool]) -> Vec<f32> {
+        debug_assert_eq!(stops.len(), legs.len() + 1);
+        // step length, gradient, and the leg of each step
+        let mut steps: Vec<(f32, f32, usize)> = Vec::new();
+        // speed limits at the start of each step, and at the end of the last step
+        let mut speeds: Vec<f32> = Vec::new();
+        for (idx, leg) in legs.iter().enumerate() {
+            let length = leg.length.0.max(0) as f32;
+            let count = (length / STEP_LENGTH).ceil().max(1.0) as usize;
+            let limit = leg
+                .speed_limit
+                .map_or(self.max_speed.0, |it| it.0.min(self.max_speed.0))
+                .max(CRAWLING_SPEED);
+            let start_limit = if stops[idx] { 0.0 } else { limit };
+            match speeds.last_mut() {
+                Some(last) => *last = last.min(start_limit),
+                None => speeds.push(0.0),
+            }
+            for _ in 0..count {
+                steps.push((length / count as f32, leg.gradient / 1000.0, idx));
+                speeds.push(limit);
+            }
+        }
+        let Some(last) = speeds.last_mut() else {
+            return Vec::new();
+        };
+        *last = 0.0;
+
+        // accelerate
+        for (idx, (length, gradient, _)) in steps.iter().enumerate() {
+            let v = speeds[idx];
+            let a = self.traction(v) - GRAVITY * gradient;
+            let reachable = (v * v + 2.0 * a * length).max(0.0).sqrt();
+            speeds[idx + 1] = speeds[idx + 1].min(reachable);
+        }
+        // brake
+        for (idx, (length, gradient, _)) in steps.iter().enumerate().rev() {
+            let v = speeds[idx + 1];
+            let b = (self.braking + GRAVITY * gradient).max(0.1);
+            let reachable = (v * v + 2.0 * b * length).sqrt();
+            speeds[idx] = speeds[idx].min(reachable);
+        }
+
+        let mut times = vec![0.0; legs.len()];
+        for (idx, (length, _, leg)) in steps.iter().enumerate() {
+            let average = ((speeds[idx] + speeds[idx + 1]) / 2.0).max(CRAWLING_SPEED);
+            times[*leg] += length / average;
+        }
+        times
+    }
+}
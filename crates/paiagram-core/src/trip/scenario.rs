@@ -0,0 +1,23 @@
+//! # Scenario
+//! Scenarios are timetable variants, e.g. weekday, holiday, or alternative diagrams. Each trip
+//! belongs to at most one scenario.
+
+use bevy::prelude::*;
+use moonshine_core::prelude::{MapEntities, ReflectMapEntities};
+
+/// A timetable variant.
+#[derive(Default, Reflect, Component, MapEntities)]
+#[reflect(Component, MapEntities)]
+#[relationship_target(relationship = crate::trip::TripScenario)]
+#[require(Name)]
+pub struct Scenario {
+    #[relationship]
+    #[entities]
+    trips: Vec<Entity>,
+}
+
+impl Scenario {
+    pub fn as_trips(&self) -> &[Entity] {
+        self.trips.as_slice()
+    }
+}
@@ -5,9 +5,20 @@ use derive_more::{Add, AddAssign, Sub, SubAssign};
 use std::ops;
 
 /// The speed represented in m/s
-#[derive(Reflect, Debug, Clone, Copy, Add, AddAssign, Sub, SubAssign)]
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, PartialOrd, Add, AddAssign, Sub, SubAssign)]
 pub struct Velocity(pub f32);
 
+impl Velocity {
+    #[inline]
+    pub fn from_kmh(kmh: f32) -> Self {
+        Self(kmh / 3.6)
+    }
+    #[inline]
+    pub fn to_kmh(self) -> f32 {
+        self.0 * 3.6
+    }
+}
+
 impl std::fmt::Display for Velocity {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:.2}m/s", self.0)
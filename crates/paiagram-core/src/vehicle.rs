@@ -1,6 +1,10 @@
+use crate::trip::TripVehicles;
 use bevy::{ecs::query::QueryData, prelude::*};
+use eros::bail;
 use moonshine_core::prelude::{MapEntities, ReflectMapEntities};
 
+pub mod scheduling;
+
 /// A vehicle is the "executor" of a [`crate::trip::Trip`].
 #[derive(Default, Reflect, Component, MapEntities)]
 #[reflect(Component, MapEntities)]
@@ -15,3 +19,36 @@ pub struct VehicleQuery {
     name: &'static Name,
     vehicle: &'static Vehicle,
 }
+
+/// Move a trip from one vehicle to another, and keep [`TripVehicles`] in sync. The trip is
+/// inserted at `index` of the target vehicle's trips, counted without the moved trip. Returns
+/// the previous position of the trip in the source vehicle.
+pub fn move_trip(
+    world: &mut World,
+    trip: Entity,
+    from: Entity,
+    to: Entity,
+    index: usize,
+) -> eros::Result<usize> {
+    let Some(target) = world.get::<Vehicle>(to) else {
+        bail!("The target vehicle has been deleted")
+    };
+    if from != to && target.trips.contains(&trip) {
+        bail!("The trip already belongs to the target vehicle")
+    }
+    let Some(mut source) = world.get_mut::<Vehicle>(from) else {
+        bail!("The source vehicle has been deleted")
+    };
+    let Some(previous_index) = source.trips.iter().position(|it| *it == trip) else {
+        bail!("The trip does not belong to the source vehicle")
+    };
+    source.trips.remove(previous_index);
+    let mut target = world.get_mut::<Vehicle>(to).unwrap();
+    let index = index.min(target.trips.len());
+    target.trips.insert(index, trip);
+    if let Some(mut vehicles) = world.get_mut::<TripVehicles>(trip) {
+        vehicles.retain(|it| *it != from && *it != to);
+        vehicles.push(to);
+    }
+    Ok(previous_index)
+}
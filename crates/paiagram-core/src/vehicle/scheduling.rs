@@ -0,0 +1,329 @@
+//! # Scheduling
+//! Build vehicle circulations automatically, using as few vehicles as possible.
+//!
+//! Each trip is a node in a directed acyclic graph, where an edge from trip A to trip B means
+//! that a vehicle could run trip B after trip A. The minimum number of vehicles equals the number
+//! of trips minus the size of a maximum matching in the bipartite graph formed by the edges.
+
+use crate::{
+    entry::EntryQuery,
+    graph::Graph,
+    interval::IntervalQuery,
+    settings::ProjectSettings,
+    station::{MinimumTurnaround, ParentStationOrStation},
+    trip::{TripQuery, TripService},
+    units::{distance::Distance, speed::Velocity, time::Duration},
+    vehicle::Vehicle,
+};
+use bevy::{ecs::entity::EntityHashSet, platform::collections::HashMap, prelude::*};
+use std::collections::VecDeque;
+
+/// Marker component for vehicles created by [`ScheduleVehicles`]. These vehicles are replaced
+/// when scheduling again, while other vehicles and their trips are left untouched.
+#[derive(Reflect, Component, Default)]
+#[reflect(Component)]
+#[require(Vehicle)]
+pub struct IsScheduledVehicle;
+
+/// Assign all trips that do not belong to a manually created vehicle to as few vehicles as
+/// possible. Trips are only chained with trips of the same scenario and calendar.
+#[derive(Event, Clone, Copy)]
+pub struct ScheduleVehicles;
+
+/// The start and the end of a trip
+struct TripEnds {
+    trip: Entity,
+    origin: Entity,
+    destination: Entity,
+    dep: i32,
+    arr: i32,
+}
+
+pub fn schedule_vehicles(
+    _: On<ScheduleVehicles>,
+    trip_q: Query<TripQuery>,
+    entry_q: Query<EntryQuery>,
+    parent_station_or_station: Query<ParentStationOrStation>,
+    turnaround_q: Query<&MinimumTurnaround>,
+    scheduled_q: Query<Entity, With<IsScheduledVehicle>>,
+    vehicle_q: Query<&Vehicle, Without<IsScheduledVehicle>>,
+    interval_q: Query<IntervalQuery>,
+    graph: Res<Graph>,
+    settings: Res<ProjectSettings>,
+    mut commands: Commands,
+) {
+    for vehicle in &scheduled_q {
+        commands.entity(vehicle).despawn();
+    }
+    let manually_assigned: EntityHashSet = vehicle_q
+        .iter()
+        .flat_map(|it| it.trips.iter().copied())
+        .collect();
+
+    let stop_of = |entry: Entity| -> Option<(Entity, i32, i32)> {
+        let entry = entry_q.get(entry).ok()?;
+        let estimate = entry.estimate?;
+        let station = parent_station_or_station.get(entry.stop()).ok()?.parent();
+        Some((station, estimate.arr.0, estimate.dep.0))
+    };
+    let mut groups: Vec<(TripService, Vec<TripEnds>)> = Vec::new();
+    for trip in trip_q
+        .iter()
+        .filter(|it| !manually_assigned.contains(&it.entity))
+    {
+        let (Some(first), Some(last)) = (trip.schedule.first(), trip.schedule.last()) else {
+            continue;
+        };
+        let (Some((origin, _, dep)), Some((destination, arr, _))) =
+            (stop_of(*first), stop_of(*last))
+        else {
+            continue;
+        };
+        let ends = TripEnds {
+            trip: trip.entity,
+            origin,
+            destination,
+            dep,
+            arr,
+        };
+        let service = trip.service();
+        match groups.iter_mut().find(|(it, _)| *it == service) {
+            Some((_, trips)) => trips.push(ends),
+            None => groups.push((service, vec![ends])),
+        }
+    }
+
+    let turnaround = |station: Entity| -> i32 {
+        turnaround_q
+            .get(station)
+            .map_or(settings.minimum_turnaround, |it| it.0)
+            .0
+    };
+    let mut deadheads: HashMap<(Entity, Entity), Option<i32>> = HashMap::new();
+    let mut deadhead = |from: Entity, to: Entity| -> Option<i32> {
+        *deadheads.entry((from, to)).or_insert_with(|| {
+            deadhead_time(&graph, &interval_q, settings.deadhead_speed, from, to).map(|it| it.0)
+        })
+    };
+
+    let mut count = 0;
+    for (_, mut trips) in groups {
+        trips.sort_unstable_by_key(|it| it.dep);
+        // trips by their origin, sorted by departure
+        let mut by_origin: HashMap<Entity, Vec<usize>> = HashMap::new();
+        for (idx, it) in trips.iter().enumerate() {
+            by_origin.entry(it.origin).or_default().push(idx);
+        }
+        let successors: Vec<Vec<usize>> = trips
+            .iter()
+            .map(|a| {
+                let ready = a.arr + turnaround(a.destination);
+                // without empty runs, vehicles can only continue from where they arrived
+                let origins: Vec<(Entity, &Vec<usize>)> = if settings.deadhead_speed.is_some() {
+                    by_origin.iter().map(|(k, v)| (*k, v)).collect()
+                } else {
+                    by_origin
+                        .get_key_value(&a.destination)
+                        .map(|(k, v)| (*k, v))
+                        .into_iter()
+                        .collect()
+                };
+                let mut candidates: Vec<usize> = Vec::new();
+                for (origin, indices) in origins {
+                    let Some(d) = deadhead(a.destination, origin) else {
+                        continue;
+                    };
+                    let first = indices.partition_point(|it| trips[*it].dep < ready + d);
+                    candidates.extend(
+                        indices[first..]
+                            .iter()
+                            .copied()
+                            .filter(|it| trips[*it].trip != a.trip),
+                    );
+                }
+                // indices follow the order of departure
+                candidates.sort_unstable();
+                candidates
+            })
+            .collect();
+        let next = maximum_matching(&successors);
+        let mut has_previous = vec![false; trips.len()];
+        for it in next.iter().flatten() {
+            has_previous[*it] = true;
+        }
+        for start in (0..trips.len()).filter(|it| !has_previous[*it]) {
+            let mut chain = vec![trips[start].trip];
+            let mut current = start;
+            while let Some(following) = next[current] {
+                chain.push(trips[following].trip);
+                current = following;
+            }
+            count += 1;
+            commands.spawn((
+                Name::new(format!("Vehicle {count}")),
+                IsScheduledVehicle,
+                Vehicle { trips: chain },
+            ));
+        }
+    }
+    info!("Scheduled {count} vehicles");
+}
+
+/// The time needed for a vehicle to run empty between two stations along the shortest path.
+/// Returns `None` if the stations differ and empty runs are not allowed, or if there is no path.
+pub fn deadhead_time(
+    graph: &Graph,
+    interval_q: &Query<IntervalQuery>,
+    speed: Option<Velocity>,
+    from: Entity,
+    to: Entity,
+) -> Option<Duration> {
+    if from == to {
+        return Some(Duration::ZERO);
+    }
+    let speed = speed?;
+    let (length, _) = graph.route_between(from, to, interval_q)?;
+    Some(Distance(length) / speed)
+}
+
+/// Hopcroft–Karp maximum bipartite matching. Returns the matched successor of each node.
+fn maximum_matching(successors: &[Vec<usize>]) -> Vec<Option<usize>> {
+    let len = successors.len();
+    let mut next: Vec<Option<usize>> = vec![None; len];
+    let mut previous: Vec<Option<usize>> = vec![None; len];
+    let mut layers = vec![u32::MAX; len];
+    loop {
+        // build layers from all unmatched left nodes
+        let mut queue: VecDeque<usize> = VecDeque::new();
+        for (idx, layer) in layers.iter_mut().enumerate() {
+            if next[idx].is_none() {
+                *layer = 0;
+                queue.push_back(idx);
+            } else {
+                *layer = u32::MAX;
+            }
+        }
+        let mut found = false;
+        while let Some(left) = queue.pop_front() {
+            for right in &successors[left] {
+                match previous[*right] {
+                    None => found = true,
+                    Some(matched) if layers[matched] == u32::MAX => {
+                        layers[matched] = layers[left] + 1;
+                        queue.push_back(matched);
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        if !found {
+            break;
+        }
+        for left in 0..len {
+            if next[left].is_none() {
+                augment(left, successors, &mut next, &mut previous, &mut layers);
+            }
+        }
+    }
+    next
+}
+
+/// Find an augmenting path from the left node along the layers, iteratively to avoid
+/// overflowing the stack on long paths.
+fn augment(
+    start: usize,
+    successors: &[Vec<usize>],
+    next: &mut [Option<usize>],
+    previous: &mut [Option<usize>],
+    layers: &mut [u32],
+) -> bool {
+    // left node and the index of the next edge to try
+    let mut stack: Vec<(usize, usize)> = vec![(start, 0)];
+    while let Some((left, edge)) = stack.last_mut() {
+        let left = *left;
+        let Some(right) = successors[left].get(*edge).copied() else {
+            // dead end
+            layers[left] = u32::MAX;
+            stack.pop();
+            continue;
+        };
+        *edge += 1;
+        match previous[right] {
+            None => {
+                // flip the path
+                let mut right = right;
+                while let Some((left, _)) = stack.pop() {
+                    let displaced = next[left];
+                    next[left] = Some(right);
+                    previous[right] = Some(left);
+                    let Some(displaced) = displaced else {
+                        break;
+                    };
+                    right = displaced;
+                }
+                return true;
+            }
+            Some(matched) if layers[matched] == layers[left] + 1 => {
+                stack.push((matched, 0));
+            }
+            Some(_) => {}
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Check that the matching only uses the given edges and matches every node at most once,
+    /// and return the number of vehicles needed.
+    fn vehicles(successors: &[Vec<usize>], next: &[Option<usize>]) -> usize {
+        let mut has_previous = vec![false; successors.len()];
+        for (left, right) in next.iter().enumerate() {
+            let Some(right) = right else {
+                continue;
+            };
+            assert!(successors[left].contains(right));
+            assert!(!has_previous[*right]);
+            has_previous[*right] = true;
+        }
+        has_previous.iter().filter(|it| !**it).count()
+    }
+
+    #[test]
+    fn chain_needs_one_vehicle() {
+        let successors = vec![vec![1], vec![2], vec![3], Vec::new()];
+        let next = maximum_matching(&successors);
+        assert_eq!(next, [Some(1), Some(2), Some(3), None]);
+        assert_eq!(vehicles(&successors, &next), 1);
+    }
+
+    #[test]
+    fn matching_better_than_greedy() {
+        // taking the earliest following trip for trip 0 would leave trip 1 without one
+        let successors = vec![vec![2, 3], vec![2], Vec::new(), Vec::new()];
+        let next = maximum_matching(&successors);
+        assert_eq!(next, [Some(3), Some(2), None, None]);
+        assert_eq!(vehicles(&successors, &next), 2);
+    }
+
+    #[test]
+    fn long_augmenting_paths() {
+        // trip i can be followed by trips i + 1 and i + 2, except for the last two trips,
+        // so greedy choices have to be undone along the whole chain
+        let len = 10_000;
+        let successors: Vec<Vec<usize>> = (0..len)
+            .map(|it| (it + 1..len.min(it + 3)).rev().collect())
+            .collect();
+        let next = maximum_matching(&successors);
+        assert_eq!(vehicles(&successors, &next), 1);
+    }
+
+    #[test]
+    fn trips_without_successors() {
+        let successors = vec![Vec::new(); 3];
+        let next = maximum_matching(&successors);
+        assert_eq!(vehicles(&successors, &next), 3);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::PdfOptions;
+use crate::draw::{self, Align, Fonts, GRAY, LIGHT_GRAY};
+use bevy::prelude::*;
+use krilla::{Document, geom::Rect, page::PageSettings};
+use paiagram_core::export::diagram::{self, DiagramTrip};
+use paiagram_core::route::Route;
+use paiagram_core::settings::ProjectSettings;
+use paiagram_core::trip::calendar::ServiceDate;
+use paiagram_core::units::time::{Duration, TimetableTime};
+
+const MARGIN: f32 = 28.0;
+const TITLE_SIZE: f32 = 11.0;
+const LABEL_SIZE: f32 = 7.0;
+const TRIP_LABEL_SIZE: f32 = 4.5;
+/// Lines are thinner on paper than on screen
+const LINE_SCALE: f32 = 0.5;
+
+pub(crate) struct DiagramData {
+    /// Names of the stations and their positions on the route
+    stations: Vec<(String, f32)>,
+    trips: Vec<DiagramTrip>,
+    /// The trips are drawn again every `repeat`
+    repeat: Duration,
+}
+
+/// The stations and the trips of the route running on the service day, in the scenario if one is
+/// given.
+pub(crate) fn collect(
+    world: &mut World,
+    route_entity: Entity,
+    service_day: Option<ServiceDate>,
+    scenario: Option<Entity>,
+) -> DiagramData {
+    let stations = world
+        .get::<Route>(route_entity)
+        .map(|route| {
+            route
+                .iter()
+                .map(|(station, height)| {
+                    let name = world
+                        .get::<Name>(station)
+                        .map_or_else(String::new, Name::to_string);
+                    (name, height)
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let trips = diagram::exported_trips(world, route_entity, service_day, scenario);
+    let repeat = world.resource::<ProjectSettings>().repeat_frequency;
+    DiagramData {
+        stations,
+        trips,
+        repeat,
+    }
+}
+
+/// Draw the diagram, one page for each time window.
+pub(crate) fn draw(
+    document: &mut Document,
+    fonts: &Fonts,
+    title: &str,
+    data: &DiagramData,
+    options: &PdfOptions,
+) {
+    let (width, height) = options.page_size;
+    let span = options.hours_per_page.clamp(1, 48) as i32 * 3600;
+    let stops = || {
+        data.trips
+            .iter()
+            .flat_map(|it| it.segments.iter().flatten())
+    };
+    let start = stops()
+        .map(|it| it.arr.0)
+        .min()
+        .map_or(0, |it| it.div_euclid(span) * span);
+    let end = stops()
+        .map(|it| it.dep.0)
+        .max()
+        .map_or(span, |it| (it.div_euclid(span) + 1) * span);
+    let max_height = data.stations.last().map_or(0.0, |(_, it)| *it);
+    let label_width = data
+        .stations
+        .iter()
+        .map(|(name, _)| fonts.width(name, LABEL_SIZE))
+        .fold(0.0, f32::max)
+        .min(width / 5.0)
+        + 6.0;
+    let Some(plot) = Rect::from_ltrb(
+        MARGIN + label_width,
+        MARGIN + TITLE_SIZE + 2.0 * LABEL_SIZE,
+        width - MARGIN,
+        height - MARGIN,
+    ) else {
+        warn!("The page is too small for the diagram");
+        return;
+    };
+    let y = |position: f32| {
+        if max_height > 0.0 {
+            plot.top() + position / max_height * plot.height()
+        } else {
+            plot.top()
+        }
+    };
+    for page_start in (start..end).step_by(span as usize) {
+        let x = |time: TimetableTime| {
+            plot.left() + (time.0 - page_start) as f32 / span as f32 * plot.width()
+        };
+        let hour_width = plot.width() * 3600.0 / span as f32;
+        let mut page = document.start_page_with(PageSettings::new(width, height));
+        let mut surface = page.surface();
+        fonts.draw(
+            &mut surface,
+            &format!(
+                "{title}  {:02}:00–{:02}:00",
+                page_start / 3600,
+                (page_start + span) / 3600
+            ),
+            (MARGIN, MARGIN + TITLE_SIZE),
+            TITLE_SIZE,
+            Align::Left,
+        );
+        // ten minute lines, if there is room for them
+        if hour_width >= 60.0 {
+            draw::lines(
+                &mut surface,
+                (page_start..page_start + span)
+                    .step_by(600)
+                    .filter(|it| it % 3600 != 0)
+                    .map(|it| {
+                        let x = x(TimetableTime(it));
+                        [(x, plot.top()), (x, plot.bottom())]
+                    }),
+                draw::stroke(LIGHT_GRAY, 0.2),
+            );
+        }
+        draw::lines(
+            &mut surface,
+            (page_start..page_start + span).step_by(3600).map(|it| {
+                let x = x(TimetableTime(it));
+                [(x, plot.top()), (x, plot.bottom())]
+            }),
+            draw::stroke(GRAY, 0.5),
+        );
+        for hour in (page_start..=page_start + span).step_by(3600) {
+            fonts.draw(
+                &mut surface,
+                &(hour / 3600).rem_euclid(24).to_string(),
+                (x(TimetableTime(hour)), plot.top() - LABEL_SIZE / 2.0),
+                LABEL_SIZE,
+                Align::Center,
+            );
+        }
+        draw::lines(
+            &mut surface,
+            data.stations.iter().map(|(_, position)| {
+                let y = y(*position);
+                [(plot.left(), y), (plot.right(), y)]
+            }),
+            draw::stroke(GRAY, 0.4),
+        );
+        for (name, position) in &data.stations {
+            let name = fonts.fit(name, LABEL_SIZE, label_width - 6.0);
+            fonts.draw(
+                &mut surface,
+                &name,
+                (plot.left() - 4.0, y(*position) + LABEL_SIZE * 0.35),
+                LABEL_SIZE,
+                Align::Right,
+            );
+        }
+        draw::rect(&mut surface, plot, draw::stroke(GRAY, 0.6));
+        draw::push_clip(&mut surface, plot);
+        for trip in &data.trips {
+            for (segment, offset) in trip.segments.iter().flat_map(|segment| {
+                diagram::repeat_offsets(
+                    segment,
+                    data.repeat,
+                    TimetableTime(page_start)..TimetableTime(page_start + span),
+                )
+                .into_iter()
+                .map(move |offset| (segment, offset))
+            }) {
+                let points: Vec<(f32, f32)> = segment
+                    .iter()
+                    .flat_map(|stop| {
+                        let y = y(data.stations[stop.station_index].1);
+                        [(x(stop.arr + offset), y), (x(stop.dep + offset), y)]
+                    })
+                    .collect();
+                draw::polyline(
+                    &mut surface,
+                    &points,
+                    draw::stroke(trip.color, trip.width * LINE_SCALE),
+                );
+                let (label_x, label_y) = points[1];
+                fonts.draw(
+                    &mut surface,
+                    &trip.name,
+                    (label_x + 1.0, label_y - 1.5),
+                    TRIP_LABEL_SIZE,
+                    Align::Left,
+                );
+            }
+        }
+        surface.pop();
+        surface.finish();
+        page.finish();
+    }
+}
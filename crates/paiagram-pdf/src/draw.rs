@@ -0,0 +1,193 @@
+use krilla::{
+    color::rgb,
+    geom::{PathBuilder, Point, Rect},
+    num::NormalizedF32,
+    paint::{Fill, FillRule, LineCap, LineJoin, Stroke},
+    surface::Surface,
+    text::{Font, TextDirection},
+};
+use skrifa::{
+    FontRef, GlyphId, MetadataProvider,
+    instance::{LocationRef, Size},
+};
+use std::collections::HashMap;
+
+pub(crate) const BLACK: [u8; 4] = [0, 0, 0, 255];
+pub(crate) const GRAY: [u8; 4] = [128, 128, 128, 255];
+pub(crate) const LIGHT_GRAY: [u8; 4] = [208, 208, 208, 255];
+
+/// Where the anchor of a text is
+#[derive(Clone, Copy)]
+pub(crate) enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+/// The embedded fonts, followed by the bundled Latin font
+pub(crate) struct Fonts {
+    fonts: Vec<FontMetrics>,
+}
+
+/// A font and the advances of its characters in ems
+struct FontMetrics {
+    font: Font,
+    advances: HashMap<char, f32>,
+    /// The advance of characters the font does not have
+    notdef: f32,
+}
+
+impl FontMetrics {
+    fn new(data: Vec<u8>) -> Option<Self> {
+        let font = FontRef::new(&data).ok()?;
+        let metrics = font.glyph_metrics(Size::new(1.0), LocationRef::default());
+        let advances = font
+            .charmap()
+            .mappings()
+            .filter_map(|(c, glyph)| Some((char::from_u32(c)?, metrics.advance_width(glyph)?)))
+            .collect();
+        let notdef = metrics.advance_width(GlyphId::NOTDEF).unwrap_or(0.0);
+        let font = Font::new(data.into(), 0)?;
+        Some(Self {
+            font,
+            advances,
+            notdef,
+        })
+    }
+    fn advance(&self, c: char) -> f32 {
+        self.advances.get(&c).copied().unwrap_or(self.notdef)
+    }
+}
+
+impl Fonts {
+    pub(crate) fn new(fonts: &[Vec<u8>]) -> Self {
+        let fonts = fonts
+            .iter()
+            .cloned()
+            .chain([epaint_default_fonts::HACK_REGULAR.to_vec()])
+            .filter_map(FontMetrics::new)
+            .collect();
+        Self { fonts }
+    }
+    /// The first font having every character of the text
+    fn pick(&self, text: &str) -> Option<&FontMetrics> {
+        self.fonts
+            .iter()
+            .find(|font| {
+                text.chars()
+                    .filter(|c| !c.is_whitespace())
+                    .all(|c| font.advances.contains_key(&c))
+            })
+            .or_else(|| self.fonts.last())
+    }
+    /// The width of the text in points
+    pub(crate) fn width(&self, text: &str, size: f32) -> f32 {
+        self.pick(text).map_or(0.0, |font| {
+            text.chars().map(|c| font.advance(c)).sum::<f32>() * size
+        })
+    }
+    /// Shorten the text until it fits the width.
+    pub(crate) fn fit(&self, text: &str, size: f32, width: f32) -> String {
+        let Some(font) = self.pick(text) else {
+            return String::new();
+        };
+        let mut sum = 0.0;
+        text.chars()
+            .take_while(|c| {
+                sum += font.advance(*c) * size;
+                sum <= width
+            })
+            .collect()
+    }
+    /// Draw the text in black, with its baseline at `y`.
+    pub(crate) fn draw(
+        &self,
+        surface: &mut Surface,
+        text: &str,
+        (x, y): (f32, f32),
+        size: f32,
+        align: Align,
+    ) {
+        let Some(font) = self.pick(text) else {
+            return;
+        };
+        let x = match align {
+            Align::Left => x,
+            Align::Center => x - self.width(text, size) / 2.0,
+            Align::Right => x - self.width(text, size),
+        };
+        surface.set_stroke(None);
+        // the default fill is opaque black
+        surface.set_fill(Some(Fill::default()));
+        surface.draw_text(
+            Point::from_xy(x, y),
+            font.font.clone(),
+            size,
+            text,
+            false,
+            TextDirection::Auto,
+        );
+    }
+}
+
+/// A stroke with an unmultiplied RGBA colour
+pub(crate) fn stroke([r, g, b, a]: [u8; 4], width: f32) -> Stroke {
+    Stroke {
+        paint: rgb::Color::new(r, g, b).into(),
+        width,
+        opacity: NormalizedF32::new(a as f32 / 255.0).unwrap_or(NormalizedF32::ONE),
+        line_cap: LineCap::Round,
+        line_join: LineJoin::Round,
+        ..Default::default()
+    }
+}
+
+/// Draw a line through the points.
+pub(crate) fn polyline(surface: &mut Surface, points: &[(f32, f32)], stroke: Stroke) {
+    let mut builder = PathBuilder::new();
+    let mut points = points.iter();
+    let Some((x, y)) = points.next() else {
+        return;
+    };
+    builder.move_to(*x, *y);
+    for (x, y) in points {
+        builder.line_to(*x, *y);
+    }
+    let Some(path) = builder.finish() else {
+        return;
+    };
+    surface.set_fill(None);
+    surface.set_stroke(Some(stroke));
+    surface.draw_path(&path);
+}
+
+/// Draw separate lines between the pairs of points.
+pub(crate) fn lines(
+    surface: &mut Surface,
+    lines: impl IntoIterator<Item = [(f32, f32); 2]>,
+    stroke: Stroke,
+) {
+    let mut builder = PathBuilder::new();
+    for [(x1, y1), (x2, y2)] in lines {
+        builder.move_to(x1, y1);
+        builder.line_to(x2, y2);
+    }
+    let Some(path) = builder.finish() else {
+        return;
+    };
+    surface.set_fill(None);
+    surface.set_stroke(Some(stroke));
+    surface.draw_path(&path);
+}
+
+/// Draw the outline of the rectangle.
+pub(crate) fn rect(surface: &mut Surface, rect: Rect, stroke: Stroke) {
+    surface.set_fill(None);
+    surface.set_stroke(Some(stroke));
+    surface.draw_path(&PathBuilder::from_rect(rect));
+}
+
+/// Only draw inside the rectangle until [`Surface::pop`] is called.
+pub(crate) fn push_clip(surface: &mut Surface, rect: Rect) {
+    surface.push_clip_path(&PathBuilder::from_rect(rect), &FillRule::NonZero);
+}
@@ -1 +1,120 @@
+//! # PDF
+//! Print-ready exports of routes. The time–distance diagram and the timetables of a route are
+//! drawn as vector graphics, with the fonts embedded.
 
+mod diagram;
+mod draw;
+mod timetable;
+
+use bevy::prelude::*;
+use draw::Fonts;
+use krilla::{Document, metadata::Metadata};
+use paiagram_core::export::ExportObject;
+use paiagram_core::trip::calendar::ServiceDate;
+use std::io::Write;
+
+/// What to draw, and how
+#[derive(Debug, Clone)]
+pub struct PdfOptions {
+    /// Width and height of the pages in points
+    pub page_size: (f32, f32),
+    /// Hours of the diagram on each page
+    pub hours_per_page: u32,
+    /// Only export trips running on this day. All trips are exported if not given.
+    pub service_day: Option<ServiceDate>,
+    /// Only export trips in this scenario. Trips in all scenarios are exported if not given.
+    pub scenario: Option<Entity>,
+    /// Contents of TrueType or OpenType fonts to embed. Each text is set in the first font having
+    /// all of its characters, or in the bundled Latin font if there is none.
+    pub fonts: Vec<Vec<u8>>,
+    /// Draw the time–distance diagram
+    pub diagram: bool,
+    /// Draw the timetables of both directions
+    pub timetable: bool,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        Self {
+            // landscape A4
+            page_size: (841.89, 595.28),
+            hours_per_page: 6,
+            service_day: None,
+            scenario: None,
+            fonts: Vec::new(),
+            diagram: true,
+            timetable: true,
+        }
+    }
+}
+
+pub struct PdfExport<'a> {
+    pub route_entity: Entity,
+    pub world: &'a mut World,
+    pub options: PdfOptions,
+}
+
+impl<'a> ExportObject for PdfExport<'a> {
+    fn export_to_buffer(&mut self, buffer: &mut Vec<u8>) {
+        match render(self.world, self.route_entity, &self.options) {
+            Ok(pdf) => buffer.extend(pdf),
+            Err(e) => error!("Failed to export PDF: {e}"),
+        }
+    }
+    /// Render the document before asking where to save it, so that nothing is written on errors.
+    fn export_to_file(&mut self) {
+        let pdf = match render(self.world, self.route_entity, &self.options) {
+            Ok(pdf) => pdf,
+            Err(e) => {
+                error!("Failed to export PDF: {e}");
+                return;
+            }
+        };
+        let filename = format!("{}{}", self.filename().as_ref(), self.extension().as_ref());
+        paiagram_rw::write::write_file(filename, move |writer| writer.write_all(&pdf));
+    }
+    fn export_to_path(&mut self, path: &std::path::Path) -> std::io::Result<()> {
+        let pdf = render(self.world, self.route_entity, &self.options)?;
+        std::fs::write(path, pdf)
+    }
+    fn extension(&self) -> impl AsRef<str> {
+        ".pdf"
+    }
+    fn filename(&self) -> impl AsRef<str> {
+        "exported_route"
+    }
+}
+
+/// Render the route into a PDF document.
+pub fn render(
+    world: &mut World,
+    route_entity: Entity,
+    options: &PdfOptions,
+) -> std::io::Result<Vec<u8>> {
+    let title = world
+        .get::<Name>(route_entity)
+        .map_or_else(String::new, Name::to_string);
+    let fonts = Fonts::new(&options.fonts);
+    let mut document = Document::new();
+    document.set_metadata(
+        Metadata::new()
+            .title(title.clone())
+            .creator("Paiagram".to_string()),
+    );
+    if options.diagram {
+        let data = diagram::collect(world, route_entity, options.service_day, options.scenario);
+        diagram::draw(&mut document, &fonts, &title, &data, options);
+    }
+    if options.timetable {
+        let tables = world
+            .run_system_cached_with(
+                timetable::collect,
+                (route_entity, options.service_day, options.scenario),
+            )
+            .map_err(std::io::Error::other)?;
+        timetable::draw(&mut document, &fonts, &title, &tables, options);
+    }
+    document
+        .finish()
+        .map_err(|e| std::io::Error::other(format!("{e:?}")))
+}
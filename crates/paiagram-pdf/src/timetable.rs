@@ -0,0 +1,309 @@
+use crate::PdfOptions;
+use crate::draw::{self, Align, BLACK, Fonts, GRAY, LIGHT_GRAY};
+use bevy::prelude::*;
+use egui_i18n::tr;
+use krilla::{Document, page::PageSettings};
+use paiagram_core::entry::{EntryQuery, EntryQueryItem, TravelMode};
+use paiagram_core::route::{Route, RouteByDirectionTrips, RouteDisplayModes};
+use paiagram_core::station::ParentStationOrStation;
+use paiagram_core::trip::TripQuery;
+use paiagram_core::trip::calendar::ServiceDate;
+use paiagram_core::units::time::TimetableTime;
+
+const MARGIN: f32 = 28.0;
+const TITLE_SIZE: f32 = 11.0;
+const TEXT_SIZE: f32 = 7.0;
+const ROW_HEIGHT: f32 = 10.0;
+const HEADER_HEIGHT: f32 = 14.0;
+const CELL_WIDTH: f32 = 26.0;
+
+/// The timetable of one direction of the route
+pub(crate) struct Table {
+    direction: String,
+    trips: Vec<String>,
+    rows: Vec<Row>,
+}
+
+/// An arrival or departure row
+struct Row {
+    station: String,
+    /// Whether this is the first row of the station
+    first: bool,
+    kind: String,
+    /// The text of each trip
+    cells: Vec<String>,
+}
+
+/// How a trip is shown at a station, following the route timetable tab
+enum Cell<'w> {
+    /// The trip passes the station without an entry
+    Skipped,
+    /// The trip does not run here
+    NoOperation,
+    /// The trip terminated at the previous station
+    Terminated,
+    Stop(EntryQueryItem<'w, 'w>),
+}
+
+impl Cell<'_> {
+    fn text(&self, arrival: bool) -> String {
+        let time = |t: TimetableTime| t.to_oud2_str(false).trim_start().to_string();
+        match self {
+            Self::Skipped => "║".to_string(),
+            Self::NoOperation => "‥".to_string(),
+            Self::Terminated => "▔".to_string(),
+            Self::Stop(entry) if arrival => match entry.mode.arr {
+                Some(TravelMode::At(t)) => time(t),
+                Some(TravelMode::For(_)) => {
+                    entry.estimate.map_or_else(String::new, |it| time(it.arr))
+                }
+                Some(TravelMode::Flexible) => "○".to_string(),
+                None => "⇂".to_string(),
+            },
+            Self::Stop(entry) => match entry.mode.dep {
+                TravelMode::At(t) => time(t),
+                TravelMode::For(_) => entry.estimate.map_or_else(String::new, |it| time(it.dep)),
+                TravelMode::Flexible => "⇂".to_string(),
+            },
+        }
+    }
+}
+
+pub(crate) fn collect(
+    (In(route_entity), In(service_day), In(scenario)): (
+        In<Entity>,
+        In<Option<ServiceDate>>,
+        In<Option<Entity>>,
+    ),
+    route_q: Query<(&Route, &RouteByDirectionTrips, Option<&RouteDisplayModes>)>,
+    trip_q: Query<TripQuery>,
+    entry_q: Query<EntryQuery>,
+    parent_station_or_station: Query<ParentStationOrStation>,
+    name_q: Query<&Name>,
+) -> Vec<Table> {
+    let Ok((route, by_direction, display_modes)) = route_q.get(route_entity) else {
+        return Vec::new();
+    };
+    // (arrival, departure) of each station
+    let modes: Vec<(bool, bool)> = match display_modes {
+        Some(modes) => modes.iter().map(|it| (it.arrival, it.departure)).collect(),
+        None => vec![(false, true); route.stops.len()],
+    };
+    let mut tables = Vec::new();
+    for (direction, trips, reversed) in [
+        (tr!("pdf-timetable-down"), &by_direction.downward, false),
+        (tr!("pdf-timetable-up"), &by_direction.upward, true),
+    ] {
+        let mut stops: Vec<(Entity, (bool, bool))> = route
+            .stops
+            .iter()
+            .copied()
+            .zip(modes.iter().copied())
+            .collect();
+        if reversed {
+            stops.reverse();
+        }
+        let trips: Vec<_> = trip_q
+            .iter_many(trips.iter())
+            .filter(|trip| trip.runs_on(service_day) && trip.is_in_scenario(scenario))
+            .collect();
+        if trips.is_empty() {
+            continue;
+        }
+        let columns: Vec<Vec<Cell>> = trips
+            .iter()
+            .map(|trip| {
+                let mut cells = Vec::with_capacity(stops.len());
+                cells.resize_with(stops.len(), || Cell::Skipped);
+                let mut next_index = 0;
+                let mut remaining = stops.iter();
+                for entry in entry_q.iter_many(trip.schedule.iter()) {
+                    let Ok(station) = parent_station_or_station.get(entry.stop()) else {
+                        continue;
+                    };
+                    // the iterator is reused, so that later entries are only matched to later
+                    // stations
+                    if let Some(found) = remaining.position(|(it, _)| *it == station.parent()) {
+                        let index = next_index + found;
+                        cells[index] = Cell::Stop(entry);
+                        next_index = index + 1;
+                    }
+                }
+                for cell in cells
+                    .iter_mut()
+                    .take_while(|it| matches!(it, Cell::Skipped))
+                {
+                    *cell = Cell::NoOperation;
+                }
+                let mut last = None;
+                for cell in cells
+                    .iter_mut()
+                    .rev()
+                    .take_while(|it| matches!(it, Cell::Skipped))
+                {
+                    *cell = Cell::NoOperation;
+                    last = Some(cell);
+                }
+                if let Some(cell) = last {
+                    *cell = Cell::Terminated;
+                }
+                cells
+            })
+            .collect();
+        let mut rows = Vec::new();
+        for (index, (station, (arrival, departure))) in stops.iter().enumerate() {
+            let station = name_q
+                .get(*station)
+                .map_or_else(|_| String::new(), Name::to_string);
+            let kinds = [
+                (*arrival, true, tr!("trip-table-arrival")),
+                (*departure, false, tr!("trip-table-departure")),
+            ];
+            for (first, (_, is_arrival, kind)) in
+                kinds.into_iter().filter(|(shown, ..)| *shown).enumerate()
+            {
+                rows.push(Row {
+                    station: station.clone(),
+                    first: first == 0,
+                    kind,
+                    cells: columns
+                        .iter()
+                        .map(|it| it[index].text(is_arrival))
+                        .collect(),
+                });
+            }
+        }
+        tables.push(Table {
+            direction,
+            trips: trips.iter().map(|it| it.name.to_string()).collect(),
+            rows,
+        });
+    }
+    tables
+}
+
+/// Draw the tables, split into pages by rows and by trips.
+pub(crate) fn draw(
+    document: &mut Document,
+    fonts: &Fonts,
+    title: &str,
+    tables: &[Table],
+    options: &PdfOptions,
+) {
+    let (width, height) = options.page_size;
+    for table in tables {
+        let station_width = table
+            .rows
+            .iter()
+            .map(|it| fonts.width(&it.station, TEXT_SIZE))
+            .fold(0.0, f32::max)
+            .min(width / 5.0)
+            + 6.0;
+        let kind_width = table
+            .rows
+            .iter()
+            .map(|it| fonts.width(&it.kind, TEXT_SIZE))
+            .fold(0.0, f32::max)
+            + 6.0;
+        let left = MARGIN + station_width + kind_width;
+        let top = MARGIN + TITLE_SIZE + 6.0 + HEADER_HEIGHT;
+        let columns_per_page = (((width - MARGIN - left) / CELL_WIDTH) as usize).max(1);
+        let rows_per_page = (((height - MARGIN - top) / ROW_HEIGHT) as usize).max(1);
+        for rows in table.rows.chunks(rows_per_page) {
+            for first_trip in (0..table.trips.len()).step_by(columns_per_page) {
+                let trips = first_trip..(first_trip + columns_per_page).min(table.trips.len());
+                let right = left + trips.len() as f32 * CELL_WIDTH;
+                let bottom = top + rows.len() as f32 * ROW_HEIGHT;
+                let mut page = document.start_page_with(PageSettings::new(width, height));
+                let mut surface = page.surface();
+                fonts.draw(
+                    &mut surface,
+                    &format!("{title}  {}", table.direction),
+                    (MARGIN, MARGIN + TITLE_SIZE),
+                    TITLE_SIZE,
+                    Align::Left,
+                );
+                for (column, name) in table.trips[trips.clone()].iter().enumerate() {
+                    let x = left + column as f32 * CELL_WIDTH;
+                    let name = fonts.fit(name, TEXT_SIZE, CELL_WIDTH - 2.0);
+                    fonts.draw(
+                        &mut surface,
+                        &name,
+                        (x + CELL_WIDTH / 2.0, top - 4.0),
+                        TEXT_SIZE,
+                        Align::Center,
+                    );
+                }
+                draw::lines(
+                    &mut surface,
+                    (1..trips.len()).map(|column| {
+                        let x = left + column as f32 * CELL_WIDTH;
+                        [(x, top - HEADER_HEIGHT), (x, bottom)]
+                    }),
+                    draw::stroke(LIGHT_GRAY, 0.3),
+                );
+                // separate the stations, and lighter lines between the rows of a station
+                let row_lines = |first: bool| {
+                    rows.iter()
+                        .enumerate()
+                        .skip(1)
+                        .filter(move |(_, row)| row.first == first)
+                        .map(|(index, _)| {
+                            let y = top + index as f32 * ROW_HEIGHT;
+                            [(MARGIN, y), (right, y)]
+                        })
+                };
+                draw::lines(&mut surface, row_lines(true), draw::stroke(GRAY, 0.3));
+                draw::lines(
+                    &mut surface,
+                    row_lines(false).map(|[(_, y), end]| [(left - kind_width, y), end]),
+                    draw::stroke(LIGHT_GRAY, 0.2),
+                );
+                draw::lines(
+                    &mut surface,
+                    [
+                        [(MARGIN, top - HEADER_HEIGHT), (right, top - HEADER_HEIGHT)],
+                        [(MARGIN, top), (right, top)],
+                        [(MARGIN, bottom), (right, bottom)],
+                        [(MARGIN, top - HEADER_HEIGHT), (MARGIN, bottom)],
+                        [(left, top - HEADER_HEIGHT), (left, bottom)],
+                        [(right, top - HEADER_HEIGHT), (right, bottom)],
+                    ],
+                    draw::stroke(BLACK, 0.6),
+                );
+                for (index, row) in rows.iter().enumerate() {
+                    let baseline = top + (index + 1) as f32 * ROW_HEIGHT - 2.5;
+                    // repeat the station at the top of the page
+                    if row.first || index == 0 {
+                        let station = fonts.fit(&row.station, TEXT_SIZE, station_width - 4.0);
+                        fonts.draw(
+                            &mut surface,
+                            &station,
+                            (MARGIN + 2.0, baseline),
+                            TEXT_SIZE,
+                            Align::Left,
+                        );
+                    }
+                    fonts.draw(
+                        &mut surface,
+                        &row.kind,
+                        (left - kind_width / 2.0, baseline),
+                        TEXT_SIZE,
+                        Align::Center,
+                    );
+                    for (column, text) in row.cells[trips.clone()].iter().enumerate() {
+                        fonts.draw(
+                            &mut surface,
+                            text,
+                            (left + (column as f32 + 0.5) * CELL_WIDTH, baseline),
+                            TEXT_SIZE,
+                            Align::Center,
+                        );
+                    }
+                }
+                surface.finish();
+                page.finish();
+            }
+        }
+    }
+}
@@ -1,154 +1,110 @@
 //! # RAPTOR Module
-//! This crate bridges between Paiagram types and the raptor-rs crate
-
-// TODO: in case if the crate author updates, switch to iterators instead of vectors
-
-use std::borrow::Cow;
-
-use bevy::{ecs::system::SystemParam, prelude::*};
-use paiagram_core::entry::EntryQuery;
-use paiagram_core::station::{ParentStationOrStation, PlatformEntries, StationQuery};
-use paiagram_core::trip::TripQuery;
-use raptor::Timetable;
-
-pub use raptor::Journey;
-pub fn make_query_data(
-    (In(departure), In(start), In(end)): (In<usize>, In<Entity>, In<Entity>),
-    info: RaptorTimetable<'_, '_>,
-) -> Vec<raptor::Journey<Entity, Entity>> {
-    // TODO: add a more reasonable limitation for transfers
-    info.raptor(10, departure, start, end)
+//! Route finding with the Round-Based Public Transit Routing algorithm.
+//!
+//! The world is flattened into a [`Timetable`] for each service day and scenario the first time
+//! it is searched. Timetables are cached until trips, stations, or the project settings change.
+
+use bevy::{platform::collections::HashMap, prelude::*};
+use paiagram_core::entry::{EntryEstimate, EntryStop};
+use paiagram_core::graph::Node;
+use paiagram_core::settings::ProjectSettings;
+use paiagram_core::station::{Footpaths, MinimumTransferTime, Station};
+use paiagram_core::trip::calendar::{ServiceCalendar, ServiceDate};
+use paiagram_core::trip::connection::TripConnection;
+use paiagram_core::trip::frequency::TripFrequency;
+use paiagram_core::trip::{Trip, TripScenario, TripSchedule};
+use std::sync::Arc;
+
+pub mod matrix;
+mod search;
+mod timetable;
+
+pub use search::{Journey, Leg, Reach, TravelTimes};
+pub use timetable::{Timetable, TimetableBuilder};
+
+/// The default limit of trips in a journey
+pub const MAX_TRIPS: usize = 10;
+
+pub struct RaptorPlugin;
+impl Plugin for RaptorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TimetableCache>().add_systems(
+            Update,
+            (invalidate_timetables, matrix::apply_od_matrix_task),
+        );
+    }
 }
 
-#[derive(SystemParam)]
-pub struct RaptorTimetable<'w, 's> {
-    station_query: Query<'w, 's, StationQuery>,
-    platform_entry_query: Query<'w, 's, &'static PlatformEntries>,
-    parent_query: Query<'w, 's, &'static ChildOf>,
-    parent_station_or_station_query: Query<'w, 's, ParentStationOrStation>,
-    trip_query: Query<'w, 's, TripQuery>,
-    entry_query: Query<'w, 's, EntryQuery>,
+/// Timetables built for each service day and scenario
+#[derive(Resource, Default)]
+pub struct TimetableCache {
+    timetables: HashMap<(Option<ServiceDate>, Option<Entity>), Arc<Timetable>>,
 }
 
-impl RaptorTimetable<'_, '_> {
-    fn normalize_stop(&self, stop: Entity) -> Option<Entity> {
-        self.parent_station_or_station_query
-            .get(stop)
-            .ok()
-            .map(|it| it.parent())
-    }
-
-    fn route_stop_sequence(&self, route: Entity) -> Vec<Entity> {
-        let Ok(trip) = self.trip_query.get(route) else {
-            return Vec::new();
-        };
-
-        self.entry_query
-            .iter_many(trip.schedule.iter())
-            .filter_map(|entry| self.normalize_stop(entry.stop()))
-            .collect()
+impl TimetableCache {
+    /// The cached timetable of the service day and scenario, built if missing.
+    pub fn get(
+        &mut self,
+        service_day: Option<ServiceDate>,
+        scenario: Option<Entity>,
+        builder: &TimetableBuilder,
+    ) -> Arc<Timetable> {
+        self.timetables
+            .entry((service_day, scenario))
+            .or_insert_with(|| Arc::new(builder.build(service_day, scenario)))
+            .clone()
     }
 }
 
-impl raptor::Timetable for RaptorTimetable<'_, '_> {
-    type Route = Entity; // in this case it is the same as trips
-    type Stop = Entity;
-    type Trip = Entity;
-
-    fn get_routes_serving_stop(&self, stop: Self::Stop) -> Cow<'_, [Self::Route]> {
-        let Ok(station) = self.station_query.get(stop) else {
-            return Cow::Borrowed(&[]);
-        };
-
-        let mut routes = std::collections::BTreeSet::new();
-        for entry in station.passing_entries(&self.platform_entry_query) {
-            if let Ok(parent) = self.parent_query.get(entry) {
-                routes.insert(parent.parent());
-            }
-        }
-        routes.into_iter().collect()
-    }
-
-    fn get_arrival_time(&self, trip: Self::Trip, stop: Self::Stop) -> raptor::Tau {
-        let Ok(route_trip) = self.trip_query.get(trip) else {
-            return raptor::Tau::MAX;
-        };
-
-        self.entry_query
-            .iter_many(route_trip.schedule.iter())
-            .find_map(|it| {
-                let normalized_stop = self.normalize_stop(it.stop())?;
-                if normalized_stop == stop {
-                    return it.estimate.map(|estimate| estimate.arr.0 as usize);
-                }
-                None
-            })
-            .unwrap_or(raptor::Tau::MAX)
-    }
-
-    fn get_departure_time(&self, trip: Self::Trip, stop: Self::Stop) -> raptor::Tau {
-        let Ok(route_trip) = self.trip_query.get(trip) else {
-            return raptor::Tau::MAX;
-        };
-
-        self.entry_query
-            .iter_many(route_trip.schedule.iter())
-            .find_map(|it| {
-                let normalized_stop = self.normalize_stop(it.stop())?;
-                if normalized_stop == stop {
-                    return it.estimate.map(|estimate| estimate.dep.0 as usize);
-                }
-                None
-            })
-            .unwrap_or(raptor::Tau::MAX)
-    }
-
-    fn get_earlier_stop(
-        &self,
-        route: Self::Route,
-        left: Self::Stop,
-        right: Self::Stop,
-    ) -> Self::Stop {
-        let stops = self.route_stop_sequence(route);
-        let pos_l = stops.iter().position(|&s| s == left);
-        let pos_r = stops.iter().position(|&s| s == right);
-
-        match (pos_l, pos_r) {
-            (Some(_), None) => left,
-            (None, Some(_)) => right,
-            (Some(l), Some(r)) => {
-                if l < r {
-                    left
-                } else {
-                    right
-                }
-            }
-            (None, None) => right,
-        }
-    }
-
-    fn get_earliest_trip(
-        &self,
-        route: Self::Route,
-        at: raptor::Tau,
-        boarding_stop: Self::Stop,
-    ) -> Option<Self::Trip> {
-        let dep = self.get_departure_time(route, boarding_stop);
-        if dep >= at && dep != raptor::Tau::MAX {
-            Some(route)
-        } else {
-            None
-        }
-    }
-
-    fn get_footpaths_from(&self, _stop: Self::Stop) -> Cow<'_, [Self::Stop]> {
-        Cow::Borrowed(&[])
+fn invalidate_timetables(
+    mut cache: ResMut<TimetableCache>,
+    changed: Query<
+        (),
+        Or<(
+            Changed<EntryEstimate>,
+            Changed<EntryStop>,
+            Changed<TripSchedule>,
+            Changed<TripFrequency>,
+            Changed<ServiceCalendar>,
+            Changed<TripScenario>,
+            Changed<TripConnection>,
+            Changed<Footpaths>,
+            Changed<MinimumTransferTime>,
+            Changed<Node>,
+        )>,
+    >,
+    mut removed_trips: RemovedComponents<Trip>,
+    mut removed_estimates: RemovedComponents<EntryEstimate>,
+    mut removed_frequencies: RemovedComponents<TripFrequency>,
+    mut removed_calendars: RemovedComponents<ServiceCalendar>,
+    mut removed_scenarios: RemovedComponents<TripScenario>,
+    mut removed_stations: RemovedComponents<Station>,
+    mut removed_connections: RemovedComponents<TripConnection>,
+    mut removed_footpaths: RemovedComponents<Footpaths>,
+    mut removed_transfer_times: RemovedComponents<MinimumTransferTime>,
+    settings: Res<ProjectSettings>,
+) {
+    let removed = removed_trips.read().count()
+        + removed_estimates.read().count()
+        + removed_frequencies.read().count()
+        + removed_calendars.read().count()
+        + removed_scenarios.read().count()
+        + removed_stations.read().count()
+        + removed_connections.read().count()
+        + removed_footpaths.read().count()
+        + removed_transfer_times.read().count();
+    if !cache.timetables.is_empty() && (removed > 0 || !changed.is_empty() || settings.is_changed())
+    {
+        cache.timetables.clear();
     }
+}
 
-    fn get_stops_after(&self, route: Self::Route, stop: Self::Stop) -> Cow<'_, [Self::Stop]> {
-        self.route_stop_sequence(route)
-            .into_iter()
-            .skip_while(|&s| s != stop)
-            .collect()
-    }
+/// The timetable of trips running on the service day, or on every day if none is given. Only
+/// trips in the scenario are included if one is given.
+pub fn timetable(
+    In((service_day, scenario)): In<(Option<ServiceDate>, Option<Entity>)>,
+    mut cache: ResMut<TimetableCache>,
+    builder: TimetableBuilder,
+) -> Arc<Timetable> {
+    cache.get(service_day, scenario, &builder)
 }
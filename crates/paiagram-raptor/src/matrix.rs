@@ -0,0 +1,159 @@
+//! # Matrix
+//! Travel times between every pair of selected stations, computed in the background.
+
+use crate::{TimetableBuilder, TimetableCache, TravelTimes};
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future::poll_once};
+use paiagram_core::{
+    export::{ExportObject, csv_field},
+    trip::calendar::ServiceDate,
+    units::time::{Duration, TimetableTime},
+};
+use std::io::Write;
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+/// The stations and departure times of an origin-destination matrix
+#[derive(Clone)]
+pub struct OdMatrixRequest {
+    pub stations: Vec<Entity>,
+    pub earliest: TimetableTime,
+    pub latest: TimetableTime,
+    /// Time between the departures sampled within the window
+    pub step: Duration,
+    pub max_trips: usize,
+    pub service_day: Option<ServiceDate>,
+    pub scenario: Option<Entity>,
+}
+
+/// Travel times between every pair of stations
+#[derive(Resource)]
+pub struct OdMatrix {
+    pub request: OdMatrixRequest,
+    /// Names of the stations, in the order of the request
+    pub names: Vec<String>,
+    /// Travel times from each station to each station
+    pub travel_times: Vec<Vec<Option<TravelTimes>>>,
+}
+
+#[derive(Resource)]
+pub struct OdMatrixTask {
+    task: Task<OdMatrix>,
+    finished: Arc<AtomicUsize>,
+    pub total: usize,
+}
+
+impl OdMatrixTask {
+    /// Origins finished, and the total number of origins
+    pub fn progress(&self) -> (usize, usize) {
+        (self.finished.load(Ordering::Relaxed), self.total)
+    }
+}
+
+/// Start computing the matrix in the background, replacing the matrix being computed.
+pub fn compute_od_matrix(
+    In(request): In<OdMatrixRequest>,
+    mut cache: ResMut<TimetableCache>,
+    builder: TimetableBuilder,
+    name_q: Query<&Name>,
+    mut commands: Commands,
+) {
+    let timetable = cache.get(request.service_day, request.scenario, &builder);
+    let names = request
+        .stations
+        .iter()
+        .map(|it| {
+            name_q
+                .get(*it)
+                .map_or_else(|_| String::new(), Name::to_string)
+        })
+        .collect();
+    let total = request.stations.len();
+    let finished = Arc::new(AtomicUsize::new(0));
+    let finished_in_task = Arc::clone(&finished);
+
+    let task = AsyncComputeTaskPool::get().spawn(async move {
+        let travel_times = request
+            .stations
+            .iter()
+            .map(|origin| {
+                let row = timetable.travel_times(
+                    *origin,
+                    &request.stations,
+                    request.earliest,
+                    request.latest,
+                    request.step,
+                    request.max_trips,
+                );
+                finished_in_task.fetch_add(1, Ordering::Relaxed);
+                row
+            })
+            .collect();
+        OdMatrix {
+            request,
+            names,
+            travel_times,
+        }
+    });
+
+    commands.insert_resource(OdMatrixTask {
+        task,
+        finished,
+        total,
+    });
+}
+
+pub(crate) fn apply_od_matrix_task(mut commands: Commands, task: Option<ResMut<OdMatrixTask>>) {
+    let Some(mut task) = task else {
+        return;
+    };
+    let Some(matrix) = block_on(poll_once(&mut task.task)) else {
+        return;
+    };
+    info!(
+        "Travel time matrix completed: stations={}",
+        matrix.names.len()
+    );
+    commands.insert_resource(matrix);
+    commands.remove_resource::<OdMatrixTask>();
+}
+
+/// One row for each pair of stations. Pairs that cannot be reached have empty times, and the
+/// median and longest times are empty if too few departures reach the destination. The last two
+/// columns count the departures that reach it and the departures sampled.
+impl ExportObject for OdMatrix {
+    fn export_to_buffer(&mut self, buffer: &mut Vec<u8>) {
+        writeln!(
+            buffer,
+            "origin,destination,min_seconds,median_seconds,max_seconds,transfers,reachable_departures,departures"
+        )
+        .unwrap();
+        for (origin, row) in self.names.iter().zip(&self.travel_times) {
+            for (destination, travel_times) in self.names.iter().zip(row) {
+                write!(buffer, "{},{},", csv_field(origin), csv_field(destination)).unwrap();
+                match travel_times {
+                    Some(it) => writeln!(
+                        buffer,
+                        "{},{},{},{},{},{}",
+                        it.min.0,
+                        it.median.map(|it| it.0.to_string()).unwrap_or_default(),
+                        it.max.map(|it| it.0.to_string()).unwrap_or_default(),
+                        it.transfers,
+                        it.reachable,
+                        it.departures
+                    ),
+                    None => writeln!(buffer, ",,,,,"),
+                }
+                .unwrap();
+            }
+        }
+    }
+    fn extension(&self) -> impl AsRef<str> {
+        ".csv"
+    }
+    fn filename(&self) -> impl AsRef<str> {
+        "od_matrix"
+    }
+}
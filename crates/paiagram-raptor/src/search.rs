@@ -0,0 +1,647 @@
+//! # Search
+//! The rounds of RAPTOR over a [`Timetable`].
+//!
+//! Round `k` finds the earliest arrival at every stop using at most `k` trips. Passengers need
+//! the stop's minimum transfer time to change between trips, unless the trips form a guaranteed
+//! connection, while passengers who walked to a stop can board right away.
+
+use crate::timetable::{Route, Timetable};
+use bevy::{ecs::entity::EntityHashMap, prelude::*};
+use paiagram_core::units::time::{Duration, TimetableTime};
+
+/// A part of a journey
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Leg {
+    /// Ride a trip from one stop to another
+    Ride {
+        trip: Entity,
+        from: Entity,
+        to: Entity,
+        departure: TimetableTime,
+        arrival: TimetableTime,
+    },
+    /// Walk from one station to another
+    Walk {
+        from: Entity,
+        to: Entity,
+        departure: TimetableTime,
+        arrival: TimetableTime,
+    },
+}
+
+/// A way to get from one stop to another
+#[derive(Debug, Clone, PartialEq)]
+pub struct Journey {
+    pub departure: TimetableTime,
+    pub arrival: TimetableTime,
+    pub legs: Vec<Leg>,
+}
+
+impl Journey {
+    /// Number of trips taken
+    pub fn trips(&self) -> usize {
+        self.legs
+            .iter()
+            .filter(|it| matches!(it, Leg::Ride { .. }))
+            .count()
+    }
+}
+
+/// How well a stop can be reached from the origin
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Reach {
+    /// The earliest arrival, departing no earlier than the start of the window
+    pub arrival: TimetableTime,
+    /// The shortest travel time of journeys departing within the window
+    pub travel_time: Duration,
+}
+
+/// Travel times from one stop to another, for departures throughout a window. Departures from
+/// which the destination cannot be reached count as infinitely long, so the median is `None` if
+/// at least half of the departures cannot reach it, and the maximum if any of them cannot.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TravelTimes {
+    pub min: Duration,
+    pub median: Option<Duration>,
+    pub max: Option<Duration>,
+    /// Transfers of the fastest journey
+    pub transfers: usize,
+    /// Departures from which the destination can be reached
+    pub reachable: usize,
+    /// Departures sampled within the window
+    pub departures: usize,
+}
+
+/// How a stop was reached
+#[derive(Clone, Copy)]
+enum Parent {
+    Origin,
+    Ride {
+        route: usize,
+        trip: usize,
+        board: usize,
+        alight: usize,
+    },
+    Walk {
+        from: usize,
+    },
+}
+
+#[derive(Clone, Copy)]
+struct Label {
+    time: TimetableTime,
+    parent: Parent,
+    /// The round the label was found in
+    round: usize,
+}
+
+/// The labels of every stop after each round. Labels are kept between runs of a range query,
+/// where each run departs earlier than the last.
+pub(crate) struct Search<'a> {
+    timetable: &'a Timetable,
+    origin: usize,
+    rounds: Vec<Vec<Option<Label>>>,
+    best: Vec<Option<TimetableTime>>,
+    marked: Vec<bool>,
+}
+
+impl<'a> Search<'a> {
+    pub(crate) fn new(timetable: &'a Timetable, origin: usize, max_trips: usize) -> Self {
+        let stop_count = timetable.stops.len();
+        Self {
+            timetable,
+            origin,
+            rounds: vec![vec![None; stop_count]; max_trips + 1],
+            best: vec![None; stop_count],
+            marked: vec![false; stop_count],
+        }
+    }
+
+    /// Run the rounds departing from the origin at `departure`.
+    pub(crate) fn run(&mut self, departure: TimetableTime) {
+        let timetable = self.timetable;
+        let stop_count = timetable.stops.len();
+        self.rounds[0][self.origin] = Some(Label {
+            time: departure,
+            parent: Parent::Origin,
+            round: 0,
+        });
+        let best = &mut self.best[self.origin];
+        *best = Some(best.map_or(departure, |it| it.min(departure)));
+        self.marked.fill(false);
+        self.marked[self.origin] = true;
+        timetable.walk(&mut self.rounds[0], &mut self.best, &mut self.marked, 0);
+
+        let mut queue: Vec<Option<usize>> = vec![None; timetable.routes.len()];
+        for round in 1..self.rounds.len() {
+            // routes to scan, from the first marked stop
+            for stop in (0..stop_count).filter(|it| self.marked[*it]) {
+                for &(route, pos) in &timetable.routes_serving_stop[stop] {
+                    let start = &mut queue[route];
+                    *start = Some(start.map_or(pos, |it| it.min(pos)));
+                }
+            }
+            self.marked.fill(false);
+            let (previous, labels) = self.rounds.split_at_mut(round);
+            let previous = &previous[round - 1];
+            let labels = &mut labels[0];
+            // at most k trips also covers fewer trips
+            for (label, previous) in labels.iter_mut().zip(previous) {
+                if let Some(previous) = previous
+                    && label.is_none_or(|it| previous.time < it.time)
+                {
+                    *label = Some(*previous);
+                }
+            }
+            for (route_idx, start) in queue.iter_mut().enumerate() {
+                let Some(start) = start.take() else {
+                    continue;
+                };
+                let route = &timetable.routes[route_idx];
+                // the trip currently ridden, and where it was boarded
+                let mut boarded: Option<(usize, usize)> = None;
+                for (pos, &stop) in route.stops.iter().enumerate().skip(start) {
+                    if let Some((trip, board)) = boarded {
+                        let arrival = route.trips[trip].arrivals[pos];
+                        if self.best[stop].is_none_or(|it| arrival < it) {
+                            self.best[stop] = Some(arrival);
+                            labels[stop] = Some(Label {
+                                time: arrival,
+                                parent: Parent::Ride {
+                                    route: route_idx,
+                                    trip,
+                                    board,
+                                    alight: pos,
+                                },
+                                round,
+                            });
+                            self.marked[stop] = true;
+                        }
+                    }
+                    if pos + 1 < route.stops.len()
+                        && let Some(label) = previous[stop]
+                        && let Some(trip) = timetable.earliest_trip(route, pos, stop, label)
+                        && boarded.is_none_or(|(it, _)| trip < it)
+                    {
+                        boarded = Some((trip, pos));
+                    }
+                }
+            }
+            timetable.walk(labels, &mut self.best, &mut self.marked, round);
+            if !self.marked.contains(&true) {
+                break;
+            }
+        }
+    }
+
+    /// The fewest trips taken to arrive at the stop the earliest.
+    fn fewest_trips(&self, stop: usize) -> usize {
+        self.rounds
+            .iter()
+            .position(|it| it[stop].is_some_and(|it| Some(it.time) == self.best[stop]))
+            .unwrap_or_default()
+    }
+
+    /// The arrival at the stop found in the round, if the round improved it.
+    fn arrival(&self, stop: usize, round: usize) -> Option<TimetableTime> {
+        self.rounds[round][stop]
+            .filter(|it| it.round == round)
+            .map(|it| it.time)
+    }
+
+    /// Trace the journey to the destination found in the round back to the origin.
+    fn journey(&self, destination: usize, round: usize) -> Option<Journey> {
+        let timetable = self.timetable;
+        let mut label = self.rounds[round][destination]?;
+        let arrival = label.time;
+        let mut stop = destination;
+        let mut legs = Vec::new();
+        loop {
+            match label.parent {
+                Parent::Origin => break,
+                Parent::Walk { from } => {
+                    let previous = self.rounds[label.round][from]?;
+                    legs.push(Leg::Walk {
+                        from: timetable.stops[from],
+                        to: timetable.stops[stop],
+                        departure: previous.time,
+                        arrival: label.time,
+                    });
+                    stop = from;
+                    label = previous;
+                }
+                Parent::Ride {
+                    route,
+                    trip,
+                    board,
+                    alight,
+                } => {
+                    let route = &timetable.routes[route];
+                    let trip = &route.trips[trip];
+                    legs.push(Leg::Ride {
+                        trip: trip.trip,
+                        from: timetable.stops[route.stops[board]],
+                        to: timetable.stops[route.stops[alight]],
+                        departure: trip.departures[board],
+                        arrival: trip.arrivals[alight],
+                    });
+                    stop = route.stops[board];
+                    label = self.rounds[label.round - 1][stop]?;
+                }
+            }
+        }
+        legs.reverse();
+        Some(Journey {
+            departure: match legs.first() {
+                Some(Leg::Ride { departure, .. }) | Some(Leg::Walk { departure, .. }) => *departure,
+                None => label.time,
+            },
+            arrival,
+            legs,
+        })
+    }
+}
+
+impl Timetable {
+    /// Find the journeys from `origin` to `destination` departing no earlier than `departure`.
+    /// Each journey arrives earlier than the journeys taking fewer trips, and no journey takes
+    /// more than `max_trips` trips.
+    pub fn journeys(
+        &self,
+        origin: Entity,
+        destination: Entity,
+        departure: TimetableTime,
+        max_trips: usize,
+    ) -> Vec<Journey> {
+        let (Some(&origin), Some(&destination)) = (
+            self.stop_index.get(&origin),
+            self.stop_index.get(&destination),
+        ) else {
+            return Vec::new();
+        };
+        let mut search = Search::new(self, origin, max_trips);
+        search.run(departure);
+        (0..=max_trips)
+            .filter(|it| search.arrival(destination, *it).is_some())
+            .filter_map(|it| search.journey(destination, it))
+            .collect()
+    }
+
+    /// Find every journey from `origin` to `destination` departing between `earliest` and
+    /// `latest` that no other journey beats. A journey is beaten by another journey that departs
+    /// no earlier, arrives no later, and takes no more trips.
+    ///
+    /// The search runs once for each departure from the origin in the window, from the latest
+    /// to the earliest, and keeps the arrivals found by later runs.
+    pub fn range_journeys(
+        &self,
+        origin: Entity,
+        destination: Entity,
+        earliest: TimetableTime,
+        latest: TimetableTime,
+        max_trips: usize,
+    ) -> Vec<Journey> {
+        let (Some(&origin), Some(&destination)) = (
+            self.stop_index.get(&origin),
+            self.stop_index.get(&destination),
+        ) else {
+            return Vec::new();
+        };
+        let mut search = Search::new(self, origin, max_trips);
+        let mut found: Vec<Journey> = Vec::new();
+        for departure in self.departures(origin, earliest, latest) {
+            let before: Vec<_> = (0..=max_trips)
+                .map(|it| search.rounds[it][destination].map(|it| it.time))
+                .collect();
+            search.run(departure);
+            for round in 0..=max_trips {
+                if let Some(arrival) = search.arrival(destination, round)
+                    && before[round] != Some(arrival)
+                    && let Some(journey) = search.journey(destination, round)
+                {
+                    found.push(journey);
+                }
+            }
+        }
+        let beats = |a: &Journey, b: &Journey| {
+            a.departure >= b.departure && a.arrival <= b.arrival && a.trips() <= b.trips()
+        };
+        let mut journeys: Vec<Journey> = Vec::new();
+        for journey in found {
+            if journeys.iter().any(|it| beats(it, &journey)) {
+                continue;
+            }
+            journeys.retain(|it| !beats(&journey, it));
+            journeys.push(journey);
+        }
+        journeys.sort_by_key(|it| (it.departure, it.trips()));
+        journeys
+    }
+
+    /// Find how well every stop can be reached from `origin`, departing between `earliest` and
+    /// `latest`. Stops that cannot be reached are left out.
+    pub fn reach(
+        &self,
+        origin: Entity,
+        earliest: TimetableTime,
+        latest: TimetableTime,
+        max_trips: usize,
+    ) -> EntityHashMap<Reach> {
+        let Some(&origin) = self.stop_index.get(&origin) else {
+            return EntityHashMap::default();
+        };
+        let mut departures = self.departures(origin, earliest, latest);
+        // the last run also finds the earliest arrivals on foot
+        if departures.last() != Some(&earliest) {
+            departures.push(earliest);
+        }
+        let mut search = Search::new(self, origin, max_trips);
+        let mut travel_times: Vec<Option<Duration>> = vec![None; self.stops.len()];
+        for departure in departures {
+            let before = search.best.clone();
+            search.run(departure);
+            for (stop, arrival) in search.best.iter().enumerate() {
+                if let Some(arrival) = *arrival
+                    && before[stop] != Some(arrival)
+                {
+                    let travel_time = arrival - departure;
+                    let shortest = &mut travel_times[stop];
+                    *shortest = Some(shortest.map_or(travel_time, |it| it.min(travel_time)));
+                }
+            }
+        }
+        search
+            .best
+            .iter()
+            .zip(travel_times)
+            .enumerate()
+            .filter_map(|(stop, (arrival, travel_time))| {
+                let reach = Reach {
+                    arrival: (*arrival)?,
+                    travel_time: travel_time?,
+                };
+                Some((self.stops[stop], reach))
+            })
+            .collect()
+    }
+
+    /// Find the travel times from `origin` to each of the `destinations`, leaving the origin
+    /// every `step` between `earliest` and `latest`. Travel times include waiting for the first
+    /// trip. Destinations that cannot be reached from any of the departures have no travel times.
+    pub fn travel_times(
+        &self,
+        origin: Entity,
+        destinations: &[Entity],
+        earliest: TimetableTime,
+        latest: TimetableTime,
+        step: Duration,
+        max_trips: usize,
+    ) -> Vec<Option<TravelTimes>> {
+        let Some(&origin) = self.stop_index.get(&origin) else {
+            return vec![None; destinations.len()];
+        };
+        let destinations: Vec<Option<usize>> = destinations
+            .iter()
+            .map(|it| self.stop_index.get(it).copied())
+            .collect();
+        let step = step.max(Duration(1));
+        let mut samples: Vec<Vec<(Duration, usize)>> = vec![Vec::new(); destinations.len()];
+        let mut search = Search::new(self, origin, max_trips);
+        let mut departures = 0;
+        let mut departure = latest;
+        while departure >= earliest {
+            search.run(departure);
+            departures += 1;
+            for (samples, destination) in samples.iter_mut().zip(&destinations) {
+                if let Some(stop) = *destination
+                    && let Some(arrival) = search.best[stop]
+                {
+                    samples.push((arrival - departure, search.fewest_trips(stop)));
+                }
+            }
+            departure -= step;
+        }
+        samples
+            .into_iter()
+            .map(|mut samples| {
+                samples.sort_unstable();
+                let (min, trips) = *samples.first()?;
+                let max = samples.last()?.0;
+                Some(TravelTimes {
+                    min,
+                    median: samples.get(departures / 2).map(|it| it.0),
+                    max: (samples.len() == departures).then_some(max),
+                    transfers: trips.saturating_sub(1),
+                    reachable: samples.len(),
+                    departures,
+                })
+            })
+            .collect()
+    }
+
+    /// Times passengers can leave the stop to catch a trip departing within the window, from
+    /// the latest to the earliest.
+    fn departures(
+        &self,
+        stop: usize,
+        earliest: TimetableTime,
+        latest: TimetableTime,
+    ) -> Vec<TimetableTime> {
+        let mut departures: Vec<TimetableTime> = std::iter::once((stop, Duration::ZERO))
+            .chain(self.footpaths[stop].iter().copied())
+            .flat_map(|(from, walking_time)| {
+                self.routes_serving_stop[from]
+                    .iter()
+                    .flat_map(|&(route, pos)| {
+                        self.routes[route]
+                            .trips
+                            .iter()
+                            .map(move |it| it.departures[pos])
+                    })
+                    .map(move |it| it - walking_time)
+            })
+            .filter(|it| (earliest..=latest).contains(it))
+            .collect();
+        departures.sort_unstable_by(|a, b| b.cmp(a));
+        departures.dedup();
+        departures
+    }
+
+    /// The earliest trip of the route that can be boarded at the stop, at position `pos`.
+    fn earliest_trip(&self, route: &Route, pos: usize, stop: usize, label: Label) -> Option<usize> {
+        let (ready, feeder) = match label.parent {
+            Parent::Ride {
+                route: feeder_route,
+                trip,
+                ..
+            } => (
+                label.time + self.transfer_times[stop],
+                Some(self.routes[feeder_route].trips[trip].trip),
+            ),
+            Parent::Origin | Parent::Walk { .. } => (label.time, None),
+        };
+        let earliest = route.trips.partition_point(|it| it.departures[pos] < ready);
+        let earliest = (earliest < route.trips.len()).then_some(earliest);
+        // trips waiting for the feeder can be boarded without the transfer time
+        let waiting = feeder
+            .and_then(|it| self.connections.get(&(it, stop)))
+            .and_then(|connecting| {
+                // a connecting trip departing before the feeder arrives has left already
+                route.trips.iter().position(|it| {
+                    connecting.contains(&it.trip) && it.departures[pos] >= label.time
+                })
+            });
+        match (earliest, waiting) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Walk from the stops reached in this round to their neighbours.
+    fn walk(
+        &self,
+        labels: &mut [Option<Label>],
+        best: &mut [Option<TimetableTime>],
+        marked: &mut [bool],
+        round: usize,
+    ) {
+        let sources: Vec<(usize, TimetableTime)> = (0..labels.len())
+            .filter(|it| marked[*it])
+            .filter_map(|it| Some((it, labels[it]?.time)))
+            .collect();
+        for (from, time) in sources {
+            for &(to, walking_time) in &self.footpaths[from] {
+                let arrival = time + walking_time;
+                if best[to].is_none_or(|it| arrival < it) {
+                    best[to] = Some(arrival);
+                    labels[to] = Some(Label {
+                        time: arrival,
+                        parent: Parent::Walk { from },
+                        round,
+                    });
+                    marked[to] = true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timetable::RouteTrip;
+    use bevy::platform::collections::HashMap;
+
+    /// A feeder from stop 0 to stop 1 arriving at 08:30, and trips from stop 1 to stop 2
+    /// departing at the given times. The first of them waits for the feeder.
+    fn timetable(departures: &[TimetableTime]) -> (Timetable, Vec<Entity>, Vec<Entity>) {
+        let mut world = World::new();
+        let stops: Vec<Entity> = (0..3).map(|_| world.spawn_empty().id()).collect();
+        let feeder = world.spawn_empty().id();
+        let onward: Vec<Entity> = departures
+            .iter()
+            .map(|_| world.spawn_empty().id())
+            .collect();
+        let routes = vec![
+            Route {
+                stops: vec![0, 1],
+                trips: vec![RouteTrip {
+                    trip: feeder,
+                    arrivals: vec![
+                        TimetableTime::from_hms(8, 0, 0),
+                        TimetableTime::from_hms(8, 30, 0),
+                    ],
+                    departures: vec![
+                        TimetableTime::from_hms(8, 0, 0),
+                        TimetableTime::from_hms(8, 30, 0),
+                    ],
+                }],
+            },
+            Route {
+                stops: vec![1, 2],
+                trips: departures
+                    .iter()
+                    .zip(&onward)
+                    .map(|(&departure, &trip)| RouteTrip {
+                        trip,
+                        arrivals: vec![departure, departure + Duration::from_hms(0, 30, 0)],
+                        departures: vec![departure, departure + Duration::from_hms(0, 30, 0)],
+                    })
+                    .collect(),
+            },
+        ];
+        let timetable = Timetable {
+            stop_index: stops
+                .iter()
+                .enumerate()
+                .map(|(idx, it)| (*it, idx))
+                .collect(),
+            stops: stops.clone(),
+            routes,
+            routes_serving_stop: vec![vec![(0, 0)], vec![(1, 0)], Vec::new()],
+            footpaths: vec![Vec::new(); 3],
+            transfer_times: vec![Duration::from_hms(0, 10, 0); 3],
+            connections: HashMap::from_iter([((feeder, 1), vec![onward[0]])]),
+        };
+        (timetable, stops, onward)
+    }
+
+    fn onward_trip(journey: &Journey) -> Option<Entity> {
+        match journey.legs.last()? {
+            Leg::Ride { trip, .. } => Some(*trip),
+            Leg::Walk { .. } => None,
+        }
+    }
+
+    #[test]
+    fn connection_skips_transfer_time() {
+        let (timetable, stops, onward) = timetable(&[
+            TimetableTime::from_hms(8, 35, 0),
+            TimetableTime::from_hms(8, 50, 0),
+        ]);
+        let journeys = timetable.journeys(stops[0], stops[2], TimetableTime::from_hms(7, 0, 0), 2);
+        assert_eq!(journeys.len(), 1);
+        assert_eq!(onward_trip(&journeys[0]), Some(onward[0]));
+        assert_eq!(journeys[0].arrival, TimetableTime::from_hms(9, 5, 0));
+    }
+
+    #[test]
+    fn connection_departing_before_feeder_arrives() {
+        let (timetable, stops, onward) = timetable(&[
+            TimetableTime::from_hms(8, 20, 0),
+            TimetableTime::from_hms(8, 50, 0),
+        ]);
+        let journeys = timetable.journeys(stops[0], stops[2], TimetableTime::from_hms(7, 0, 0), 2);
+        assert_eq!(journeys.len(), 1);
+        assert_eq!(onward_trip(&journeys[0]), Some(onward[1]));
+        assert_eq!(journeys[0].arrival, TimetableTime::from_hms(9, 20, 0));
+    }
+
+    #[test]
+    fn unreachable_departures_count_as_infinite() {
+        let (timetable, stops, _) = timetable(&[
+            TimetableTime::from_hms(9, 0, 0),
+            TimetableTime::from_hms(10, 0, 0),
+        ]);
+        // Leaving at 07:50, 08:20, ..., 10:20. Nothing departs after 10:20.
+        let travel_times = timetable.travel_times(
+            stops[1],
+            &[stops[2]],
+            TimetableTime::from_hms(7, 50, 0),
+            TimetableTime::from_hms(10, 20, 0),
+            Duration::from_hms(0, 30, 0),
+            2,
+        );
+        assert_eq!(
+            travel_times[0],
+            Some(TravelTimes {
+                min: Duration::from_hms(0, 40, 0),
+                median: Some(Duration::from_hms(1, 10, 0)),
+                max: None,
+                transfers: 0,
+                reachable: 5,
+                departures: 6,
+            })
+        );
+    }
+}
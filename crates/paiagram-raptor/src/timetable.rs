@@ -0,0 +1,245 @@
+//! # Timetable
+//! Trips, stops, and footpaths flattened into plain data for route finding.
+//!
+//! Trips with the same sequence of stops are grouped into routes. Trips in a route are sorted,
+//! and never overtake each other, so that the earliest trip at any stop can be found with a
+//! binary search. Trips that would overtake another trip are put into another route with the
+//! same stops.
+
+use bevy::{
+    ecs::{entity::EntityHashMap, system::SystemParam},
+    platform::collections::HashMap,
+    prelude::*,
+};
+use paiagram_core::{
+    entry::{EntryEstimate, EntryQuery},
+    graph::{GraphSpatialIndex, Node},
+    settings::ProjectSettings,
+    station::{Footpaths, MinimumTransferTime, ParentStationOrStation, Station},
+    trip::{TripQuery, calendar::ServiceDate, connection::TripConnection},
+    units::time::{Duration, TimetableTime},
+};
+
+/// A trip, or a run of a frequency-based trip, in a route
+pub(crate) struct RouteTrip {
+    pub trip: Entity,
+    pub arrivals: Vec<TimetableTime>,
+    pub departures: Vec<TimetableTime>,
+}
+
+impl RouteTrip {
+    /// Whether the trip is never later than `other` at any stop.
+    fn is_before(&self, other: &Self) -> bool {
+        self.arrivals
+            .iter()
+            .zip(&other.arrivals)
+            .all(|(a, b)| a <= b)
+            && self
+                .departures
+                .iter()
+                .zip(&other.departures)
+                .all(|(a, b)| a <= b)
+    }
+}
+
+/// Trips calling at the same stops in the same order
+pub(crate) struct Route {
+    pub stops: Vec<usize>,
+    /// Sorted by departure, at every stop
+    pub trips: Vec<RouteTrip>,
+}
+
+/// Trips running on one service day, prepared for route finding. Stops are stations, and
+/// platforms are merged into their stations.
+pub struct Timetable {
+    pub(crate) stops: Vec<Entity>,
+    pub(crate) stop_index: EntityHashMap<usize>,
+    pub(crate) routes: Vec<Route>,
+    /// Routes calling at each stop, and the position of the stop in the route
+    pub(crate) routes_serving_stop: Vec<Vec<(usize, usize)>>,
+    /// Stops that can be reached on foot from each stop, and the walking time
+    pub(crate) footpaths: Vec<Vec<(usize, Duration)>>,
+    pub(crate) transfer_times: Vec<Duration>,
+    /// Trips waiting for a feeder trip at a stop
+    pub(crate) connections: HashMap<(Entity, usize), Vec<Entity>>,
+}
+
+#[derive(SystemParam)]
+pub struct TimetableBuilder<'w, 's> {
+    station_q: Query<'w, 's, (Entity, &'static Node), With<Station>>,
+    trip_q: Query<'w, 's, TripQuery>,
+    entry_q: Query<'w, 's, EntryQuery>,
+    estimate_q: Query<'w, 's, &'static EntryEstimate>,
+    parent_station_or_station: Query<'w, 's, ParentStationOrStation>,
+    footpath_q: Query<'w, 's, (Entity, &'static Footpaths)>,
+    transfer_time_q: Query<'w, 's, &'static MinimumTransferTime>,
+    connection_q: Query<'w, 's, &'static TripConnection>,
+    spatial_index: Res<'w, GraphSpatialIndex>,
+    settings: Res<'w, ProjectSettings>,
+}
+
+impl TimetableBuilder<'_, '_> {
+    /// Build the timetable of trips running on the service day, in the scenario if one is given.
+    pub fn build(&self, service_day: Option<ServiceDate>, scenario: Option<Entity>) -> Timetable {
+        let stops: Vec<Entity> = self.station_q.iter().map(|(it, _)| it).collect();
+        let stop_index: EntityHashMap<usize> = stops
+            .iter()
+            .enumerate()
+            .map(|(idx, it)| (*it, idx))
+            .collect();
+        let normalize = |stop: Entity| -> Option<usize> {
+            let station = self.parent_station_or_station.get(stop).ok()?;
+            stop_index.get(&station.parent()).copied()
+        };
+
+        // trips and runs by their stops
+        let mut patterns: HashMap<Vec<usize>, Vec<RouteTrip>> = HashMap::new();
+        for trip in self
+            .trip_q
+            .iter()
+            .filter(|it| it.runs_on(service_day) && it.is_in_scenario(scenario))
+        {
+            let mut pattern = Vec::new();
+            let mut arrivals = Vec::new();
+            let mut departures = Vec::new();
+            for entry in self.entry_q.iter_many(trip.schedule.iter()) {
+                let (Some(estimate), Some(stop)) = (entry.estimate, normalize(entry.stop())) else {
+                    continue;
+                };
+                pattern.push(stop);
+                arrivals.push(estimate.arr);
+                departures.push(estimate.dep);
+            }
+            if pattern.len() < 2 {
+                continue;
+            }
+            let runs = patterns.entry(pattern).or_default();
+            for offset in trip.run_offsets(&self.estimate_q) {
+                runs.push(RouteTrip {
+                    trip: trip.entity,
+                    arrivals: arrivals.iter().map(|it| *it + offset).collect(),
+                    departures: departures.iter().map(|it| *it + offset).collect(),
+                });
+            }
+        }
+
+        let mut routes: Vec<Route> = Vec::new();
+        for (pattern, mut trips) in patterns {
+            trips.sort_by_key(|it| it.departures[0]);
+            let first = routes.len();
+            for trip in trips {
+                let route = routes[first..]
+                    .iter_mut()
+                    .find(|route| route.trips.last().is_some_and(|it| it.is_before(&trip)));
+                match route {
+                    Some(route) => route.trips.push(trip),
+                    None => routes.push(Route {
+                        stops: pattern.clone(),
+                        trips: vec![trip],
+                    }),
+                }
+            }
+        }
+
+        let mut routes_serving_stop = vec![Vec::new(); stops.len()];
+        for (route_idx, route) in routes.iter().enumerate() {
+            // the last stop cannot be boarded at
+            for (pos, stop) in route.stops.iter().enumerate().take(route.stops.len() - 1) {
+                routes_serving_stop[*stop].push((route_idx, pos));
+            }
+        }
+
+        let transfer_times = stops
+            .iter()
+            .map(|it| {
+                self.transfer_time_q
+                    .get(*it)
+                    .map_or(self.settings.minimum_transfer_time, |it| it.0)
+            })
+            .collect();
+
+        let mut connections: HashMap<(Entity, usize), Vec<Entity>> = HashMap::new();
+        for connection in &self.connection_q {
+            if let Some(stop) = normalize(connection.station) {
+                connections
+                    .entry((connection.feeder, stop))
+                    .or_default()
+                    .push(connection.connecting);
+            }
+        }
+
+        Timetable {
+            footpaths: self.footpaths(&stops, &stop_index),
+            stops,
+            stop_index,
+            routes,
+            routes_serving_stop,
+            transfer_times,
+            connections,
+        }
+    }
+
+    /// Walking links between stations, added by hand or found within the walking distance.
+    fn footpaths(
+        &self,
+        stops: &[Entity],
+        stop_index: &EntityHashMap<usize>,
+    ) -> Vec<Vec<(usize, Duration)>> {
+        let mut footpaths: Vec<Vec<(usize, Duration)>> = vec![Vec::new(); stops.len()];
+        // footpaths can be walked in both directions
+        for (from, links) in &self.footpath_q {
+            let Some(&from) = stop_index.get(&from) else {
+                continue;
+            };
+            for link in links.iter() {
+                if let Some(&to) = stop_index.get(&link.to) {
+                    add_footpath(&mut footpaths[from], to, link.walking_time);
+                    add_footpath(&mut footpaths[to], from, link.walking_time);
+                }
+            }
+        }
+        let Some(distance) = self.settings.walking_distance else {
+            return footpaths;
+        };
+        if self.settings.walking_speed.0 <= 0.0 {
+            return footpaths;
+        }
+        // stations nearby, unless linked explicitly
+        for (from, entity) in stops.iter().enumerate() {
+            let Ok((_, node)) = self.station_q.get(*entity) else {
+                continue;
+            };
+            // web mercator stretches distances by 1 / cos(latitude)
+            let (x, y) = node.coor.to_xy();
+            let radius = distance.0 as f64 / node.coor.lat.to_radians().cos().max(0.01);
+            for other in self.spatial_index.entities_in_xy_aabb(
+                x - radius,
+                y - radius,
+                x + radius,
+                y + radius,
+            ) {
+                let (Some(&to), Ok((_, other_node))) =
+                    (stop_index.get(&other), self.station_q.get(other))
+                else {
+                    continue;
+                };
+                if to == from || footpaths[from].iter().any(|(it, _)| *it == to) {
+                    continue;
+                }
+                let length = node.coor.distance(&other_node.coor);
+                if length.0 <= distance.0 {
+                    footpaths[from].push((to, length / self.settings.walking_speed));
+                }
+            }
+        }
+        footpaths
+    }
+}
+
+/// Add a footpath, keeping the shorter walking time if the stops are already linked.
+fn add_footpath(footpaths: &mut Vec<(usize, Duration)>, to: usize, walking_time: Duration) {
+    match footpaths.iter_mut().find(|(it, _)| *it == to) {
+        Some((_, time)) => *time = (*time).min(walking_time),
+        None => footpaths.push((to, walking_time)),
+    }
+}
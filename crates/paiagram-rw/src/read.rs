@@ -12,10 +12,11 @@ impl Plugin for ReadPlugin {
     }
 }
 
-pub type CallbackFn = fn(&mut Commands, Vec<u8>);
+/// Called with the name and the content of the picked file
+pub type CallbackFn = fn(&mut Commands, String, Vec<u8>);
 
 #[derive(Component)]
-struct SelectedFile(Task<Option<Vec<u8>>>, CallbackFn);
+struct SelectedFile(Task<Option<(String, Vec<u8>)>>, CallbackFn);
 
 #[derive(Event)]
 pub struct ReadFile {
@@ -37,7 +38,7 @@ fn dialog(trigger: On<ReadFile>, mut commands: Commands) {
         }
         match a.pick_file().await {
             None => None,
-            Some(it) => Some(it.read().await),
+            Some(it) => Some((it.file_name(), it.read().await)),
         }
     });
     commands.spawn(SelectedFile(task, callback));
@@ -46,8 +47,8 @@ fn dialog(trigger: On<ReadFile>, mut commands: Commands) {
 fn poll(mut commands: Commands, mut tasks: Query<(Entity, &mut SelectedFile)>) {
     for (entity, mut selected_file) in tasks.iter_mut() {
         if let Some(result) = future::block_on(future::poll_once(&mut selected_file.0)) {
-            if let Some(res) = result {
-                selected_file.1(&mut commands, res);
+            if let Some((name, res)) = result {
+                selected_file.1(&mut commands, name, res);
             }
             commands.entity(entity).despawn();
         }
@@ -1,9 +1,11 @@
 use bevy::{
     prelude::*,
+    reflect::TypeRegistry,
     scene::serde::{SceneDeserializer, SceneSerializer},
 };
 use cbor4ii::core::utils::IoReader;
 use serde::de::DeserializeSeed;
+use std::io::Write;
 
 pub struct SavePlugin;
 
@@ -19,11 +21,11 @@ impl Plugin for SavePlugin {
 #[derive(Resource, Deref, DerefMut)]
 pub struct LoadCandidate(pub SaveData);
 
-pub fn add_load_candidate_ron(commands: &mut Commands, data: Vec<u8>) {
+pub fn add_load_candidate_ron(commands: &mut Commands, _name: String, data: Vec<u8>) {
     commands.insert_resource(LoadCandidate(SaveData::Ron(data)));
 }
 
-pub fn add_load_candidate_compressed_cbor(commands: &mut Commands, data: Vec<u8>) {
+pub fn add_load_candidate_compressed_cbor(commands: &mut Commands, _name: String, data: Vec<u8>) {
     commands.insert_resource(LoadCandidate(SaveData::CompressedCbor(data)));
 }
 
@@ -37,30 +39,41 @@ pub struct LoadedScene(pub DynamicScene);
 
 pub fn save(scene: DynamicScene, registry: AppTypeRegistry, filename: String) {
     super::write::write_file(filename, move |writer| {
-        let reg = registry.read();
-        let serializer = SceneSerializer::new(&scene, &reg);
-        let mut encoder = lz4_flex::frame::FrameEncoder::new(writer);
-        cbor4ii::serde::to_writer(&mut encoder, &serializer)
-            .map_err(std::io::Error::other)
-            .and_then(|_| encoder.finish().map(|_| ()).map_err(std::io::Error::other))
+        write_compressed_cbor(&scene, &registry.read(), writer)
     });
 }
 
 pub fn save_ron(scene: DynamicScene, registry: AppTypeRegistry, filename: String) {
     super::write::write_file(filename, move |writer| {
-        let reg = registry.read();
-        scene
-            .serialize(&reg)
-            .map_err(std::io::Error::other)
-            .and_then(|s| {
-                writer
-                    .write(s.as_bytes())
-                    .map(|_| ())
-                    .map_err(std::io::Error::other)
-            })
+        write_ron(&scene, &registry.read(), writer)
     });
 }
 
+/// Write the scene as compressed CBOR, the format of `.paia` files.
+pub fn write_compressed_cbor(
+    scene: &DynamicScene,
+    registry: &TypeRegistry,
+    writer: &mut dyn Write,
+) -> std::io::Result<()> {
+    let serializer = SceneSerializer::new(scene, registry);
+    let mut encoder = lz4_flex::frame::FrameEncoder::new(writer);
+    cbor4ii::serde::to_writer(&mut encoder, &serializer)
+        .map_err(std::io::Error::other)
+        .and_then(|_| encoder.finish().map(|_| ()).map_err(std::io::Error::other))
+}
+
+/// Write the scene as RON.
+pub fn write_ron(
+    scene: &DynamicScene,
+    registry: &TypeRegistry,
+    writer: &mut dyn Write,
+) -> std::io::Result<()> {
+    scene
+        .serialize(registry)
+        .map_err(std::io::Error::other)
+        .and_then(|s| writer.write_all(s.as_bytes()))
+}
+
 fn deserialize_load_candidate(world: &mut World) {
     let Some(data) = world.remove_resource::<LoadCandidate>() else {
         error!("Tried to load data but the data does not exist");
@@ -46,6 +46,14 @@ tab-diagram-save-typst-module-desc = You must use the Typst module to render you
 tab-diagram-export-json-data = Export diagram as JSON
 tab-diagram-export-json-data-desc = Export the current diagram to JSON.
 tab-diagram-export-typst-diagram-output = Typst output length: {$bytes} bytes
+tab-diagram-export-svg = Export to SVG
+tab-diagram-export-svg-desc = Save the diagram as an SVG image that can be edited in Inkscape, with the stations, the time grid, and the trips in separate layers.
+tab-diagram-export-svg-view = Current view
+tab-diagram-export-svg-full-day = Full day
+tab-diagram-export-pdf = Export to PDF
+tab-diagram-export-pdf-desc = Save the diagram and the timetables of the route as a PDF file for printing. Only trips running on the selected service day are included.
+pdf-timetable-down = Down
+pdf-timetable-up = Up
 tab-diagram-export-typst-timetable = Export to timetable (Typst)
 tab-diagram-export-typst-timetable-desc = Export the current diagram's timetable to a Typst timetable. The exported timetable can be further customized in your preferred editor.
 tab-diagram-export-json-timetable = Export to timetable (JSON)
@@ -72,11 +80,57 @@ tab-graph-underlay-none = None
 tab-graph-underlay-openstreetmap = OpenStreetMap
 tab-graph-underlay-amap = Amap
 tab-graph-underlay-chiriin = Chiri-in Chizu
+tab-graph-station-capacity = Limited capacity
+tab-graph-station-turnaround = Minimum turnaround
+tab-graph-station-transfer-time = Minimum transfer time
+tab-graph-station-footpath = Walk to {$station}
+tab-graph-station-add-footpath = Link with a footpath
+tab-graph-interval-tracks = Tracks to {$station}
+tab-graph-interval-speed-limit = Speed limit
+tab-graph-interval-gradient = Gradient, positive values are uphill
+tab-graph-isochrone = Isochrone
+tab-graph-isochrone-desc = Colour stations by the shortest travel time from the origin, departing within the window.
+tab-graph-isochrone-origin = Origin
+tab-graph-isochrone-window = Departing between
+tab-graph-isochrone-max-transfers = Max transfers
+tab-graph-isochrone-compute = Compute
+tab-graph-isochrone-clear = Clear
+tab-graph-isochrone-reached = {$reached}/{$total} stations reached, up to {$longest}
+tab-graph-isochrone-export = Export isochrone to CSV
+tab-graph-od-matrix = Travel time matrix
+tab-graph-od-matrix-desc = Compute the shortest, median, and longest travel times between every pair of selected stations, leaving every step within the window, and the transfers of the fastest journey.
+tab-graph-od-matrix-step = Step
+tab-graph-od-matrix-stations = {$count} stations selected
+tab-graph-od-matrix-ready = Matrix of {$count} stations departing {$earliest}–{$latest}
 
 # Trip tab
 trip-table-station = Station
 trip-table-arrival = Arrival
 trip-table-departure = Departure
+trip-generator = Clock-Face Service
+trip-generator-new = Generate copies of this trip
+trip-generator-headway = Headway
+trip-generator-window-start = First departure
+trip-generator-window-end = Last departure
+trip-generator-name-pattern = Name pattern, {"{"}name{"}"} and {"{"}n{"}"} are replaced
+trip-generator-first-number = First number
+trip-generator-number-step = Number step
+trip-generator-regenerate = Regenerate when this trip changes
+trip-generator-generate = Generate
+trip-generator-remove = Remove copies
+trip-generator-generated-from = Generated from
+trip-generator-edited = Edited by hand, kept when the copies are generated again
+trip-generator-discard-edits = Discard edits
+trip-generator-edited-copies = {$count} copies edited by hand are kept when generating
+trip-connections = Guaranteed Connections
+trip-connection-to = To {$trip}
+trip-connection-from = From {$trip}
+trip-connection-remove = Remove
+trip-connection-new = New connection from this trip
+trip-connection-station = Station
+trip-connection-connecting = Connecting trip
+trip-connection-transfer-time = Transfer time
+trip-connection-add = Add connection
 
 # new lines desc
 new-displayed-line = New Displayed Line
@@ -44,6 +44,14 @@ tab-diagram = 运行图
 tab-diagram-export-typst-diagram = 导出为运行图 (Typst)
 tab-diagram-export-typst-diagram-desc = 将当前运行图导出为 Typst 运行图。导出的运行图可在文本编辑器中进一步编辑。
 tab-diagram-export-typst-diagram-output = Typst 输出长度：{$bytes} 字节
+tab-diagram-export-svg = 导出为 SVG
+tab-diagram-export-svg-desc = 将运行图保存为可在 Inkscape 中编辑的 SVG 图像。车站、时间网格和车次位于不同的图层。
+tab-diagram-export-svg-view = 当前视图
+tab-diagram-export-svg-full-day = 全天
+tab-diagram-export-pdf = 导出为 PDF
+tab-diagram-export-pdf-desc = 将本线路的运行图和时刻表保存为可供打印的 PDF 文件。仅包含在所选运行日运行的车次。
+pdf-timetable-down = 下行
+pdf-timetable-up = 上行
 # Graph tab
 tab-graph = 线路网
 tab-graph-new-displayed-line = 新建基线
@@ -67,11 +75,57 @@ tab-graph-underlay-none = 无
 tab-graph-underlay-openstreetmap = OpenStreetMap
 tab-graph-underlay-amap = 高德地图（AutoNavi）
 tab-graph-underlay-chiriin = 日本地理院地图
+tab-graph-station-capacity = 限制容量
+tab-graph-station-turnaround = 最短折返时间
+tab-graph-station-transfer-time = 最短换乘时间
+tab-graph-station-footpath = 步行至{$station}
+tab-graph-station-add-footpath = 以步行连接
+tab-graph-interval-tracks = 至{$station}的线路数
+tab-graph-interval-speed-limit = 限速
+tab-graph-interval-gradient = 坡度，正值为上坡
+tab-graph-isochrone = 等时圈
+tab-graph-isochrone-desc = 按在时间窗口内从起点出发的最短行程时间为车站着色。
+tab-graph-isochrone-origin = 起点
+tab-graph-isochrone-window = 出发时间
+tab-graph-isochrone-max-transfers = 最多换乘次数
+tab-graph-isochrone-compute = 计算
+tab-graph-isochrone-clear = 清除
+tab-graph-isochrone-reached = 可到达 {$reached}/{$total} 个车站，最长 {$longest}
+tab-graph-isochrone-export = 导出等时圈为 CSV
+tab-graph-od-matrix = 行程时间矩阵
+tab-graph-od-matrix-desc = 在时间窗口内每隔一个步长出发，计算所选车站两两之间的最短、中位与最长行程时间，以及最快行程的换乘次数。
+tab-graph-od-matrix-step = 步长
+tab-graph-od-matrix-stations = 已选择 {$count} 个车站
+tab-graph-od-matrix-ready = {$count} 个车站的矩阵，出发时间 {$earliest}–{$latest}
 
 # Trip tab
 trip-table-station = 车站
 trip-table-arrival = 到达
 trip-table-departure = 发车
+trip-generator = 等间隔运行
+trip-generator-new = 生成此车次的副本
+trip-generator-headway = 间隔
+trip-generator-window-start = 首班发车
+trip-generator-window-end = 末班发车
+trip-generator-name-pattern = 命名规则，{"{"}name{"}"} 与 {"{"}n{"}"} 会被替换
+trip-generator-first-number = 起始编号
+trip-generator-number-step = 编号步长
+trip-generator-regenerate = 此车次变化时重新生成
+trip-generator-generate = 生成
+trip-generator-remove = 删除副本
+trip-generator-generated-from = 生成自
+trip-generator-edited = 已手动编辑，重新生成时保留
+trip-generator-discard-edits = 放弃编辑
+trip-generator-edited-copies = 重新生成时保留 {$count} 个手动编辑的副本
+trip-connections = 保证接续
+trip-connection-to = 接续至 {$trip}
+trip-connection-from = 接续自 {$trip}
+trip-connection-remove = 删除
+trip-connection-new = 从此车次新建接续
+trip-connection-station = 车站
+trip-connection-connecting = 接续车次
+trip-connection-transfer-time = 换乘时间
+trip-connection-add = 添加接续
 
 # new lines desc
 new-displayed-line = 新基线
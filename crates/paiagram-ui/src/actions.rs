@@ -6,6 +6,9 @@ use eros::bail;
 use std::collections::VecDeque;
 
 mod change_entry_mode;
+mod move_vehicle_trip;
+
+pub(crate) use move_vehicle_trip::move_vehicle_trip;
 
 pub(crate) struct ActionsPlugin;
 impl Plugin for ActionsPlugin {
@@ -24,6 +27,7 @@ macro_rules! for_all_actions {
     ($action:expr, $t:ident, $body:expr) => {
         match $action {
             RevertableActions::ChangeEntryMode($t) => $body,
+            RevertableActions::MoveVehicleTrip($t) => $body,
         }
     };
 }
@@ -31,6 +35,7 @@ macro_rules! for_all_actions {
 #[derive(Reflect)]
 enum RevertableActions {
     ChangeEntryMode(change_entry_mode::ChangeEntryMode),
+    MoveVehicleTrip(move_vehicle_trip::MoveVehicleTrip),
 }
 
 impl RevertableActions {
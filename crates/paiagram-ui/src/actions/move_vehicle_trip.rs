@@ -0,0 +1,45 @@
+use super::RevertableActions::MoveVehicleTrip as MoveVehicleTripWrapper;
+use bevy::prelude::*;
+use paiagram_core::vehicle::move_trip;
+
+/// Move a trip to another vehicle and record the action in the [`super::ActionHistory`].
+/// See [`move_trip`] for the meaning of `index`.
+pub(crate) fn move_vehicle_trip(
+    world: &mut World,
+    trip: Entity,
+    from: Entity,
+    to: Entity,
+    index: usize,
+) -> eros::Result<()> {
+    let previous_index = move_trip(world, trip, from, to, index)?;
+    world
+        .resource_mut::<super::ActionHistory>()
+        .add(MoveVehicleTripWrapper(MoveVehicleTrip {
+            trip,
+            from,
+            to,
+            previous_index,
+            index,
+        }));
+    Ok(())
+}
+
+#[derive(Reflect)]
+pub(super) struct MoveVehicleTrip {
+    trip: Entity,
+    from: Entity,
+    to: Entity,
+    previous_index: usize,
+    index: usize,
+}
+
+impl super::RevertableAction for MoveVehicleTrip {
+    fn undo(&self, world: &mut World) -> eros::Result<()> {
+        move_trip(world, self.trip, self.to, self.from, self.previous_index)?;
+        Ok(())
+    }
+    fn redo(&self, world: &mut World) -> eros::Result<()> {
+        move_trip(world, self.trip, self.from, self.to, self.index)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,240 @@
+use bevy::prelude::*;
+use egui::{Color32, FontId, Painter, Vec2};
+use paiagram_core::export::ExportObject;
+use paiagram_core::export::diagram;
+use paiagram_core::route::Route;
+use paiagram_core::settings::ProjectSettings;
+use paiagram_core::trip::calendar::ServiceDate;
+use paiagram_core::units::time::TimetableTime;
+use std::borrow::Cow;
+use std::io::Write;
+use std::ops::Range;
+
+/// Room for the time labels at the top
+const HEADER_HEIGHT: f32 = 24.0;
+const MARGIN: f32 = 16.0;
+const FONT_SIZE: f32 = 12.0;
+const TRIP_FONT_SIZE: f32 = 9.0;
+
+/// The diagram of a route as SVG, with the stations, the time grid, and the trips in separate
+/// Inkscape layers.
+pub struct SvgDiagram<'a> {
+    pub route_entity: Entity,
+    pub world: &'a mut World,
+    pub service_day: Option<ServiceDate>,
+    pub scenario: Option<Entity>,
+    /// The times to draw
+    pub range: Range<TimetableTime>,
+    /// Points per second and points per unit of the route's length
+    pub scale: Vec2,
+    /// Measures the station names
+    pub painter: Painter,
+}
+
+impl<'a> ExportObject for SvgDiagram<'a> {
+    fn export_to_buffer(&mut self, buffer: &mut Vec<u8>) {
+        let heights: Vec<(Entity, f32)> = self
+            .world
+            .get::<Route>(self.route_entity)
+            .map(|route| route.iter().collect())
+            .unwrap_or_default();
+        let trips = diagram::exported_trips(
+            self.world,
+            self.route_entity,
+            self.service_day,
+            self.scenario,
+        );
+        let repeat_frequency = self.world.resource::<ProjectSettings>().repeat_frequency;
+        let name = |entity: Entity| {
+            self.world
+                .get::<Name>(entity)
+                .map_or_else(String::new, Name::to_string)
+        };
+        // room for the station names on the left, measured with the fonts of the user interface
+        let label_width = MARGIN
+            + heights
+                .iter()
+                .map(|(station, _)| {
+                    self.painter
+                        .layout_no_wrap(
+                            name(*station),
+                            FontId::proportional(FONT_SIZE),
+                            Color32::BLACK,
+                        )
+                        .size()
+                        .x
+                })
+                .fold(0.0, f32::max)
+            + 6.0;
+        let start = self.range.start.0;
+        let end = self.range.end.0.max(start + 1);
+        let x = |time: i32| label_width + (time - start) as f32 * self.scale.x;
+        let y = |height: f32| HEADER_HEIGHT + height * self.scale.y;
+        let max_height = heights.last().map_or(0.0, |(_, it)| *it);
+        let width = x(end) + MARGIN;
+        let height = y(max_height) + MARGIN;
+
+        writeln!(
+            buffer,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" width="{width}" height="{height}" viewBox="0 0 {width} {height}" font-family="sans-serif">"#
+        )
+        .unwrap();
+        writeln!(
+            buffer,
+            r#"<rect width="{width}" height="{height}" fill="white"/>"#
+        )
+        .unwrap();
+        // trips are cut at the ends of the time range
+        writeln!(
+            buffer,
+            r#"<defs><clipPath id="range"><rect x="{}" y="0" width="{}" height="{height}"/></clipPath></defs>"#,
+            x(start),
+            x(end) - x(start),
+        )
+        .unwrap();
+
+        // time grid, with ten minute lines if there is room for them
+        writeln!(
+            buffer,
+            r#"<g inkscape:groupmode="layer" inkscape:label="Time grid" fill="gray" font-size="{FONT_SIZE}" text-anchor="middle">"#
+        )
+        .unwrap();
+        let step = if self.scale.x * 600.0 >= 8.0 {
+            600
+        } else {
+            3600
+        };
+        let first = start + (step - start.rem_euclid(step)) % step;
+        for time in (first..=end).step_by(step as usize) {
+            let (color, stroke_width) = if time % 3600 == 0 {
+                ("gray", 0.6)
+            } else {
+                ("lightgray", 0.3)
+            };
+            writeln!(
+                buffer,
+                r#"<line x1="{x}" y1="{top}" x2="{x}" y2="{bottom}" stroke="{color}" stroke-width="{stroke_width}"/>"#,
+                x = x(time),
+                top = y(0.0),
+                bottom = y(max_height),
+            )
+            .unwrap();
+            if time % 3600 == 0 {
+                let (h, m, ..) = TimetableTime(time).to_hmsd();
+                writeln!(
+                    buffer,
+                    r#"<text x="{}" y="{}">{h}:{m:02}</text>"#,
+                    x(time),
+                    HEADER_HEIGHT - 6.0,
+                )
+                .unwrap();
+            }
+        }
+        writeln!(buffer, "</g>").unwrap();
+
+        // station lines and names
+        writeln!(
+            buffer,
+            r#"<g inkscape:groupmode="layer" inkscape:label="Stations" font-size="{FONT_SIZE}" text-anchor="end">"#
+        )
+        .unwrap();
+        for (station, station_height) in &heights {
+            let y = y(*station_height);
+            writeln!(
+                buffer,
+                r#"<line x1="{}" y1="{y}" x2="{}" y2="{y}" stroke="gray" stroke-width="0.6"/>"#,
+                x(start),
+                x(end),
+            )
+            .unwrap();
+            writeln!(
+                buffer,
+                r#"<text x="{}" y="{}">{}</text>"#,
+                label_width - 6.0,
+                y + FONT_SIZE * 0.35,
+                escape(&name(*station)),
+            )
+            .unwrap();
+        }
+        writeln!(buffer, "</g>").unwrap();
+
+        // trips, each in its own group
+        writeln!(
+            buffer,
+            r#"<g inkscape:groupmode="layer" inkscape:label="Trips" clip-path="url(#range)" font-size="{TRIP_FONT_SIZE}" fill="none" stroke-linejoin="round" stroke-linecap="round">"#
+        )
+        .unwrap();
+        for trip in &trips {
+            let [r, g, b, a] = trip.color;
+            let color = format!("#{r:02x}{g:02x}{b:02x}");
+            writeln!(
+                buffer,
+                r#"<g inkscape:label="{}" stroke="{color}" stroke-opacity="{}" stroke-width="{}">"#,
+                escape(&trip.name),
+                a as f32 / 255.0,
+                trip.width,
+            )
+            .unwrap();
+            for segment in &trip.segments {
+                let Some(first) = segment.first() else {
+                    continue;
+                };
+                // draw the copies of the segment repeated by the project's repeat frequency
+                let offsets = diagram::repeat_offsets(
+                    segment,
+                    repeat_frequency,
+                    TimetableTime(start)..TimetableTime(end),
+                );
+                for offset in offsets.into_iter().map(|it| it.0) {
+                    let points = segment
+                        .iter()
+                        .flat_map(|point| {
+                            let y = y(heights[point.station_index].1);
+                            [(point.arr.0, y), (point.dep.0, y)]
+                        })
+                        .map(|(time, y)| format!("{},{}", x(time + offset), y))
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    writeln!(buffer, r#"<polyline points="{points}"/>"#).unwrap();
+                    writeln!(
+                        buffer,
+                        r#"<text x="{}" y="{}" fill="{}" stroke="none">{}</text>"#,
+                        x(first.dep.0 + offset) + 2.0,
+                        y(heights[first.station_index].1) - 2.0,
+                        color,
+                        escape(&trip.name),
+                    )
+                    .unwrap();
+                }
+            }
+            writeln!(buffer, "</g>").unwrap();
+        }
+        writeln!(buffer, "</g>").unwrap();
+        writeln!(buffer, "</svg>").unwrap();
+    }
+    fn extension(&self) -> impl AsRef<str> {
+        ".svg"
+    }
+    fn filename(&self) -> impl AsRef<str> {
+        "exported_diagram"
+    }
+}
+
+/// Escape the text for XML.
+fn escape(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
@@ -1,5 +1,11 @@
 use bevy::prelude::*;
 use paiagram_core::export::ExportObject;
+use paiagram_core::export::diagram;
+use paiagram_core::route::Route;
+use paiagram_core::settings::ProjectSettings;
+use paiagram_core::trip::calendar::ServiceDate;
+use paiagram_core::units::time::TimetableTime;
+use serde::Serialize;
 use std::io::Write;
 
 pub struct TypstModule;
@@ -18,11 +24,51 @@ impl ExportObject for TypstModule {
 pub struct TypstDiagram<'a> {
     pub route_entity: Entity,
     pub world: &'a mut World,
+    pub service_day: Option<ServiceDate>,
+    pub scenario: Option<Entity>,
+}
+
+/// The data read by `typst_diagram.typ`
+#[derive(Serialize)]
+struct DiagramData {
+    /// Names of the stations and their positions on the route, in the unit of the route's lengths
+    stations: Vec<(String, f32)>,
+    trips: Vec<DiagramTrip>,
+}
+
+#[derive(Serialize)]
+struct DiagramTrip {
+    name: String,
+    /// Unmultiplied RGBA
+    color: [u8; 4],
+    width: f32,
+    /// Segments of the trip drawn as a single line. Each stop has four points: two at the
+    /// arrival and two at the departure.
+    points: Vec<Vec<[DiagramPoint; 4]>>,
+}
+
+/// A point in minutes after midnight and the position on the route
+#[derive(Serialize, Clone, Copy)]
+struct DiagramPoint {
+    x: f64,
+    y: f32,
 }
 
 impl<'a> ExportObject for TypstDiagram<'a> {
-    fn export_to_buffer(&mut self, _buffer: &mut Vec<u8>) {
-        todo!("Implement this thing before I die")
+    fn export_to_buffer(&mut self, buffer: &mut Vec<u8>) {
+        let heights: Vec<(Entity, f32)> = self
+            .world
+            .get::<Route>(self.route_entity)
+            .map(|route| route.iter().collect())
+            .unwrap_or_default();
+        let trips = diagram::exported_trips(
+            self.world,
+            self.route_entity,
+            self.service_day,
+            self.scenario,
+        );
+        let data = diagram_data(self.world, &heights, trips);
+        serde_json::to_writer(buffer, &data).unwrap();
     }
     fn extension(&self) -> impl AsRef<str> {
         ".json"
@@ -31,3 +77,54 @@ impl<'a> ExportObject for TypstDiagram<'a> {
         "exported_diagram_data"
     }
 }
+
+fn diagram_data(
+    world: &World,
+    heights: &[(Entity, f32)],
+    trips: Vec<diagram::DiagramTrip>,
+) -> DiagramData {
+    let stations = heights
+        .iter()
+        .map(|(station, height)| {
+            let name = world
+                .get::<Name>(*station)
+                .map_or_else(String::new, Name::to_string);
+            (name, *height)
+        })
+        .collect();
+    // the diagram's grid is one day long
+    let repeat = world.resource::<ProjectSettings>().repeat_frequency;
+    let day = TimetableTime(0)..TimetableTime(86400);
+    let point = |time: TimetableTime, y: f32| DiagramPoint {
+        x: time.0 as f64 / 60.0,
+        y,
+    };
+    let trips = trips
+        .into_iter()
+        .map(|trip| DiagramTrip {
+            points: trip
+                .segments
+                .iter()
+                .flat_map(|segment| {
+                    diagram::repeat_offsets(segment, repeat, day.clone())
+                        .into_iter()
+                        .map(move |offset| {
+                            segment
+                                .iter()
+                                .map(|it| {
+                                    let y = heights[it.station_index].1;
+                                    let arr = point(it.arr + offset, y);
+                                    let dep = point(it.dep + offset, y);
+                                    [arr, arr, dep, dep]
+                                })
+                                .collect()
+                        })
+                })
+                .collect(),
+            name: trip.name,
+            color: trip.color,
+            width: trip.width,
+        })
+        .collect();
+    DiagramData { stations, trips }
+}
@@ -3,6 +3,7 @@
 
 mod actions;
 mod command_palette;
+pub mod export_svg_diagram;
 pub mod export_typst_diagram;
 pub mod save;
 pub mod tabs;
@@ -60,6 +61,8 @@ impl Plugin for UiPlugin {
             .add_plugins((
                 // bevy_inspector_egui::DefaultInspectorConfigPlugin,
                 actions::ActionsPlugin,
+                paiagram_raptor::RaptorPlugin,
+                paiagram_core::plugin::WasmPluginPlugin,
             ))
             .add_message::<OpenOrFocus>()
             .add_message::<ModifySelectedItems>()
@@ -436,6 +439,10 @@ macro_rules! for_all_tabs {
             MainTab::PriorityGraph($t) => $body,
             MainTab::Text($t) => $body,
             MainTab::Station($t) => $body,
+            MainTab::Problems($t) => $body,
+            MainTab::Vehicles($t) => $body,
+            MainTab::JourneyPlanner($t) => $body,
+            MainTab::Plugins($t) => $body,
         }
     };
 }
@@ -453,6 +460,10 @@ macro_rules! for_all_tab_types {
             MainTab::PriorityGraph(_) => PriorityGraphTab::$body,
             MainTab::Text(_) => TextTab::$body,
             MainTab::Station(_) => StationTab::$body,
+            MainTab::Problems(_) => ProblemsTab::$body,
+            MainTab::Vehicles(_) => VehiclesTab::$body,
+            MainTab::JourneyPlanner(_) => JourneyPlannerTab::$body,
+            MainTab::Plugins(_) => PluginsTab::$body,
         }
     };
 }
@@ -469,6 +480,10 @@ pub(crate) enum MainTab {
     PriorityGraph(PriorityGraphTab),
     Text(TextTab),
     Station(StationTab),
+    Problems(ProblemsTab),
+    Vehicles(VehiclesTab),
+    JourneyPlanner(JourneyPlannerTab),
+    Plugins(PluginsTab),
 }
 
 impl MapEntities for MainTab {
@@ -572,6 +587,13 @@ impl<'w> MainTabViewer<'w> {
             ("Settings", MainTab::Settings(SettingsTab::default())),
             ("Classes", MainTab::Classes(ClassesTab::default())),
             ("Graph", MainTab::Graph(GraphTab::default())),
+            ("Problems", MainTab::Problems(ProblemsTab::default())),
+            ("Vehicles", MainTab::Vehicles(VehiclesTab::default())),
+            (
+                "Journey Planner",
+                MainTab::JourneyPlanner(JourneyPlannerTab::default()),
+            ),
+            ("Plugins", MainTab::Plugins(PluginsTab)),
         ] {
             if ui.button(s).clicked() {
                 self.world.write_message(OpenOrFocus(t));
@@ -921,21 +943,21 @@ pub fn show_ui(ui: &mut Ui, world: &mut World, cpu_time: Option<f32>) {
                             });
                         }
                     };
-                    read_file("OuDia", &["oud"], |c, s| {
+                    read_file("OuDia", &["oud"], |c, _, s| {
                         c.trigger(LoadOuDia::original(s));
                     });
-                    read_file("OuDiaSecond", &["oud2"], |c, s| {
+                    read_file("OuDiaSecond", &["oud2"], |c, _, s| {
                         c.trigger(LoadOuDia::second(String::from_utf8(s).unwrap()));
                     });
-                    read_file("qETRC/pyETRC", &["pyetgr", "json"], |c, s| {
+                    read_file("qETRC/pyETRC", &["pyetgr", "json"], |c, _, s| {
                         c.trigger(LoadQETRC {
                             content: String::from_utf8(s).unwrap(),
                         });
                     });
-                    read_file("GTFS", &["zip"], |c, s| {
+                    read_file("GTFS", &["zip"], |c, _, s| {
                         c.trigger(LoadGTFS { content: s });
                     });
-                    read_file("LLT", &["json"], |c, s| {
+                    read_file("LLT", &["json"], |c, _, s| {
                         c.trigger(LoadLlt {
                             content: String::from_utf8(s).unwrap(),
                         });
@@ -944,6 +966,44 @@ pub fn show_ui(ui: &mut Ui, world: &mut World, cpu_time: Option<f32>) {
                     if ui.button("Save...").clicked() {
                         save::save(world, "save.paia".to_string());
                     }
+                    {
+                        use paiagram_core::export::{ExportObject, gtfs::Gtfs};
+                        use paiagram_core::trip::scenario::Scenario;
+                        let scenarios: Vec<(Entity, String)> = world
+                            .query_filtered::<(Entity, &Name), With<Scenario>>()
+                            .iter(world)
+                            .map(|(entity, name)| (entity, name.to_string()))
+                            .collect();
+                        // scenarios are alternatives to each other, pick the one to export
+                        if scenarios.is_empty() {
+                            if ui.button("Export GTFS...").clicked() {
+                                Gtfs {
+                                    world,
+                                    scenario: None,
+                                }
+                                .export_to_file();
+                            }
+                        } else {
+                            ui.menu_button("Export GTFS", |ui| {
+                                for (scenario, name) in scenarios {
+                                    if ui.button(name).clicked() {
+                                        Gtfs {
+                                            world,
+                                            scenario: Some(scenario),
+                                        }
+                                        .export_to_file();
+                                    }
+                                }
+                                if ui.button("All trips").clicked() {
+                                    Gtfs {
+                                        world,
+                                        scenario: None,
+                                    }
+                                    .export_to_file();
+                                }
+                            });
+                        }
+                    }
                     if ui.button("Read...").clicked() {
                         world.commands().trigger(paiagram_rw::read::ReadFile {
                             title: "Load Save".to_string(),
@@ -1183,10 +1243,14 @@ fn sync_ui(InRef(ctx): InRef<Context>, preferences: Res<UserPreferences>) {
     }
 }
 
+/// The font for Chinese and Japanese text, once it is loaded. The web version downloads it at
+/// runtime.
+static SARASA: std::sync::OnceLock<Option<Vec<u8>>> = std::sync::OnceLock::new();
+
 pub fn apply_custom_fonts(ctx: &Context) {
     #[cfg(not(target_arch = "wasm32"))]
     {
-        let sarasa = load_sarasa_local();
+        let sarasa = SARASA.get_or_init(load_sarasa_local).clone();
         ctx.set_fonts(build_font_definitions(sarasa));
     }
 
@@ -1197,6 +1261,16 @@ pub fn apply_custom_fonts(ctx: &Context) {
     }
 }
 
+/// Fonts embedded in exported PDF files, so that names in any script are printed. Empty until
+/// the font for Chinese and Japanese text is loaded, or if it is missing.
+pub fn pdf_fonts() -> Vec<Vec<u8>> {
+    #[cfg(not(target_arch = "wasm32"))]
+    let sarasa = SARASA.get_or_init(load_sarasa_local);
+    #[cfg(target_arch = "wasm32")]
+    let sarasa = SARASA.get().and_then(Option::as_ref);
+    sarasa.into_iter().cloned().collect()
+}
+
 fn build_font_definitions(sarasa: Option<Vec<u8>>) -> egui::FontDefinitions {
     let mut fonts = egui::FontDefinitions::default();
 
@@ -1288,6 +1362,7 @@ fn download_sarasa_and_apply(ctx: Context) {
         };
 
         let bytes = js_sys::Uint8Array::new(&array_buffer).to_vec();
+        let _ = SARASA.set(Some(bytes.clone()));
         ctx.set_fonts(build_font_definitions(Some(bytes)));
     });
 }
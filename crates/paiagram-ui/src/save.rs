@@ -1,17 +1,19 @@
 use bevy::{ecs::entity::EntityHashMap, prelude::*};
 
 pub fn save(world: &mut World, filename: String) {
-    let entities: Vec<_> = world.query::<Entity>().iter(world).collect();
     let registry = world.resource::<AppTypeRegistry>().clone();
-    let scene = make_scene(world, entities.into_iter());
-    paiagram_rw::save::save(scene, registry, filename);
+    paiagram_rw::save::save(scene(world), registry, filename);
 }
 
 pub fn save_ron(world: &mut World, filename: String) {
-    let entities: Vec<_> = world.query::<Entity>().iter(world).collect();
     let registry = world.resource::<AppTypeRegistry>().clone();
-    let scene = make_scene(world, entities.into_iter());
-    paiagram_rw::save::save_ron(scene, registry, filename);
+    paiagram_rw::save::save_ron(scene(world), registry, filename);
+}
+
+/// Everything in the world that is saved in project files
+pub fn scene(world: &mut World) -> DynamicScene {
+    let entities: Vec<_> = world.query::<Entity>().iter(world).collect();
+    make_scene(world, entities.into_iter())
 }
 
 pub fn apply_loaded_scene(world: &mut World) {
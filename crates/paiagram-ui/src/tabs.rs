@@ -8,25 +8,33 @@ use std::borrow::Cow;
 pub(crate) mod classes;
 pub(crate) mod diagram;
 pub(crate) mod graph;
+pub(crate) mod journey_planner;
+pub(crate) mod plugins;
 pub(crate) mod priority_graph;
+pub(crate) mod problems;
 pub(crate) mod route_timetable;
 pub(crate) mod settings;
 pub(crate) mod start;
 pub(crate) mod station;
 pub(crate) mod text;
 pub(crate) mod trip;
+pub(crate) mod vehicles;
 
 pub mod all_tabs {
     pub(crate) use super::classes::ClassesTab;
     pub(crate) use super::diagram::DiagramTab;
     pub(crate) use super::graph::GraphTab;
+    pub(crate) use super::journey_planner::JourneyPlannerTab;
+    pub(crate) use super::plugins::PluginsTab;
     pub(crate) use super::priority_graph::PriorityGraphTab;
+    pub(crate) use super::problems::ProblemsTab;
     pub(crate) use super::route_timetable::RouteTimetableTab;
     pub(crate) use super::settings::SettingsTab;
     pub(crate) use super::start::StartTab;
     pub(crate) use super::station::StationTab;
     pub(crate) use super::text::TextTab;
     pub(crate) use super::trip::TripTab;
+    pub(crate) use super::vehicles::VehiclesTab;
 }
 
 fn handle_keyboard_navigation(ui: &Ui) -> Vec2 {
@@ -4,7 +4,11 @@ use super::Tab;
 use bevy::ecs::entity::MapEntities;
 use bevy::prelude::*;
 use egui::{Button, Panel, ScrollArea, Ui};
-use paiagram_core::trip::class::{Class, DisplayedStroke};
+use paiagram_core::trip::{
+    class::{Class, DisplayedStroke},
+    running_time::RollingStock,
+};
+use paiagram_core::units::speed::Velocity;
 use serde::{Deserialize, Serialize};
 
 #[derive(Default, PartialEq, Clone, Serialize, Deserialize, MapEntities)]
@@ -27,6 +31,7 @@ impl Tab for ClassesTab {
 fn list_classes(
     (InMut(ui), InMut(tab)): (InMut<Ui>, InMut<ClassesTab>),
     mut class_q: Query<(Entity, &Class, &Name, &mut DisplayedStroke)>,
+    mut stock_q: Query<Option<&mut RollingStock>, With<Class>>,
     entity_name_q: Query<(Entity, &Name)>,
     mut commands: Commands,
 ) {
@@ -41,6 +46,10 @@ fn list_classes(
                 let Ok((_, class, _, _)) = class_q.get(class_entity) else {
                     return;
                 };
+                if let Ok(stock) = stock_q.get_mut(class_entity) {
+                    edit_rolling_stock(ui, class_entity, stock, &mut commands);
+                    ui.separator();
+                }
                 let mut hovered = false;
                 for (trip_entity, name) in entity_name_q.iter_many(class.as_trips().iter().copied())
                 {
@@ -90,3 +99,76 @@ fn list_classes(
         ui.label(trip_entity.to_string());
     });
 }
+
+/// Edit the rolling stock performance of a class, or add one if there is none
+fn edit_rolling_stock(
+    ui: &mut Ui,
+    entity: Entity,
+    stock: Option<Mut<RollingStock>>,
+    commands: &mut Commands,
+) {
+    let mut enabled = stock.is_some();
+    if ui
+        .checkbox(&mut enabled, "Rolling stock performance")
+        .changed()
+    {
+        if enabled {
+            commands.entity(entity).insert(RollingStock::default());
+        } else {
+            commands.entity(entity).remove::<RollingStock>();
+        }
+    }
+    let Some(mut stock) = stock else {
+        return;
+    };
+    let mut edited = *stock;
+    let mut max_speed = edited.max_speed.to_kmh();
+    egui::Grid::new("rolling stock grid")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Max speed");
+            ui.add(
+                egui::DragValue::new(&mut max_speed)
+                    .range(1.0..=600.0)
+                    .suffix(" km/h"),
+            );
+            ui.end_row();
+            ui.label("Acceleration");
+            ui.add(
+                egui::DragValue::new(&mut edited.acceleration)
+                    .range(0.01..=5.0)
+                    .speed(0.01)
+                    .suffix(" m/s²"),
+            );
+            ui.end_row();
+            ui.label("Braking");
+            ui.add(
+                egui::DragValue::new(&mut edited.braking)
+                    .range(0.01..=5.0)
+                    .speed(0.01)
+                    .suffix(" m/s²"),
+            );
+            ui.end_row();
+            ui.label("Mass");
+            ui.add(
+                egui::DragValue::new(&mut edited.mass)
+                    .range(0.0..=20000.0)
+                    .suffix(" t"),
+            );
+            ui.end_row();
+            ui.label("Power");
+            ui.add(
+                egui::DragValue::new(&mut edited.power)
+                    .range(0.0..=50000.0)
+                    .speed(10.0)
+                    .suffix(" kW"),
+            );
+            ui.end_row();
+        });
+    // avoid rounding errors from converting back and forth
+    if max_speed != edited.max_speed.to_kmh() {
+        edited.max_speed = Velocity::from_kmh(max_speed);
+    }
+    // only touch the component when edited, since changes trigger recalculation
+    stock.set_if_neq(edited);
+}
@@ -1,8 +1,9 @@
 use super::{Navigatable, Tab};
+use crate::tabs::journey_planner::JourneyPlannerTab;
 use crate::tabs::station::StationTab;
 use crate::widgets::indicators::display_time_indicator_indicator_horizontal;
 use crate::widgets::timetable_popup::{POPUP_WIDTH, arrival_popup, departure_popup};
-use crate::widgets::{TimeDragValue, buttons};
+use crate::widgets::{ServiceDayFilter, TimeDragValue, buttons};
 use crate::{
     ExtendingTripSelection, GlobalTimer, IntervalSelection, ModifySelectedItems, OpenOrFocus,
     SelectedItem, SelectedItems, StationSelection, TripSelection,
@@ -10,7 +11,6 @@ use crate::{
 use bevy::ecs::entity::EntityHashMap;
 use bevy::ecs::entity::MapEntities;
 use bevy::prelude::*;
-use egui::emath::Numeric;
 use egui::{
     Align2, Button, Color32, FontId, Id, Margin, NumExt, Painter, Pos2, Rect, RectAlign, Sense,
     Shape, Stroke, StrokeKind, Ui, Vec2, vec2,
@@ -22,14 +22,15 @@ use paiagram_core::entry::{
     TravelMode,
 };
 use paiagram_core::export::ExportObject;
+use paiagram_core::problems::Problems;
 use paiagram_core::route::Route;
 use paiagram_core::settings::{LevelOfDetailMode, ProjectSettings, UserPreferences};
-use paiagram_core::station::Station;
+use paiagram_core::trip::calendar::ServiceDate;
 use paiagram_core::trip::class::DisplayedStroke;
 use paiagram_core::trip::routing::AddEntryToTrip;
-use paiagram_core::trip::{TripBundle, TripClass, TripQuery};
+use paiagram_core::trip::scenario::Scenario;
+use paiagram_core::trip::{TripBundle, TripClass, TripQuery, TripScenario};
 use paiagram_core::units::time::{Duration, Tick, TimetableTime};
-use paiagram_raptor::Journey;
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
 use std::sync::Arc;
@@ -70,7 +71,7 @@ pub(crate) enum CanvasState<'a> {
     ExtendingTrip(&'a mut ExtendingTripSelection),
 }
 
-type TripCache = EntityHashMap<SmallVec<[Vec1<TripPoint>; 1]>>;
+pub(crate) type TripCache = EntityHashMap<SmallVec<[Vec1<TripPoint>; 1]>>;
 
 /// The diagram tab.
 #[derive(Serialize, Deserialize, Clone, MapEntities)]
@@ -85,24 +86,26 @@ pub struct DiagramTab {
     route_entity: Entity,
     /// Whether to use the [`GlobalTimer`]
     use_global_timer: bool,
+    /// Only show trips running on this day
+    #[serde(default)]
+    service_day: Option<ServiceDate>,
+    /// Only show trips in this scenario
+    #[serde(default)]
+    #[entities]
+    scenario: Option<Entity>,
+    /// Whether to highlight problems on the canvas
+    #[serde(default = "default_show_problems")]
+    show_problems: bool,
+    /// Whether to draw guaranteed connections on the canvas
+    #[serde(default = "default_show_connections")]
+    show_connections: bool,
     #[serde(skip, default)]
     cached_trips: Option<TripCache>,
-    /// RAPTOR's results
-    #[serde(skip, default)]
-    raptor_params: RaptorParams,
     /// GPU state for drawing the lines
     #[serde(skip, default)]
     gpu_state: Arc<egui::mutex::Mutex<gpu_draw::GpuTripRendererState>>,
 }
 
-#[derive(Clone, Default)]
-pub struct RaptorParams {
-    departure_time: TimetableTime,
-    start_stop: Option<Entity>,
-    end_stop: Option<Entity>,
-    result: Vec<Journey<Entity, Entity>>,
-}
-
 #[derive(Serialize, Deserialize, Clone)]
 pub struct DiagramTabNavigation {
     pub x_offset: Tick,
@@ -130,6 +133,14 @@ fn default_visible_rect() -> Rect {
     Rect::NOTHING
 }
 
+fn default_show_problems() -> bool {
+    true
+}
+
+fn default_show_connections() -> bool {
+    true
+}
+
 impl PartialEq for DiagramTab {
     fn eq(&self, other: &Self) -> bool {
         self.route_entity == other.route_entity
@@ -143,8 +154,11 @@ impl DiagramTab {
             last_secondary_click_position: None,
             route_entity,
             use_global_timer: false,
+            service_day: None,
+            scenario: None,
+            show_problems: true,
+            show_connections: true,
             cached_trips: None,
-            raptor_params: RaptorParams::default(),
             gpu_state: Arc::new(egui::mutex::Mutex::new(
                 gpu_draw::GpuTripRendererState::default(),
             )),
@@ -226,10 +240,11 @@ impl Navigatable for DiagramTabNavigation {
 
 #[derive(Clone, Copy, Debug)]
 pub(crate) struct TripPoint {
-    arr: TimetableTime,
-    dep: TimetableTime,
-    entry: Entity,
-    station_index: usize,
+    pub arr: TimetableTime,
+    pub dep: TimetableTime,
+    pub entry: Entity,
+    /// Index into the stations of the route
+    pub station_index: usize,
 }
 
 impl Tab for DiagramTab {
@@ -241,19 +256,23 @@ impl Tab for DiagramTab {
         [false; 2]
     }
     fn export_display(&mut self, world: &mut World, ui: &mut Ui) {
+        use crate::export_svg_diagram::SvgDiagram;
         use crate::export_typst_diagram::{TypstDiagram, TypstModule};
         use paiagram_core::export::oudia::OuDia;
+        use paiagram_pdf::{PdfExport, PdfOptions};
         ui.strong(tr!("tab-diagram-save-typst-module"));
         ui.label(tr!("tab-diagram-save-typst-module-desc"));
         if ui.button(tr!("export")).clicked() {
             TypstModule.export_to_file();
         }
         ui.strong(tr!("tab-diagram-export-json-data"));
-        ui.label(tr!("tab-diagram-export-json-data"));
+        ui.label(tr!("tab-diagram-export-json-data-desc"));
         if ui.button(tr!("export")).clicked() {
             TypstDiagram {
                 route_entity: self.route_entity,
                 world: world,
+                service_day: self.service_day,
+                scenario: self.scenario,
             }
             .export_to_file();
         }
@@ -264,6 +283,54 @@ impl Tab for DiagramTab {
             }
             .export_to_file();
         }
+        ui.strong(tr!("tab-diagram-export-svg"));
+        ui.label(tr!("tab-diagram-export-svg-desc"));
+        ui.horizontal(|ui| {
+            let visible_x = self.navi.visible_x();
+            let view = visible_x.start.to_timetable_time()..visible_x.end.to_timetable_time();
+            let ticks_per_second = Tick::from_timetable_time(TimetableTime(1)).0 as f32;
+            let mut range = None;
+            if ui
+                .add_enabled(
+                    view.start < view.end,
+                    egui::Button::new(tr!("tab-diagram-export-svg-view")),
+                )
+                .clicked()
+            {
+                range = Some((view, self.navi.zoom.x * ticks_per_second));
+            }
+            if ui.button(tr!("tab-diagram-export-svg-full-day")).clicked() {
+                // 100 points per hour
+                range = Some((TimetableTime(0)..TimetableTime(86400), 100.0 / 3600.0));
+            }
+            if let Some((range, x_scale)) = range {
+                SvgDiagram {
+                    route_entity: self.route_entity,
+                    world: world,
+                    service_day: self.service_day,
+                    scenario: self.scenario,
+                    range,
+                    scale: vec2(x_scale, self.navi.zoom.y),
+                    painter: ui.painter().clone(),
+                }
+                .export_to_file();
+            }
+        });
+        ui.strong(tr!("tab-diagram-export-pdf"));
+        ui.label(tr!("tab-diagram-export-pdf-desc"));
+        if ui.button(tr!("export")).clicked() {
+            PdfExport {
+                route_entity: self.route_entity,
+                world: world,
+                options: PdfOptions {
+                    service_day: self.service_day,
+                    scenario: self.scenario,
+                    fonts: crate::pdf_fonts(),
+                    ..default()
+                },
+            }
+            .export_to_file();
+        }
     }
     fn edit_display(&mut self, world: &mut World, ui: &mut Ui) {
         ui.checkbox(&mut self.use_global_timer, "Use global timer");
@@ -276,14 +343,16 @@ impl Tab for DiagramTab {
                     let default_class = world
                         .resource::<paiagram_core::class::ClassResource>()
                         .default_class;
-                    let new_trip = world
-                        .commands()
-                        .spawn(TripBundle::new(
-                            "New Trip",
-                            TripClass(default_class),
-                            Vec::new(),
-                        ))
-                        .id();
+                    let mut commands = world.commands();
+                    let mut new_trip = commands.spawn(TripBundle::new(
+                        "New Trip",
+                        TripClass(default_class),
+                        Vec::new(),
+                    ));
+                    if let Some(scenario) = self.scenario {
+                        new_trip.insert(TripScenario(scenario));
+                    }
+                    let new_trip = new_trip.id();
                     *world.resource_mut::<SelectedItems>() =
                         SelectedItems::ExtendingTrip(ExtendingTripSelection {
                             trip: new_trip,
@@ -315,86 +384,23 @@ impl Tab for DiagramTab {
         ui.separator();
     }
     fn display_display(&mut self, world: &mut World, ui: &mut Ui) {
-        ui.label("Find a route between...");
-        ui.add(
-            egui::Slider::new(
-                &mut self.raptor_params.departure_time,
-                TimetableTime(0)..=TimetableTime(86400),
-            )
-            .custom_formatter(|val, _| TimetableTime::from_f64(val).to_string())
-            .custom_parser(|s| TimetableTime::from_str(s).map(TimetableTime::to_f64)),
-        );
-        // station selection
-        // TODO: support both select from canvas and select from station list
-        // TODO: make this a modal instead of this list thingy
-        fn select_name(
-            (InMut(ui), InMut(sel)): (InMut<Ui>, InMut<Option<Entity>>),
-            stations: Query<(Entity, &Name), With<Station>>,
-        ) {
-            let res = ui.button(
-                sel.and_then(|sel| stations.get(sel).ok())
-                    .map_or("None", |(_, n)| n.as_str()),
-            );
-            egui::Popup::menu(&res).show(|ui| {
-                egui::scroll_area::ScrollArea::vertical().show(ui, |ui| {
-                    for (entity, station_name) in stations.iter() {
-                        if ui.button(station_name.as_str()).clicked() {
-                            *sel = Some(entity)
-                        }
-                    }
-                })
-            });
+        if ui.add(ServiceDayFilter(&mut self.service_day)).changed() {
+            self.cached_trips = None;
         }
-        world
-            .run_system_cached_with(select_name, (ui, &mut self.raptor_params.start_stop))
-            .unwrap();
-        world
-            .run_system_cached_with(select_name, (ui, &mut self.raptor_params.end_stop))
+        let scenario_changed = world
+            .run_system_cached_with(select_scenario, (ui, &mut self.scenario))
             .unwrap();
-        if ui
-            .add_enabled(
-                self.raptor_params.start_stop.is_some() && self.raptor_params.end_stop.is_some(),
-                egui::Button::new("Find"),
-            )
-            .clicked()
-            && let Some(start) = self.raptor_params.start_stop
-            && let Some(end) = self.raptor_params.end_stop
-        {
-            self.raptor_params.result = world
-                .run_system_cached_with(
-                    paiagram_raptor::make_query_data,
-                    (self.raptor_params.departure_time.0 as usize, start, end),
-                )
-                .unwrap();
+        if scenario_changed {
+            self.cached_trips = None;
         }
-        // TODO: highlight the trips on the canvas instead of displaying them here
-        fn display_journey(
-            (InMut(ui), InRef(journeys)): (InMut<Ui>, InRef<[Journey<Entity, Entity>]>),
-            name_q: Query<&Name>,
-        ) {
-            for (idx, Journey { plan, arrival }) in journeys.iter().enumerate() {
-                egui::Grid::new(("journey grid", idx))
-                    .num_columns(2)
-                    .striped(true)
-                    .show(ui, |ui| {
-                        ui.label("Arrival Time:");
-                        ui.label(TimetableTime(*arrival as i32).to_string());
-                        ui.end_row();
-                        for (route, stop) in plan.iter().copied() {
-                            let stop_n = name_q.get(stop).map_or("No Name", Name::as_str);
-                            let route_n = name_q.get(route).map_or("No Name", Name::as_str);
-                            ui.label(stop_n);
-                            ui.label(route_n);
-                            ui.end_row();
-                        }
-                    });
-                ui.separator();
-            }
+        ui.checkbox(&mut self.show_problems, "Highlight problems");
+        ui.checkbox(&mut self.show_connections, "Show connections");
+        ui.separator();
+        if ui.button("Plan a journey...").clicked() {
+            world.write_message(OpenOrFocus(crate::MainTab::JourneyPlanner(
+                JourneyPlannerTab::new(self.service_day, self.scenario),
+            )));
         }
-
-        world
-            .run_system_cached_with(display_journey, (ui, self.raptor_params.result.as_slice()))
-            .unwrap();
     }
     fn main_display(&mut self, world: &mut World, ui: &mut egui::Ui) {
         world.resource_scope(|world, mut selected: Mut<SelectedItems>| {
@@ -409,6 +415,26 @@ impl Tab for DiagramTab {
     }
 }
 
+/// Scenario selector. Returns whether the selection changed.
+pub(crate) fn select_scenario(
+    (InMut(ui), InMut(sel)): (InMut<Ui>, InMut<Option<Entity>>),
+    scenarios: Query<(Entity, &Name), With<Scenario>>,
+) -> bool {
+    let previous = *sel;
+    let selected_text = sel
+        .and_then(|sel| scenarios.get(sel).ok())
+        .map_or("All", |(_, name)| name.as_str());
+    egui::ComboBox::from_label("Scenario")
+        .selected_text(selected_text)
+        .show_ui(ui, |ui| {
+            ui.selectable_value(sel, None, "All");
+            for (entity, name) in scenarios.iter() {
+                ui.selectable_value(sel, Some(entity), name.as_str());
+            }
+        });
+    previous != *sel
+}
+
 fn main_display(
     tab: &mut DiagramTab,
     world: &mut World,
@@ -462,7 +488,13 @@ fn main_display(
     let cached_trips_are_changed = world
         .run_system_cached_with(
             prep_segments::calc,
-            (tab.route_entity, &station_heights, &mut tab.cached_trips),
+            (
+                tab.route_entity,
+                &station_heights,
+                &mut tab.cached_trips,
+                tab.service_day,
+                tab.scenario,
+            ),
         )
         .unwrap();
 
@@ -523,10 +555,14 @@ fn main_display(
     );
 
     if cached_trips_are_changed {
+        let run_offsets = world
+            .run_system_cached_with(prep_segments::run_offsets, cached_trips)
+            .unwrap();
         gpu_draw::rewrite_trip_cache(
             cached_trips,
             station_heights.iter().map(|(_, y)| *y),
             &world.query::<&TripClass>().query(world),
+            &run_offsets,
             &mut state,
         );
     }
@@ -553,8 +589,17 @@ fn main_display(
         .normalized_with(repeat_frequency.to_ticks())
         .to_timetable_time();
 
-    let callback = gpu_draw::paint_callback(response.rect, tab.gpu_state.clone());
-    painter.add(callback);
+    // the target format is only known if there is a suitable adapter to draw with
+    if state.target_format.is_some() {
+        let callback = gpu_draw::paint_callback(response.rect, tab.gpu_state.clone());
+        painter.add(callback);
+    } else {
+        painter.add(gpu_draw::trip_mesh(
+            &state,
+            response.rect,
+            ui.ctx().pixels_per_point(),
+        ));
+    }
 
     // check for selection
     let selection_strength = ui.ctx().animate_bool(
@@ -586,6 +631,31 @@ fn main_display(
         .flatten();
     let repeat_interval_ticks = Tick::from_timetable_time(TimetableTime(repeat_frequency.0));
 
+    if tab.show_problems {
+        draw_lines::draw_problems(
+            &mut painter,
+            &tab.navi,
+            cached_trips,
+            &station_heights,
+            world.resource::<Problems>(),
+            repeat_interval_ticks,
+        );
+    }
+
+    if tab.show_connections {
+        let connections = world
+            .run_system_cached_with(prep_segments::connections, cached_trips)
+            .unwrap();
+        draw_lines::draw_connections(
+            &mut painter,
+            &tab.navi,
+            cached_trips,
+            &station_heights,
+            &connections,
+            repeat_interval_ticks,
+        );
+    }
+
     let get_closest_station = |selected_y: f32| -> (Entity, f32, usize) {
         let idx = station_heights.partition_point(|(_, y)| *y < selected_y);
         let (e, h) = if idx == 0 {
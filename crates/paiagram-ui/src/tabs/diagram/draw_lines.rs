@@ -1,7 +1,9 @@
 use bevy::prelude::*;
-use egui::{Color32, FontId, Painter, Pos2, Stroke, Visuals};
+use egui::{Color32, FontId, Painter, Pos2, Shape, Stroke, Visuals, epaint::QuadraticBezierShape};
+use paiagram_core::problems::Problems;
 use paiagram_core::units::time::{Tick, TimetableTime};
 
+use super::TripCache;
 use crate::tabs::{Navigatable, diagram::DiagramTabNavigation};
 
 pub fn draw_station_lines(
@@ -122,3 +124,120 @@ pub fn draw_time_lines(painter: &mut Painter, navi: &DiagramTabNavigation) {
         }
     }
 }
+
+/// Highlight the parts of trips involved in problems
+pub fn draw_problems(
+    painter: &mut Painter,
+    navi: &DiagramTabNavigation,
+    cached_trips: &TripCache,
+    station_heights: &[(Entity, f32)],
+    problems: &Problems,
+    repeat_interval_ticks: Tick,
+) {
+    let stroke = Stroke::new(6.0, Color32::RED.gamma_multiply(0.4));
+    let visible_ticks = navi.visible_x();
+    for segment in problems.iter().flat_map(|it| it.segments.iter()) {
+        let Some(trip_segments) = cached_trips.get(&segment.trip) else {
+            continue;
+        };
+        for points in trip_segments {
+            let Some(from_idx) = points.iter().position(|it| it.entry == segment.from) else {
+                continue;
+            };
+            let Some(to) = points[from_idx..].iter().find(|it| it.entry == segment.to) else {
+                continue;
+            };
+            let from = &points[from_idx];
+            // problems at a station cover the stop, while problems on an interval cover the run
+            let (start, end) = if segment.from == segment.to {
+                (from.arr.to_ticks().0, from.dep.to_ticks().0)
+            } else {
+                (from.dep.to_ticks().0, to.arr.to_ticks().0)
+            };
+            let from_y = navi.logical_y_to_screen_y(station_heights[from.station_index].1 as f64);
+            let to_y = navi.logical_y_to_screen_y(station_heights[to.station_index].1 as f64);
+            let (repeat_start, repeat_end) = if repeat_interval_ticks.0 > 0 {
+                (
+                    (visible_ticks.start.0 - end).div_euclid(repeat_interval_ticks.0),
+                    (visible_ticks.end.0 - start).div_euclid(repeat_interval_ticks.0),
+                )
+            } else {
+                (0, 0)
+            };
+            for repeat in repeat_start..=repeat_end {
+                let offset = repeat * repeat_interval_ticks.0;
+                let p0 = Pos2::new(navi.logical_x_to_screen_x(Tick(start + offset)), from_y);
+                let p1 = Pos2::new(navi.logical_x_to_screen_x(Tick(end + offset)), to_y);
+                painter.line_segment([p0, p1], stroke);
+            }
+        }
+    }
+}
+
+/// A guaranteed connection between two cached trips
+pub struct ConnectionArc {
+    pub feeder: Entity,
+    pub feeder_entry: Entity,
+    pub connecting: Entity,
+    pub connecting_entry: Entity,
+    /// Whether passengers have less than the transfer time
+    pub missed: bool,
+}
+
+/// Draw an arc from the feeder's arrival to the connecting departure of each connection.
+pub fn draw_connections(
+    painter: &mut Painter,
+    navi: &DiagramTabNavigation,
+    cached_trips: &TripCache,
+    station_heights: &[(Entity, f32)],
+    connections: &[ConnectionArc],
+    repeat_interval_ticks: Tick,
+) {
+    const ARC_HEIGHT: f32 = 16.0;
+    let visible_ticks = navi.visible_x();
+    let find_point = |trip: Entity, entry: Entity| {
+        cached_trips
+            .get(&trip)?
+            .iter()
+            .flat_map(|points| points.iter())
+            .find(|it| it.entry == entry)
+    };
+    for connection in connections {
+        let (Some(feeder), Some(connecting)) = (
+            find_point(connection.feeder, connection.feeder_entry),
+            find_point(connection.connecting, connection.connecting_entry),
+        ) else {
+            continue;
+        };
+        let stroke = if connection.missed {
+            Stroke::new(2.0, Color32::RED)
+        } else {
+            Stroke::new(2.0, Color32::from_rgb(0, 160, 80))
+        };
+        let start = feeder.arr.to_ticks().0;
+        let end = connecting.dep.to_ticks().0;
+        let from_y = navi.logical_y_to_screen_y(station_heights[feeder.station_index].1 as f64);
+        let to_y = navi.logical_y_to_screen_y(station_heights[connecting.station_index].1 as f64);
+        let (repeat_start, repeat_end) = if repeat_interval_ticks.0 > 0 {
+            (
+                (visible_ticks.start.0 - start.max(end)).div_euclid(repeat_interval_ticks.0),
+                (visible_ticks.end.0 - start.min(end)).div_euclid(repeat_interval_ticks.0),
+            )
+        } else {
+            (0, 0)
+        };
+        for repeat in repeat_start..=repeat_end {
+            let offset = repeat * repeat_interval_ticks.0;
+            let p0 = Pos2::new(navi.logical_x_to_screen_x(Tick(start + offset)), from_y);
+            let p2 = Pos2::new(navi.logical_x_to_screen_x(Tick(end + offset)), to_y);
+            let p1 = Pos2::new((p0.x + p2.x) / 2.0, from_y.min(to_y) - ARC_HEIGHT);
+            painter.add(QuadraticBezierShape::from_points_stroke(
+                [p0, p1, p2],
+                false,
+                Color32::TRANSPARENT,
+                stroke,
+            ));
+            painter.add(Shape::circle_filled(p2, 3.0, stroke.color));
+        }
+    }
+}
@@ -11,7 +11,8 @@ use egui::{Rect, mutex::Mutex};
 use egui_wgpu::CallbackTrait;
 use paiagram_core::settings::{AntialiasingMode, LevelOfDetailMode};
 use paiagram_core::trip::TripClass;
-use paiagram_core::units::time::TimetableTime;
+use paiagram_core::units::time::{Duration, TimetableTime};
+use smallvec::SmallVec;
 use std::collections::HashMap;
 use std::sync::Arc;
 use wgpu::BufferDescriptor;
@@ -73,6 +74,18 @@ impl Entry {
         TimetableTime(Self::signed_24(self.field1 & 0x00ff_ffff))
     }
 
+    fn connects_to_next_entry(&self) -> bool {
+        (self.field0 >> 24) & 1 != 0
+    }
+
+    fn style_index(&self) -> usize {
+        (self.field1 >> 24) as usize
+    }
+
+    fn station_index(&self) -> usize {
+        (self.field2 >> 16) as usize
+    }
+
     const fn new(
         arr_secs: i32,
         dep_secs: i32,
@@ -239,10 +252,12 @@ pub fn upload_trip_strokes(
     }
 }
 
+/// `run_offsets` holds the runs of frequency-based trips. Other trips run once.
 pub fn rewrite_trip_cache(
     cache: &super::TripCache,
     stations: impl Iterator<Item = f32>,
     class_lookup: &bevy::prelude::Query<&TripClass>,
+    run_offsets: &EntityHashMap<SmallVec<[Duration; 1]>>,
     state: &mut GpuTripRendererState,
 ) {
     const MAX_STATION_COUNT: usize = (u16::MAX as usize) + 1;
@@ -270,39 +285,44 @@ pub fn rewrite_trip_cache(
             .copied()
             .and_then(|index| u8::try_from(index).ok())
             .unwrap_or(DEFAULT_STYLE_INDEX);
+        let offsets = run_offsets
+            .get(trip_entity)
+            .map_or(&[Duration::ZERO][..], |it| it.as_slice());
         for (last, rest) in segments.iter().filter_map(|it| it.split_last()) {
-            let capacity = ((rest.len() + 1) / 4 + 2) * 4;
-            let mut segment_entries: Vec<Entry> = Vec::with_capacity(capacity);
-            segment_entries.extend(rest.iter().map(|it| {
-                Entry::new(
-                    it.arr.seconds(),
-                    it.dep.seconds(),
-                    it.station_index as u16,
+            for offset in offsets.iter().copied() {
+                let capacity = ((rest.len() + 1) / 4 + 2) * 4;
+                let mut segment_entries: Vec<Entry> = Vec::with_capacity(capacity);
+                segment_entries.extend(rest.iter().map(|it| {
+                    Entry::new(
+                        (it.arr + offset).seconds(),
+                        (it.dep + offset).seconds(),
+                        it.station_index as u16,
+                        0,
+                        style_index,
+                        true,
+                    )
+                }));
+                // manually push the last entry
+                let last_entry = Entry::new(
+                    (last.arr + offset).seconds(),
+                    (last.dep + offset).seconds(),
+                    last.station_index as u16,
                     0,
                     style_index,
-                    true,
-                )
-            }));
-            // manually push the last entry
-            let last_entry = Entry::new(
-                last.arr.seconds(),
-                last.dep.seconds(),
-                last.station_index as u16,
-                0,
-                style_index,
-                false,
-            );
-            // |    |    |    |
-            // 0000 0000 T... 0000 0 (1)
-            // 0000 0000 0TTT T... 1 (4)
-            // 0000 0000 00TT T... 2 (3)
-            // 0000 0000 000T T... 3 (2)
-            let padding_count = 4 - (segment_entries.len() + 3) % 4;
-            segment_entries.extend(std::iter::repeat(last_entry).take(padding_count));
-            segment_entries.extend(std::iter::repeat(Entry::INVALID).take(3));
-            state
-                .entries
-                .push((segment_entries.into_boxed_slice(), 0, TimetableTime::ZERO));
+                    false,
+                );
+                // |    |    |    |
+                // 0000 0000 T... 0000 0 (1)
+                // 0000 0000 0TTT T... 1 (4)
+                // 0000 0000 00TT T... 2 (3)
+                // 0000 0000 000T T... 3 (2)
+                let padding_count = 4 - (segment_entries.len() + 3) % 4;
+                segment_entries.extend(std::iter::repeat(last_entry).take(padding_count));
+                segment_entries.extend(std::iter::repeat(Entry::INVALID).take(3));
+                state
+                    .entries
+                    .push((segment_entries.into_boxed_slice(), 0, TimetableTime::ZERO));
+            }
         }
     }
 
@@ -321,6 +341,223 @@ pub fn rewrite_trip_cache(
         max = std::cmp::max(dep_max, max);
         *curr_max = max;
     }
+
+    let mut data_tick_min = i32::MAX;
+    let mut data_tick_max = i32::MIN;
+    for entry in state.entries.iter().flat_map(|(it, _, _)| it) {
+        let arr_ticks = entry.arr_secs().0.saturating_mul(100);
+        let dep_ticks = entry.dep_secs().0.saturating_mul(100);
+        data_tick_min = data_tick_min.min(arr_ticks.min(dep_ticks));
+        data_tick_max = data_tick_max.max(arr_ticks.max(dep_ticks));
+    }
+    if data_tick_min > data_tick_max {
+        data_tick_min = 0;
+        data_tick_max = 0;
+    }
+    state.data_tick_min = data_tick_min;
+    state.data_tick_max = data_tick_max;
+}
+
+/// The part of the flattened entry array to draw, and how many times it is repeated
+struct DrawRange {
+    total_entry_count: usize,
+    visible_entry_min_index: usize,
+    visible_entry_count: usize,
+    repeat_interval: i32,
+    repeat_from: i32,
+    repeat_count: usize,
+    lod_stride: usize,
+}
+
+impl GpuTripRendererState {
+    fn draw_range(&self, visible_ticks_min: i32, visible_ticks_max: i32) -> DrawRange {
+        let total_entry_count = self.entries.last().map(|(_, count, _)| *count).unwrap_or(0);
+        let visible_window_wraps = self.visible_secs_min >= self.visible_secs_max;
+
+        // Find the visible source-entry window [min, max) over the flattened entry array.
+        // `curr_max` is a prefix max departure time; `arr_secs` is segment start time.
+        let mut visible_entry_min_index = if self.entries.is_empty() {
+            0
+        } else {
+            let idx = self
+                .entries
+                .partition_point(|(_, _, max_secs)| *max_secs < self.visible_secs_min);
+            if idx == 0 { 0 } else { self.entries[idx - 1].1 }
+        };
+        let visible_entry_max_index = if self.entries.is_empty() {
+            0
+        } else {
+            let idx = self.entries.partition_point(|(segment, _, _)| {
+                segment
+                    .first()
+                    .map(|entry| entry.arr_secs() <= self.visible_secs_max)
+                    .unwrap_or(false)
+            });
+            if idx == 0 { 0 } else { self.entries[idx - 1].1 }
+        }
+        .min(total_entry_count);
+
+        let mut visible_entry_count = if total_entry_count == 0 {
+            0
+        } else if visible_window_wraps {
+            total_entry_count
+                .saturating_sub(visible_entry_min_index)
+                .saturating_add(visible_entry_max_index)
+                .min(total_entry_count)
+        } else {
+            visible_entry_max_index.saturating_sub(visible_entry_min_index)
+        };
+
+        let repeat_interval = self.uniforms.repeat_interval_ticks.max(0);
+
+        // If one viewport already spans at least one full repeat period, culling would only
+        // remove segments that are still visible after wrapping. Render everything instead.
+        if repeat_interval > 0
+            && visible_ticks_max.saturating_sub(visible_ticks_min) >= repeat_interval
+        {
+            visible_entry_min_index = 0;
+            visible_entry_count = total_entry_count;
+        }
+
+        let repeat_from = if repeat_interval > 0 {
+            (visible_ticks_min - self.data_tick_max).div_euclid(repeat_interval)
+        } else {
+            0
+        };
+        let repeat_count = if repeat_interval > 0 {
+            let repeat_to = (visible_ticks_max - self.data_tick_min).div_euclid(repeat_interval);
+            (repeat_to - repeat_from + 1).max(1) as usize
+        } else {
+            1usize
+        };
+
+        DrawRange {
+            total_entry_count,
+            visible_entry_min_index,
+            visible_entry_count,
+            repeat_interval,
+            repeat_from,
+            repeat_count,
+            lod_stride: self.level_of_detail_mode.as_u8() as usize,
+        }
+    }
+
+    /// The entry at `index` of the flattened entry array
+    fn entry_at(&self, index: usize) -> Entry {
+        let segment = self.entries.partition_point(|(_, sum, _)| *sum <= index);
+        let (entries, sum, _) = &self.entries[segment];
+        entries[index + entries.len() - sum]
+    }
+}
+
+/// `(along, side, outer)` of the vertices of a segment, same as in `gpu_trip.wgsl`
+const SEGMENT_MESH_VERTICES: [(f32, f32, f32); 8] = [
+    (0.0, 1.0, 0.0),
+    (0.0, -1.0, 0.0),
+    (1.0, 1.0, 0.0),
+    (1.0, -1.0, 0.0),
+    (0.0, 1.0, 1.0),
+    (1.0, 1.0, 1.0),
+    (0.0, -1.0, 1.0),
+    (1.0, -1.0, 1.0),
+];
+
+const SEGMENT_MESH_INDICES: [u32; 18] = [
+    0, 1, 2, 1, 3, 2, //
+    4, 0, 5, 0, 2, 5, //
+    1, 6, 3, 6, 7, 3,
+];
+
+/// Draw the trips without the GPU, for when there is no suitable adapter. This does the same
+/// work as the compute and vertex shaders, and produces the same lines.
+pub fn trip_mesh(state: &GpuTripRendererState, rect: Rect, pixels_per_point: f32) -> egui::Mesh {
+    let mut mesh = egui::Mesh::default();
+    let uniforms = &state.uniforms;
+    let ticks_min = uniforms.ticks_min.clamp(i32::MIN as i64, i32::MAX as i64) as i32;
+    let visible_ticks_max = ticks_min.saturating_add((rect.width() * uniforms.x_per_unit) as i32);
+    let range = state.draw_range(ticks_min, visible_ticks_max);
+    if range.total_entry_count == 0 {
+        return mesh;
+    }
+    let feathering = match state.antialiasing_mode {
+        AntialiasingMode::On => 1.2 / pixels_per_point,
+        AntialiasingMode::Off => 0.0,
+    };
+    let origin = egui::pos2(uniforms.screen_origin[0], uniforms.screen_origin[1]);
+    let point = |secs: TimetableTime, station_index: usize| {
+        let ticks = secs.0 as i64 * 100 - ticks_min as i64;
+        let height = state.stations.get(station_index).copied().unwrap_or(0.0);
+        origin
+            + egui::vec2(
+                ticks as f32 / uniforms.x_per_unit,
+                (height - uniforms.y_min as f32) / uniforms.y_per_unit,
+            )
+    };
+
+    // (start, end, style) of the segments before repeating
+    let mut segments = Vec::new();
+    for visible_offset in (0..range.visible_entry_count).step_by(range.lod_stride) {
+        let entry_index =
+            (range.visible_entry_min_index + visible_offset) % range.total_entry_count;
+        let entry = state.entry_at(entry_index);
+        let style = state.styles.get(entry.style_index()).copied().unwrap_or(0);
+        let arr = point(entry.arr_secs(), entry.station_index());
+        let dep = point(entry.dep_secs(), entry.station_index());
+        segments.push((arr, dep, style));
+        let can_connect = entry.connects_to_next_entry()
+            && visible_offset + range.lod_stride < range.visible_entry_count
+            && entry_index + range.lod_stride < range.total_entry_count;
+        if can_connect {
+            let next = state.entry_at(entry_index + range.lod_stride);
+            segments.push((dep, point(next.arr_secs(), next.station_index()), style));
+        }
+    }
+
+    for repeat in 0..range.repeat_count as i32 {
+        let repeat_ticks = (range.repeat_from + repeat) as i64 * range.repeat_interval as i64;
+        let offset = egui::vec2(repeat_ticks as f32 / uniforms.x_per_unit, 0.0);
+        for (a, b, style) in segments.iter().copied() {
+            let (a, b) = (a + offset, b + offset);
+            let half_width = (((style >> 24) & 0xff) as f32 * 0.25).max(1.0) * 0.5;
+            let margin = half_width + feathering;
+            if a.x.max(b.x) < rect.left() - margin || a.x.min(b.x) > rect.right() + margin {
+                continue;
+            }
+            add_segment(&mut mesh, a, b, half_width, style, feathering);
+        }
+    }
+    mesh
+}
+
+/// Add the quad of a segment to the mesh, with faded edges if `feathering` is not zero.
+fn add_segment(
+    mesh: &mut egui::Mesh,
+    a: egui::Pos2,
+    b: egui::Pos2,
+    half_width: f32,
+    style: u32,
+    feathering: f32,
+) {
+    let color = egui::Color32::from_rgb(style as u8, (style >> 8) as u8, (style >> 16) as u8);
+    let direction = b - a;
+    let normal = egui::vec2(-direction.y, direction.x) / direction.length().max(1e-6);
+    let half = (half_width - feathering * 0.5).max(0.01);
+    let (vertex_count, index_count) = if feathering > 0.0 { (8, 18) } else { (4, 6) };
+    let first = mesh.vertices.len() as u32;
+    for (along, side, outer) in &SEGMENT_MESH_VERTICES[..vertex_count] {
+        let position = a + direction * *along + normal * (side * (half + outer * feathering));
+        let color = if *outer > 0.0 {
+            egui::Color32::TRANSPARENT
+        } else {
+            color
+        };
+        mesh.colored_vertex(position, color);
+    }
+    mesh.indices.extend(
+        SEGMENT_MESH_INDICES[..index_count]
+            .iter()
+            .map(|it| first + it),
+    );
 }
 
 pub fn paint_callback(rect: Rect, state: Arc<Mutex<GpuTripRendererState>>) -> egui::PaintCallback {
@@ -660,73 +897,35 @@ impl CallbackTrait for TripCallback {
 
         let mut needs_rebind = false;
 
-        if state.entries_dirty {
-            let mut data_tick_min = i32::MAX;
-            let mut data_tick_max = i32::MIN;
-            for entry in state.entries.iter().flat_map(|(it, _, _)| it) {
-                let arr_ticks = entry.arr_secs().0.saturating_mul(100);
-                let dep_ticks = entry.dep_secs().0.saturating_mul(100);
-                data_tick_min = data_tick_min.min(arr_ticks.min(dep_ticks));
-                data_tick_max = data_tick_max.max(arr_ticks.max(dep_ticks));
-            }
-            if data_tick_min > data_tick_max {
-                data_tick_min = 0;
-                data_tick_max = 0;
-            }
-            state.data_tick_min = data_tick_min;
-            state.data_tick_max = data_tick_max;
-        }
-        let data_tick_min = state.data_tick_min;
-        let data_tick_max = state.data_tick_max;
-
-        let total_entry_count = state
-            .entries
-            .last()
-            .map(|(_, count, _)| *count)
-            .unwrap_or(0);
-        let visible_window_wraps = state.visible_secs_min >= state.visible_secs_max;
-
-        // Find the visible source-entry window [min, max) over the flattened entry array.
-        // `curr_max` is a prefix max departure time; `arr_secs` is segment start time.
-        let mut visible_entry_min_index = if state.entries.is_empty() {
-            0
-        } else {
-            let idx = state
-                .entries
-                .partition_point(|(_, _, max_secs)| *max_secs < state.visible_secs_min);
-            if idx == 0 {
-                0
-            } else {
-                state.entries[idx - 1].1
-            }
-        };
-        let visible_entry_max_index = if state.entries.is_empty() {
-            0
-        } else {
-            let idx = state.entries.partition_point(|(segment, _, _)| {
-                segment
-                    .first()
-                    .map(|entry| entry.arr_secs() <= state.visible_secs_max)
-                    .unwrap_or(false)
-            });
-            if idx == 0 {
-                0
-            } else {
-                state.entries[idx - 1].1
-            }
-        }
-        .min(total_entry_count);
-
-        let mut in_viewport_entry_count = if total_entry_count == 0 {
-            0
-        } else if visible_window_wraps {
-            total_entry_count
-                .saturating_sub(visible_entry_min_index)
-                .saturating_add(visible_entry_max_index)
-                .min(total_entry_count)
-        } else {
-            visible_entry_max_index.saturating_sub(visible_entry_min_index)
+        // uniforms
+        let uniforms = GpuUniforms {
+            ticks_min: state
+                .uniforms
+                .ticks_min
+                .clamp(i32::MIN as i64, i32::MAX as i64) as i32,
+            y_min: state.uniforms.y_min as f32,
+            screen_size: [
+                screen_descriptor.size_in_pixels[0] as f32 / screen_descriptor.pixels_per_point,
+                screen_descriptor.size_in_pixels[1] as f32 / screen_descriptor.pixels_per_point,
+            ],
+            x_per_unit: state.uniforms.x_per_unit,
+            y_per_unit: state.uniforms.y_per_unit,
+            screen_origin: state.uniforms.screen_origin,
+            ..Default::default()
         };
+        let visible_ticks_min = uniforms.ticks_min;
+        let visible_ticks_max = uniforms
+            .ticks_min
+            .saturating_add((uniforms.screen_size[0] * uniforms.x_per_unit) as i32);
+        let DrawRange {
+            total_entry_count,
+            visible_entry_min_index,
+            visible_entry_count: in_viewport_entry_count,
+            repeat_interval,
+            repeat_from,
+            repeat_count,
+            lod_stride,
+        } = state.draw_range(visible_ticks_min, visible_ticks_max);
 
         // Entries
         if total_entry_count > 0 {
@@ -774,50 +973,6 @@ impl CallbackTrait for TripCallback {
             }
         }
 
-        // uniforms
-        let uniforms = GpuUniforms {
-            ticks_min: state
-                .uniforms
-                .ticks_min
-                .clamp(i32::MIN as i64, i32::MAX as i64) as i32,
-            y_min: state.uniforms.y_min as f32,
-            screen_size: [
-                screen_descriptor.size_in_pixels[0] as f32 / screen_descriptor.pixels_per_point,
-                screen_descriptor.size_in_pixels[1] as f32 / screen_descriptor.pixels_per_point,
-            ],
-            x_per_unit: state.uniforms.x_per_unit,
-            y_per_unit: state.uniforms.y_per_unit,
-            screen_origin: state.uniforms.screen_origin,
-            ..Default::default()
-        };
-        let visible_ticks_min = uniforms.ticks_min;
-        let visible_ticks_max = uniforms
-            .ticks_min
-            .saturating_add((uniforms.screen_size[0] * uniforms.x_per_unit) as i32);
-        let repeat_interval = state.uniforms.repeat_interval_ticks.max(0);
-
-        // If one viewport already spans at least one full repeat period, culling would only
-        // remove segments that are still visible after wrapping. Render everything instead.
-        if repeat_interval > 0
-            && visible_ticks_max.saturating_sub(visible_ticks_min) >= repeat_interval
-        {
-            visible_entry_min_index = 0;
-            in_viewport_entry_count = total_entry_count;
-        }
-
-        let lod_stride = state.level_of_detail_mode.as_u8() as usize;
-        let repeat_from = if repeat_interval > 0 {
-            (visible_ticks_min - data_tick_max).div_euclid(repeat_interval)
-        } else {
-            0
-        };
-        let repeat_count = if repeat_interval > 0 {
-            let repeat_to = (visible_ticks_max - data_tick_min).div_euclid(repeat_interval);
-            (repeat_to - repeat_from + 1).max(1) as usize
-        } else {
-            1usize
-        };
-
         let logical_entry_count = in_viewport_entry_count.div_ceil(lod_stride);
         let rendered_segment_count = logical_entry_count.saturating_mul(2);
         let total_instances = rendered_segment_count.saturating_mul(repeat_count);
@@ -1,23 +1,40 @@
-use super::{TripCache, TripPoint};
+use super::{TripCache, TripPoint, draw_lines::ConnectionArc};
 use bevy::{ecs::entity::EntityHashMap, prelude::*};
 use paiagram_core::{
     entry::{EntryEstimate, EntryQuery},
+    export::diagram::{SegmentEntry, SegmentSplitter},
     route::{Route, RouteTrips},
     station::ParentStationOrStation,
-    trip::TripQuery,
+    trip::{
+        TripQuery, TripScenario, TripSchedule,
+        calendar::{ServiceCalendar, ServiceDate},
+        connection::TripConnection,
+        frequency::TripFrequency,
+    },
+    units::time::Duration,
 };
 use smallvec::SmallVec;
-use vec1::{Vec1, vec1};
+use vec1::Vec1;
 
 pub(crate) fn calc(
-    (In(route_entity), InRef(heights), InMut(map)): (
+    (In(route_entity), InRef(heights), InMut(map), In(service_day), In(scenario)): (
         In<Entity>,
         InRef<[(Entity, f32)]>,
         InMut<Option<TripCache>>,
+        In<Option<ServiceDate>>,
+        In<Option<Entity>>,
     ),
     route_q: Query<(&RouteTrips, Ref<Route>)>,
     trip_q: Query<TripQuery>,
     changed_entries: Query<&ChildOf, Changed<EntryEstimate>>,
+    changed_filters: Query<
+        Entity,
+        Or<(
+            Changed<ServiceCalendar>,
+            Changed<TripScenario>,
+            Changed<TripFrequency>,
+        )>,
+    >,
     entries: Query<EntryQuery>,
     parent_station_or_station: Query<ParentStationOrStation>,
     mut invalidate_cache: Local<Vec<Entity>>,
@@ -27,13 +44,17 @@ pub(crate) fn calc(
     let refresh_candidates = if map.is_none() || route.is_changed() {
         trips.as_slice()
     } else {
-        if changed_entries.is_empty() {
+        if changed_entries.is_empty() && changed_filters.is_empty() {
             return false;
         }
         let mut trips = trips.to_vec();
         trips.sort_unstable();
         invalidate_cache.clear();
-        for entity in changed_entries.iter().map(ChildOf::parent) {
+        for entity in changed_entries
+            .iter()
+            .map(ChildOf::parent)
+            .chain(changed_filters.iter())
+        {
             let Ok(_) = trips.binary_search(&entity) else {
                 continue;
             };
@@ -45,172 +66,86 @@ pub(crate) fn calc(
     };
 
     let map: &mut EntityHashMap<SmallVec<[Vec1<TripPoint>; 1]>> = map.get_or_insert_default();
-
-    #[derive(Clone, Copy)]
-    struct TripEntryData {
-        entity: Entity,
-        station: Entity,
-        estimate: Option<EntryEstimate>,
-    }
-
-    struct TripData {
-        entity: Entity,
-        // stroke: DisplayedStroke,
-        entries: Vec<TripEntryData>,
-    }
-
-    let stations_for_layout = &heights[..];
-    let mut station_index_map: EntityHashMap<SmallVec<[usize; 2]>> = EntityHashMap::new();
-    for (idx, (station, _)) in stations_for_layout.iter().enumerate() {
-        station_index_map.entry(*station).or_default().push(idx);
-    }
-
-    let mut trip_data = Vec::with_capacity(refresh_candidates.len());
+    let splitter = SegmentSplitter::new(heights.iter().map(|(station, _)| *station));
     for trip_entity in refresh_candidates.iter().copied() {
         let trip = trip_q.get(trip_entity).unwrap();
-        let mut trip_entries_vec: Vec<TripEntryData> = Vec::new();
-        for entry_entity in trip.schedule.iter().copied() {
-            let entry = entries.get(entry_entity).unwrap();
-            let station_entity = parent_station_or_station
-                .get(entry.stop())
-                .unwrap()
-                .parent();
-            trip_entries_vec.push(TripEntryData {
-                entity: entry_entity,
-                station: station_entity,
-                estimate: entry.estimate.copied(),
-            });
-        }
-
-        if trip_entries_vec.len() < 2 {
+        if !trip.runs_on(service_day) || !trip.is_in_scenario(scenario) {
+            map.remove(&trip_entity);
             continue;
         }
-
-        trip_data.push(TripData {
-            entity: trip_entity,
-            entries: trip_entries_vec,
-        });
-    }
-
-    for trip in trip_data.into_iter() {
-        let trip_bucket = map.entry(trip.entity).or_default();
-        trip_bucket.clear();
-
-        let mut push_to_bucket = |points: Vec1<TripPoint>| {
-            if points.len() < 2 {
-                return;
-            }
-            trip_bucket.push(points);
-        };
-
-        let trip_entries = trip.entries.as_slice();
-        if trip_entries.len() < 2 {
-            continue;
-        }
-
-        // points, end index
-        let mut local_edges: Vec<Vec1<TripPoint>> = Vec::new();
-        let mut previous_indices: &[usize] = &[];
-
-        if let Some(first) = trip_entries.first()
-            && let Some(indices) = station_index_map.get(&first.station)
-        {
-            previous_indices = indices.as_slice();
-        }
-
-        for entry_idx in 0..trip_entries.len() {
-            let curr = &trip_entries[entry_idx];
-            let next = trip_entries.get(entry_idx + 1);
-
-            if previous_indices.is_empty() {
-                if let Some(next) = next {
-                    if let Some(indices) = station_index_map.get(&next.station) {
-                        previous_indices = indices.as_slice();
-                    } else {
-                        previous_indices = &[];
-                    }
-                }
-                for it in local_edges.drain(..) {
-                    push_to_bucket(it)
+        let trip_entries: Vec<SegmentEntry> = trip
+            .schedule
+            .iter()
+            .map(|entry_entity| {
+                let entry = entries.get(*entry_entity).unwrap();
+                SegmentEntry {
+                    entry: *entry_entity,
+                    station: parent_station_or_station
+                        .get(entry.stop())
+                        .unwrap()
+                        .parent(),
+                    estimate: entry.estimate.copied(),
                 }
-                continue;
-            }
-
-            let Some(estimate) = curr.estimate else {
-                for it in local_edges.drain(..) {
-                    push_to_bucket(it)
-                }
-                if let Some(next_entry) = next {
-                    if let Some(indices) = station_index_map.get(&next_entry.station) {
-                        previous_indices = indices.as_slice();
-                    } else {
-                        previous_indices = &[];
-                    }
-                }
-                continue;
-            };
-
-            let mut next_local_edges: Vec<Vec1<TripPoint>> = Vec::new();
-
-            for current_line_index in previous_indices.iter().copied() {
-                let matched_idx = local_edges
-                    .iter()
-                    .position(|it| current_line_index.abs_diff(it.last().station_index) <= 1);
-
-                let segment = if let Some(idx) = matched_idx {
-                    let mut a = local_edges.swap_remove(idx);
-                    a.push(TripPoint {
-                        arr: estimate.arr,
-                        dep: estimate.dep,
-                        entry: curr.entity,
-                        station_index: current_line_index,
-                    });
-                    a
-                } else {
-                    vec1![TripPoint {
-                        arr: estimate.arr,
-                        dep: estimate.dep,
-                        entry: curr.entity,
-                        station_index: current_line_index,
-                    }]
-                };
-
-                let mut segment = Some(segment);
-                if let Some(next_entry) = next {
-                    for offset in [-1, 0, 1] {
-                        let next_idx = (current_line_index as isize + offset) as usize;
-                        if let Some((s, _)) = stations_for_layout.get(next_idx) {
-                            if *s == next_entry.station {
-                                let forwarded_segment = segment.take().unwrap();
-                                next_local_edges.push(forwarded_segment);
-                                break;
-                            }
-                        }
-                    }
-                }
-
-                if let Some(it) = segment {
-                    push_to_bucket(it);
-                }
-            }
-
-            for it in local_edges.drain(..) {
-                push_to_bucket(it)
-            }
-
-            local_edges = next_local_edges;
-            if let Some(next_entry) = next {
-                if let Some(indices) = station_index_map.get(&next_entry.station) {
-                    previous_indices = indices.as_slice();
-                } else {
-                    previous_indices = &[];
-                }
-            }
-        }
-
-        for it in local_edges.drain(..) {
-            push_to_bucket(it)
-        }
+            })
+            .collect();
+        let trip_bucket = map.entry(trip_entity).or_default();
+        trip_bucket.clear();
+        trip_bucket.extend(
+            splitter
+                .split(&trip_entries)
+                .into_iter()
+                .filter_map(|segment| {
+                    let points = segment
+                        .into_iter()
+                        .map(|it| TripPoint {
+                            arr: it.arr,
+                            dep: it.dep,
+                            entry: it.entry,
+                            station_index: it.station_index,
+                        })
+                        .collect();
+                    Vec1::try_from_vec(points).ok()
+                }),
+        );
     }
     return true;
 }
+
+/// Offsets of the runs of frequency-based trips in the cache.
+pub(crate) fn run_offsets(
+    InRef(map): InRef<TripCache>,
+    trip_q: Query<TripQuery, With<TripFrequency>>,
+    estimate_q: Query<&EntryEstimate>,
+) -> EntityHashMap<SmallVec<[Duration; 1]>> {
+    trip_q
+        .iter_many(map.keys())
+        .map(|trip| (trip.entity, trip.run_offsets(&estimate_q)))
+        .collect()
+}
+
+/// Connections between trips in the cache.
+pub(crate) fn connections(
+    InRef(map): InRef<TripCache>,
+    connection_q: Query<&TripConnection>,
+    schedule_q: Query<&TripSchedule>,
+    entry_q: Query<EntryQuery>,
+    parent_station_or_station: Query<ParentStationOrStation>,
+) -> Vec<ConnectionArc> {
+    connection_q
+        .iter()
+        .filter(|it| map.contains_key(&it.feeder) && map.contains_key(&it.connecting))
+        .filter_map(|connection| {
+            let (feeder_entry, connecting_entry) =
+                connection.entries(&schedule_q, &entry_q, &parent_station_or_station)?;
+            let arrival = entry_q.get(feeder_entry).ok()?.estimate?.arr;
+            let departure = entry_q.get(connecting_entry).ok()?.estimate?.dep;
+            Some(ConnectionArc {
+                feeder: connection.feeder,
+                feeder_entry,
+                connecting: connection.connecting,
+                connecting_entry,
+                missed: arrival + connection.transfer_time > departure,
+            })
+        })
+        .collect()
+}
@@ -6,14 +6,18 @@ use egui::{
 };
 use egui_i18n::tr;
 use paiagram_core::graph::{AddIntervalPair, Graph, NodeCoor};
-use paiagram_core::interval::IntervalQuery;
+use paiagram_core::interval::{Interval, IntervalQuery};
 use paiagram_core::route::Route;
-use paiagram_core::station::{CreateNewStation, StationNamePending, StationQuery};
-use paiagram_core::units::distance::Distance;
+use paiagram_core::station::{
+    CreateNewStation, Footpath, Footpaths, MinimumTransferTime, MinimumTurnaround, StationCapacity,
+    StationNamePending, StationQuery,
+};
+use paiagram_core::units::{distance::Distance, speed::Velocity, time::Duration};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use walkers::sources::Attribution;
 
+use crate::widgets::DurationDragValue;
 use crate::{
     CoordinateSelection, IntervalSelection, ModifySelectedItems, SelectedItem, SelectedItems,
     StationSelection, TripSelection,
@@ -24,7 +28,7 @@ use crate::{GlobalTimer, tabs::Navigatable};
 use paiagram_core::{
     colors::PredefinedColor,
     graph::{GraphIntervalSpatialIndex, GraphSpatialIndex, Node},
-    settings::ProjectSettings,
+    settings::{ProjectSettings, UserPreferences},
     trip::{
         Trip, TripClass, TripSpatialIndex,
         class::{Class, DisplayedStroke},
@@ -32,6 +36,8 @@ use paiagram_core::{
 };
 
 mod gpu_draw;
+mod isochrone;
+mod od_matrix;
 mod underlay;
 
 /// The state of the graph
@@ -84,6 +90,10 @@ pub struct GraphTab {
     gpu_state: Arc<egui::mutex::Mutex<gpu_draw::GpuGraphRendererState>>,
     #[serde(skip, default)]
     highlight_station_intervals: Vec<Entity>,
+    #[serde(skip, default)]
+    isochrone: isochrone::IsochroneParams,
+    #[serde(skip, default)]
+    od_matrix: od_matrix::OdMatrixParams,
 }
 
 fn default_arrange_iterations() -> u32 {
@@ -102,6 +112,8 @@ impl Default for GraphTab {
                 gpu_draw::GpuGraphRendererState::default(),
             )),
             highlight_station_intervals: Vec::new(),
+            isochrone: isochrone::IsochroneParams::default(),
+            od_matrix: od_matrix::OdMatrixParams::default(),
         }
     }
 }
@@ -252,6 +264,12 @@ impl super::Tab for GraphTab {
             SelectedItems::ExtendingRoute(r) => {}
         }
     }
+    fn display_display(&mut self, world: &mut World, ui: &mut egui::Ui) {
+        self.isochrone.show(world, ui);
+    }
+    fn export_display(&mut self, world: &mut World, ui: &mut egui::Ui) {
+        self.od_matrix.show(world, ui);
+    }
 }
 
 fn display_station_info(
@@ -262,13 +280,68 @@ fn display_station_info(
     ),
     station_q: Query<StationQuery>,
     interval_q: Query<IntervalQuery>,
+    mut capacity_q: Query<Option<&mut StationCapacity>>,
+    mut turnaround_q: Query<Option<&mut MinimumTurnaround>>,
+    mut transfer_time_q: Query<Option<&mut MinimumTransferTime>>,
+    mut footpath_q: Query<(Entity, &mut Footpaths)>,
+    name_q: Query<&Name>,
     graph: Res<Graph>,
+    settings: Res<ProjectSettings>,
     mut commands: Commands,
     mut last_hovered: Local<bool>,
 ) {
     for station in station_q.iter_many(selected_stations.iter().map(|it| it.station)) {
         ui.label(station.name.as_ref());
     }
+    if let [selected] = selected_stations {
+        edit_station_tracks(
+            ui,
+            selected.station,
+            &graph,
+            &mut capacity_q,
+            &mut turnaround_q,
+            &mut transfer_time_q,
+            &interval_q,
+            &name_q,
+            &settings,
+            &mut commands,
+        );
+        edit_station_footpaths(
+            ui,
+            selected.station,
+            &mut footpath_q,
+            &name_q,
+            &mut commands,
+        );
+    }
+    if let [a, b] = selected_stations
+        // footpaths can be walked in both directions
+        && ui
+            .add_enabled(
+                ![(a.station, b.station), (b.station, a.station)]
+                    .into_iter()
+                    .any(|(from, to)| {
+                        footpath_q
+                            .get(from)
+                            .is_ok_and(|(_, footpaths)| footpaths.iter().any(|it| it.to == to))
+                    }),
+                egui::Button::new(tr!("tab-graph-station-add-footpath")),
+            )
+            .clicked()
+        && let (Ok(a_item), Ok(b_item)) = (station_q.get(a.station), station_q.get(b.station))
+    {
+        let walking_time =
+            a_item.position.coor.distance(&b_item.position.coor) / settings.walking_speed;
+        let footpath = Footpath {
+            to: b.station,
+            walking_time,
+        };
+        commands
+            .entity(a.station)
+            .entry::<Footpaths>()
+            .or_default()
+            .and_modify(move |mut it| it.push(footpath));
+    }
     let res = ui.button("Create new route");
     if selected_stations.len() < 2 {
         *last_hovered = res.hovered();
@@ -297,6 +370,216 @@ fn display_station_info(
     *last_hovered = res.hovered();
 }
 
+/// Edit the capacity of a station and the number of tracks of its adjacent intervals
+fn edit_station_tracks(
+    ui: &mut Ui,
+    station: Entity,
+    graph: &Graph,
+    capacity_q: &mut Query<Option<&mut StationCapacity>>,
+    turnaround_q: &mut Query<Option<&mut MinimumTurnaround>>,
+    transfer_time_q: &mut Query<Option<&mut MinimumTransferTime>>,
+    interval_q: &Query<IntervalQuery>,
+    name_q: &Query<&Name>,
+    settings: &ProjectSettings,
+    commands: &mut Commands,
+) {
+    egui::Grid::new("station tracks grid")
+        .num_columns(4)
+        .show(ui, |ui| {
+            if let Ok(capacity) = capacity_q.get_mut(station) {
+                let mut limited = capacity.is_some();
+                let mut value = capacity.as_ref().map_or(2, |it| it.0);
+                let mut changed = ui
+                    .checkbox(&mut limited, tr!("tab-graph-station-capacity"))
+                    .changed();
+                if limited {
+                    changed |= ui
+                        .add(egui::DragValue::new(&mut value).range(1..=u8::MAX))
+                        .changed();
+                } else {
+                    ui.label("∞");
+                }
+                match (changed, limited, capacity) {
+                    (false, ..) => {}
+                    (true, true, Some(mut capacity)) => capacity.0 = value,
+                    (true, true, None) => {
+                        commands.entity(station).insert(StationCapacity(value));
+                    }
+                    (true, false, _) => {
+                        commands.entity(station).remove::<StationCapacity>();
+                    }
+                }
+                ui.end_row();
+            }
+            if let Ok(turnaround) = turnaround_q.get_mut(station) {
+                let mut overridden = turnaround.is_some();
+                let mut value = turnaround
+                    .as_ref()
+                    .map_or(Duration::from_secs(300), |it| it.0);
+                let mut changed = ui
+                    .checkbox(&mut overridden, tr!("tab-graph-station-turnaround"))
+                    .changed();
+                if overridden {
+                    changed |= ui.add(DurationDragValue(&mut value)).changed();
+                } else {
+                    ui.label("—");
+                }
+                match (changed, overridden, turnaround) {
+                    (false, ..) => {}
+                    (true, true, Some(mut turnaround)) => turnaround.0 = value,
+                    (true, true, None) => {
+                        commands.entity(station).insert(MinimumTurnaround(value));
+                    }
+                    (true, false, _) => {
+                        commands.entity(station).remove::<MinimumTurnaround>();
+                    }
+                }
+                ui.end_row();
+            }
+            if let Ok(transfer_time) = transfer_time_q.get_mut(station) {
+                let mut overridden = transfer_time.is_some();
+                let mut value = transfer_time
+                    .as_ref()
+                    .map_or(settings.minimum_transfer_time, |it| it.0);
+                let mut changed = ui
+                    .checkbox(&mut overridden, tr!("tab-graph-station-transfer-time"))
+                    .changed();
+                if overridden {
+                    changed |= ui.add(DurationDragValue(&mut value)).changed();
+                } else {
+                    ui.label("—");
+                }
+                match (changed, overridden, transfer_time) {
+                    (false, ..) => {}
+                    (true, true, Some(mut transfer_time)) => transfer_time.0 = value,
+                    (true, true, None) => {
+                        commands.entity(station).insert(MinimumTransferTime(value));
+                    }
+                    (true, false, _) => {
+                        commands.entity(station).remove::<MinimumTransferTime>();
+                    }
+                }
+                ui.end_row();
+            }
+            for (_, neighbour, interval) in graph.edges(station) {
+                let Ok(item) = interval_q.get(*interval) else {
+                    continue;
+                };
+                let leg = item.leg();
+                let mut tracks = item.tracks();
+                ui.label(tr!(
+                    "tab-graph-interval-tracks",
+                    { station: name_q.get(neighbour).map_or("<??>", Name::as_str) }
+                ));
+                if ui
+                    .add(egui::DragValue::new(&mut tracks).range(1..=u8::MAX))
+                    .changed()
+                {
+                    // tracks are usually shared by both directions
+                    for entity in [
+                        Some(*interval),
+                        graph.edge_weight(neighbour, station).copied(),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    {
+                        commands
+                            .entity(entity)
+                            .entry::<Interval>()
+                            .and_modify(move |mut it| it.tracks = tracks);
+                    }
+                }
+                // speed limits and gradients differ by direction
+                let original_speed_limit = leg.speed_limit.map_or(0.0, |it| it.to_kmh());
+                let mut speed_limit = original_speed_limit;
+                let mut gradient = leg.gradient;
+                let speed_limit_changed = ui
+                    .add(
+                        egui::DragValue::new(&mut speed_limit)
+                            .range(0.0..=600.0)
+                            .custom_formatter(|v, _| {
+                                if v <= 0.0 {
+                                    "—".to_string()
+                                } else {
+                                    format!("{v:.0} km/h")
+                                }
+                            }),
+                    )
+                    .on_hover_text(tr!("tab-graph-interval-speed-limit"))
+                    .changed();
+                let gradient_changed = ui
+                    .add(
+                        egui::DragValue::new(&mut gradient)
+                            .range(-100.0..=100.0)
+                            .speed(0.1)
+                            .suffix(" ‰"),
+                    )
+                    .on_hover_text(tr!("tab-graph-interval-gradient"))
+                    .changed();
+                if speed_limit_changed || gradient_changed {
+                    commands
+                        .entity(*interval)
+                        .entry::<Interval>()
+                        .and_modify(move |mut it| {
+                            if speed_limit != original_speed_limit {
+                                it.speed_limit =
+                                    (speed_limit > 0.0).then(|| Velocity::from_kmh(speed_limit));
+                            }
+                            it.gradient = gradient;
+                        });
+                }
+                ui.end_row();
+            }
+        });
+}
+
+/// Edit the walking times of the footpaths of a station, or remove them
+fn edit_station_footpaths(
+    ui: &mut Ui,
+    station: Entity,
+    footpath_q: &mut Query<(Entity, &mut Footpaths)>,
+    name_q: &Query<&Name>,
+    commands: &mut Commands,
+) {
+    egui::Grid::new("station footpaths grid")
+        .num_columns(3)
+        .show(ui, |ui| {
+            for (from, mut footpaths) in footpath_q.iter_mut() {
+                let mut changed = false;
+                let mut removed = None;
+                // footpaths can be walked in both directions
+                for (idx, footpath) in footpaths.bypass_change_detection().iter_mut().enumerate() {
+                    let other = if from == station {
+                        footpath.to
+                    } else if footpath.to == station {
+                        from
+                    } else {
+                        continue;
+                    };
+                    ui.label(tr!(
+                        "tab-graph-station-footpath",
+                        { station: name_q.get(other).map_or("<??>", Name::as_str) }
+                    ));
+                    changed |= ui
+                        .add(DurationDragValue(&mut footpath.walking_time))
+                        .changed();
+                    if ui.button("×").clicked() {
+                        removed = Some(idx);
+                    }
+                    ui.end_row();
+                }
+                if let Some(idx) = removed {
+                    footpaths.remove(idx);
+                    if footpaths.is_empty() {
+                        commands.entity(from).remove::<Footpaths>();
+                    }
+                } else if changed {
+                    footpaths.set_changed();
+                }
+            }
+        });
+}
+
 fn display(tab: &mut GraphTab, world: &mut World, ui: &mut egui::Ui) {
     // allocate painter for drawing afterwards
     let (response, mut painter) =
@@ -340,6 +623,7 @@ fn display(tab: &mut GraphTab, world: &mut World, ui: &mut egui::Ui) {
                 &mut painter,
                 interact_pos,
                 ui.animate_bool(ui.id().with("gugugaga"), tab.navi.zoom > 0.002),
+                &tab.isochrone.result,
             ),
         )
         .unwrap();
@@ -370,8 +654,14 @@ fn display(tab: &mut GraphTab, world: &mut World, ui: &mut egui::Ui) {
         }
     }
 
-    let callback = gpu_draw::paint_callback(response.rect, tab.gpu_state.clone());
-    painter.add(callback);
+    // the target format is only known if there is a suitable adapter to draw with
+    if state.target_format.is_some() {
+        let callback = gpu_draw::paint_callback(response.rect, tab.gpu_state.clone());
+        painter.add(callback);
+    } else {
+        let antialiasing = world.resource::<UserPreferences>().antialiasing_mode;
+        painter.extend(state.instances.iter().map(|it| it.to_shape(antialiasing)));
+    }
 
     // draw the attribution and the scale bar
     if let Some(attribution) = attribution {
@@ -642,6 +932,7 @@ fn push_draw_items(
         InMut(painter),
         In(maybe_interact_pos),
         In(text_strength),
+        InRef(isochrone),
     ): (
         In<bool>,
         InRef<GraphNavigation>,
@@ -649,6 +940,7 @@ fn push_draw_items(
         InMut<Painter>,
         In<Option<Pos2>>,
         In<f32>,
+        InRef<Option<isochrone::Isochrone>>,
     ),
     nodes: Query<(Entity, &Node, Option<&Name>)>,
     spatial_index: Res<GraphSpatialIndex>,
@@ -771,12 +1063,43 @@ fn push_draw_items(
             GraphState::SelectingStations(_) | GraphState::SelectingStation(_)
         );
 
-        buffer.push(gpu_draw::ShapeInstance::circle(
-            station_screen_pos,
-            4.0,
-            color,
-        ));
-        draw_name(name.map(Name::as_str), station_screen_pos, color);
+        let Some(isochrone) = isochrone else {
+            buffer.push(gpu_draw::ShapeInstance::circle(
+                station_screen_pos,
+                4.0,
+                color,
+            ));
+            draw_name(name.map(Name::as_str), station_screen_pos, color);
+            continue;
+        };
+        // stations that cannot be reached are faded out
+        match (
+            isochrone.color(station_entity),
+            isochrone.travel_time(station_entity),
+        ) {
+            (Some(station_color), Some(travel_time)) => {
+                buffer.push(gpu_draw::ShapeInstance::circle(
+                    station_screen_pos,
+                    6.0,
+                    station_color,
+                ));
+                let label = format!(
+                    "{} {}",
+                    name.map_or("", Name::as_str),
+                    travel_time.to_string_no_arrow()
+                );
+                draw_name(Some(label.as_str()), station_screen_pos, station_color);
+            }
+            _ => {
+                let faded = color.gamma_multiply(0.3);
+                buffer.push(gpu_draw::ShapeInstance::circle(
+                    station_screen_pos,
+                    4.0,
+                    faded,
+                ));
+                draw_name(name.map(Name::as_str), station_screen_pos, faded);
+            }
+        }
     }
 
     // entries
@@ -2,6 +2,7 @@ use bytemuck::cast_slice;
 use eframe::egui_wgpu::{self, wgpu};
 use egui::{Color32, Pos2, Rect, Vec2, mutex::Mutex};
 use egui_wgpu::CallbackTrait;
+use paiagram_core::settings::AntialiasingMode;
 use std::sync::Arc;
 
 impl ShapeInstance {
@@ -71,6 +72,80 @@ impl ShapeInstance {
             kind: 2,
         }
     }
+    /// The same shape as drawn by `gpu_graph.wgsl`, for drawing without the GPU. egui feathers
+    /// the edges of lines and circles but not of meshes, hence shapes are drawn as meshes if
+    /// antialiasing is off.
+    pub fn to_shape(&self, antialiasing: AntialiasingMode) -> egui::Shape {
+        let a = Pos2::from(self.a);
+        let b = Pos2::from(self.b);
+        let [red, green, blue, alpha] = self.color.map(|it| (it * 255.0).round() as u8);
+        let color = Color32::from_rgba_premultiplied(red, green, blue, alpha);
+        let smooth = antialiasing == AntialiasingMode::On;
+        // a polygon drawn as a fan of triangles from its first point
+        let fan = |points: &[Pos2]| {
+            let mut mesh = egui::Mesh::default();
+            for position in points {
+                mesh.colored_vertex(*position, color);
+            }
+            for idx in 1..points.len().saturating_sub(1) as u32 {
+                mesh.add_triangle(0, idx, idx + 1);
+            }
+            egui::Shape::mesh(mesh)
+        };
+        match self.kind {
+            0 if smooth => {
+                egui::Shape::line_segment([a, b], egui::Stroke::new(self.size.max(1.0), color))
+            }
+            0 => {
+                let direction = b - a;
+                let direction = direction / direction.length().max(0.0001);
+                let normal = Vec2::new(-direction.y, direction.x) * self.size.max(1.0) * 0.5;
+                fan(&[a + normal, b + normal, b - normal, a - normal])
+            }
+            2 => {
+                let direction = b - a;
+                let direction = direction / direction.length().max(0.0001);
+                let normal = Vec2::new(-direction.y, direction.x);
+                let arrow_len = self.size.max(1.0);
+                let half_width = arrow_len * (12.0 / 14.0) * 0.5;
+                let stealth = 0.2;
+                let point = |x: f32, y: f32| a + direction * x + normal * y;
+                let tip = point(arrow_len * (1.0 - stealth) * 0.5, 0.0);
+                let indent = point(-arrow_len * (1.0 - stealth) * 0.5, 0.0);
+                let left_x = -arrow_len * (1.0 + stealth) * 0.5;
+                let (left, right) = (point(left_x, half_width), point(left_x, -half_width));
+                // the arrow is concave, so it is drawn as two triangles
+                if smooth {
+                    egui::Shape::Vec(vec![
+                        egui::Shape::convex_polygon(
+                            vec![tip, left, indent],
+                            color,
+                            egui::Stroke::NONE,
+                        ),
+                        egui::Shape::convex_polygon(
+                            vec![tip, indent, right],
+                            color,
+                            egui::Stroke::NONE,
+                        ),
+                    ])
+                } else {
+                    fan(&[tip, left, indent, right])
+                }
+            }
+            _ if smooth => egui::Shape::circle_filled(a, self.size.max(0.5), color),
+            _ => {
+                let radius = self.size.max(0.5);
+                let count = ((radius * 2.0) as usize).clamp(8, 64);
+                let points: Vec<Pos2> = (0..count)
+                    .map(|it| {
+                        let angle = it as f32 / count as f32 * std::f32::consts::TAU;
+                        a + Vec2::angled(angle) * radius
+                    })
+                    .collect();
+                fan(&points)
+            }
+        }
+    }
 }
 
 #[repr(C)]
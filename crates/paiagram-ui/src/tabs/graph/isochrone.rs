@@ -0,0 +1,189 @@
+use crate::tabs::diagram::select_scenario;
+use crate::tabs::journey_planner::select_station;
+use crate::widgets::{ServiceDayFilter, TimeDragValue};
+use bevy::ecs::entity::EntityHashMap;
+use bevy::prelude::*;
+use egui::{Color32, Ui, ecolor::Hsva};
+use egui_i18n::tr;
+use paiagram_core::{
+    export::{ExportObject, csv_field},
+    station::Station,
+    trip::calendar::ServiceDate,
+    units::time::{Duration, TimetableTime},
+};
+use paiagram_raptor::Reach;
+use std::borrow::Cow;
+use std::io::Write;
+
+/// The parameters of the isochrone shown on the graph, and the isochrone itself
+#[derive(Clone)]
+pub(super) struct IsochroneParams {
+    origin: Option<Entity>,
+    earliest: TimetableTime,
+    latest: TimetableTime,
+    max_transfers: usize,
+    service_day: Option<ServiceDate>,
+    scenario: Option<Entity>,
+    pub result: Option<Isochrone>,
+}
+
+impl Default for IsochroneParams {
+    fn default() -> Self {
+        Self {
+            origin: None,
+            earliest: TimetableTime::from_hms(7, 0, 0),
+            latest: TimetableTime::from_hms(9, 0, 0),
+            max_transfers: paiagram_raptor::MAX_TRIPS - 1,
+            service_day: None,
+            scenario: None,
+            result: None,
+        }
+    }
+}
+
+/// How well every station can be reached from the origin
+#[derive(Clone)]
+pub(super) struct Isochrone {
+    origin: Entity,
+    reach: EntityHashMap<Reach>,
+    /// The longest travel time to a reached station
+    longest: Duration,
+}
+
+impl Isochrone {
+    /// The colour of the station, from green for the origin to red for the longest travel time.
+    pub fn color(&self, station: Entity) -> Option<Color32> {
+        let reach = self.reach.get(&station)?;
+        let f = reach.travel_time.0 as f32 / self.longest.0.max(1) as f32;
+        Some(Hsva::new((1.0 - f) / 3.0, 0.85, 0.85, 1.0).into())
+    }
+    /// The shortest travel time to the station
+    pub fn travel_time(&self, station: Entity) -> Option<Duration> {
+        self.reach.get(&station).map(|it| it.travel_time)
+    }
+}
+
+impl IsochroneParams {
+    pub fn show(&mut self, world: &mut World, ui: &mut Ui) {
+        ui.strong(tr!("tab-graph-isochrone"));
+        ui.label(tr!("tab-graph-isochrone-desc"));
+        ui.horizontal(|ui| {
+            ui.label(tr!("tab-graph-isochrone-origin"));
+            world
+                .run_system_cached_with(select_station, (ui, &mut self.origin))
+                .unwrap();
+        });
+        ui.horizontal(|ui| {
+            ui.label(tr!("tab-graph-isochrone-window"));
+            ui.add(TimeDragValue(&mut self.earliest));
+            ui.label("–");
+            ui.add(TimeDragValue(&mut self.latest));
+        });
+        ui.horizontal(|ui| {
+            ui.label(tr!("tab-graph-isochrone-max-transfers"));
+            ui.add(egui::DragValue::new(&mut self.max_transfers).range(0..=20));
+        });
+        ui.add(ServiceDayFilter(&mut self.service_day));
+        world
+            .run_system_cached_with(select_scenario, (ui, &mut self.scenario))
+            .unwrap();
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(
+                    self.origin.is_some(),
+                    egui::Button::new(tr!("tab-graph-isochrone-compute")),
+                )
+                .clicked()
+                && let Some(origin) = self.origin
+            {
+                let timetable = world
+                    .run_system_cached_with(
+                        paiagram_raptor::timetable,
+                        (self.service_day, self.scenario),
+                    )
+                    .unwrap();
+                let reach = timetable.reach(
+                    origin,
+                    self.earliest,
+                    self.latest.max(self.earliest),
+                    self.max_transfers + 1,
+                );
+                let longest = reach
+                    .values()
+                    .map(|it| it.travel_time)
+                    .max()
+                    .unwrap_or_default();
+                self.result = Some(Isochrone {
+                    origin,
+                    reach,
+                    longest,
+                });
+            }
+            if ui
+                .add_enabled(
+                    self.result.is_some(),
+                    egui::Button::new(tr!("tab-graph-isochrone-clear")),
+                )
+                .clicked()
+            {
+                self.result = None;
+            }
+        });
+        let Some(isochrone) = &self.result else {
+            return;
+        };
+        let total = world
+            .query_filtered::<(), With<Station>>()
+            .iter(world)
+            .count();
+        ui.label(tr!(
+            "tab-graph-isochrone-reached",
+            {
+                reached: isochrone.reach.len(),
+                total: total,
+                longest: isochrone.longest.to_string_no_arrow()
+            }
+        ));
+        if ui.button(tr!("tab-graph-isochrone-export")).clicked() {
+            IsochroneCsv { isochrone, world }.export_to_file();
+        }
+    }
+}
+
+/// The isochrone as CSV, with a row for every station. Stations that cannot be reached have
+/// empty times.
+struct IsochroneCsv<'a> {
+    isochrone: &'a Isochrone,
+    world: &'a mut World,
+}
+
+impl ExportObject for IsochroneCsv<'_> {
+    fn export_to_buffer(&mut self, buffer: &mut Vec<u8>) {
+        let mut stations: Vec<(String, Option<Reach>)> = self
+            .world
+            .query_filtered::<(Entity, &Name), With<Station>>()
+            .iter(self.world)
+            .map(|(entity, name)| (name.to_string(), self.isochrone.reach.get(&entity).copied()))
+            .collect();
+        stations.sort_by_key(|(_, reach)| reach.map_or(i32::MAX, |it| it.travel_time.0));
+        let origin = self
+            .world
+            .get::<Name>(self.isochrone.origin)
+            .map_or(Cow::Borrowed(""), |it| csv_field(it.as_str()));
+        writeln!(buffer, "origin,station,arrival,travel_time_seconds").unwrap();
+        for (name, reach) in stations {
+            write!(buffer, "{},{},", origin, csv_field(&name)).unwrap();
+            match reach {
+                Some(reach) => writeln!(buffer, "{},{}", reach.arrival, reach.travel_time.0),
+                None => writeln!(buffer, ","),
+            }
+            .unwrap();
+        }
+    }
+    fn extension(&self) -> impl AsRef<str> {
+        ".csv"
+    }
+    fn filename(&self) -> impl AsRef<str> {
+        "isochrone"
+    }
+}
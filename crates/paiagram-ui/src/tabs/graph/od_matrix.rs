@@ -0,0 +1,107 @@
+use crate::SelectedItems;
+use crate::tabs::diagram::select_scenario;
+use crate::widgets::{DurationDragValue, ServiceDayFilter, TimeDragValue};
+use bevy::prelude::*;
+use egui::Ui;
+use egui_i18n::tr;
+use paiagram_core::{
+    export::ExportObject,
+    trip::calendar::ServiceDate,
+    units::time::{Duration, TimetableTime},
+};
+use paiagram_raptor::matrix::{OdMatrix, OdMatrixRequest, OdMatrixTask, compute_od_matrix};
+
+/// The departure times of the travel time matrix between the selected stations
+#[derive(Clone)]
+pub(super) struct OdMatrixParams {
+    earliest: TimetableTime,
+    latest: TimetableTime,
+    step: Duration,
+    max_transfers: usize,
+    service_day: Option<ServiceDate>,
+    scenario: Option<Entity>,
+}
+
+impl Default for OdMatrixParams {
+    fn default() -> Self {
+        Self {
+            earliest: TimetableTime::from_hms(7, 0, 0),
+            latest: TimetableTime::from_hms(9, 0, 0),
+            step: Duration(60),
+            max_transfers: paiagram_raptor::MAX_TRIPS - 1,
+            service_day: None,
+            scenario: None,
+        }
+    }
+}
+
+impl OdMatrixParams {
+    pub fn show(&mut self, world: &mut World, ui: &mut Ui) {
+        ui.strong(tr!("tab-graph-od-matrix"));
+        ui.label(tr!("tab-graph-od-matrix-desc"));
+        let stations: Vec<Entity> = match world.resource::<SelectedItems>() {
+            SelectedItems::Stations(it) => it.iter().map(|it| it.station).collect(),
+            _ => Vec::new(),
+        };
+        ui.horizontal(|ui| {
+            ui.label(tr!("tab-graph-isochrone-window"));
+            ui.add(TimeDragValue(&mut self.earliest));
+            ui.label("–");
+            ui.add(TimeDragValue(&mut self.latest));
+        });
+        ui.horizontal(|ui| {
+            ui.label(tr!("tab-graph-od-matrix-step"));
+            ui.add(DurationDragValue(&mut self.step));
+        });
+        ui.horizontal(|ui| {
+            ui.label(tr!("tab-graph-isochrone-max-transfers"));
+            ui.add(egui::DragValue::new(&mut self.max_transfers).range(0..=20));
+        });
+        ui.add(ServiceDayFilter(&mut self.service_day));
+        world
+            .run_system_cached_with(select_scenario, (ui, &mut self.scenario))
+            .unwrap();
+        ui.label(tr!("tab-graph-od-matrix-stations", { count: stations.len() }));
+        if let Some(task) = world.get_resource::<OdMatrixTask>() {
+            let (finished, total) = task.progress();
+            ui.add(
+                egui::ProgressBar::new(finished as f32 / total.max(1) as f32)
+                    .text(format!("{finished}/{total}")),
+            );
+            ui.ctx()
+                .request_repaint_after(std::time::Duration::from_millis(100));
+        } else if ui
+            .add_enabled(
+                stations.len() >= 2,
+                egui::Button::new(tr!("tab-graph-isochrone-compute")),
+            )
+            .clicked()
+        {
+            let request = OdMatrixRequest {
+                stations,
+                earliest: self.earliest,
+                latest: self.latest.max(self.earliest),
+                step: self.step,
+                max_trips: self.max_transfers + 1,
+                service_day: self.service_day,
+                scenario: self.scenario,
+            };
+            world
+                .run_system_cached_with(compute_od_matrix, request)
+                .unwrap();
+        }
+        if let Some(mut matrix) = world.get_resource_mut::<OdMatrix>() {
+            ui.label(tr!(
+                "tab-graph-od-matrix-ready",
+                {
+                    count: matrix.names.len(),
+                    earliest: matrix.request.earliest.to_string(),
+                    latest: matrix.request.latest.to_string()
+                }
+            ));
+            if ui.button(tr!("export")).clicked() {
+                matrix.export_to_file();
+            }
+        }
+    }
+}
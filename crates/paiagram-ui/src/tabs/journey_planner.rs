@@ -0,0 +1,211 @@
+use super::Tab;
+use crate::tabs::diagram::select_scenario;
+use crate::widgets::{ServiceDayFilter, TimeDragValue};
+use crate::{OpenOrFocus, tabs::trip::TripTab};
+use bevy::ecs::entity::MapEntities;
+use bevy::prelude::*;
+use egui::{ScrollArea, Ui};
+use paiagram_core::{station::Station, trip::calendar::ServiceDate, units::time::TimetableTime};
+use paiagram_raptor::{Journey, Leg};
+use serde::{Deserialize, Serialize};
+
+/// Lists the best journeys between two stations over a window of departure times.
+#[derive(Clone, Serialize, Deserialize, MapEntities)]
+pub(crate) struct JourneyPlannerTab {
+    #[entities]
+    origin: Option<Entity>,
+    #[entities]
+    destination: Option<Entity>,
+    earliest: TimetableTime,
+    latest: TimetableTime,
+    max_transfers: usize,
+    service_day: Option<ServiceDate>,
+    #[serde(default)]
+    #[entities]
+    scenario: Option<Entity>,
+    #[serde(skip)]
+    journeys: Vec<Journey>,
+}
+
+impl Default for JourneyPlannerTab {
+    fn default() -> Self {
+        Self {
+            origin: None,
+            destination: None,
+            earliest: TimetableTime::from_hms(6, 0, 0),
+            latest: TimetableTime::from_hms(10, 0, 0),
+            max_transfers: paiagram_raptor::MAX_TRIPS - 1,
+            service_day: None,
+            scenario: None,
+            journeys: Vec::new(),
+        }
+    }
+}
+
+impl JourneyPlannerTab {
+    pub(crate) fn new(service_day: Option<ServiceDate>, scenario: Option<Entity>) -> Self {
+        Self {
+            service_day,
+            scenario,
+            ..Default::default()
+        }
+    }
+}
+
+impl PartialEq for JourneyPlannerTab {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Tab for JourneyPlannerTab {
+    const NAME: &'static str = "Journey Planner";
+    fn main_display(&mut self, world: &mut World, ui: &mut Ui) {
+        ui.horizontal_wrapped(|ui| {
+            ui.label("From");
+            world
+                .run_system_cached_with(select_station, (ui, &mut self.origin))
+                .unwrap();
+            ui.label("To");
+            world
+                .run_system_cached_with(select_station, (ui, &mut self.destination))
+                .unwrap();
+            if ui.button("⇄").on_hover_text("Swap").clicked() {
+                std::mem::swap(&mut self.origin, &mut self.destination);
+            }
+        });
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Departing between");
+            ui.add(TimeDragValue(&mut self.earliest));
+            ui.label("and");
+            ui.add(TimeDragValue(&mut self.latest));
+        });
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Max transfers");
+            ui.add(egui::DragValue::new(&mut self.max_transfers).range(0..=20));
+            ui.add(ServiceDayFilter(&mut self.service_day));
+            world
+                .run_system_cached_with(select_scenario, (ui, &mut self.scenario))
+                .unwrap();
+            if ui
+                .add_enabled(
+                    self.origin.is_some() && self.destination.is_some(),
+                    egui::Button::new("Find"),
+                )
+                .clicked()
+                && let Some(origin) = self.origin
+                && let Some(destination) = self.destination
+            {
+                let timetable = world
+                    .run_system_cached_with(
+                        paiagram_raptor::timetable,
+                        (self.service_day, self.scenario),
+                    )
+                    .unwrap();
+                self.journeys = timetable.range_journeys(
+                    origin,
+                    destination,
+                    self.earliest,
+                    self.latest.max(self.earliest),
+                    self.max_transfers + 1,
+                );
+            }
+        });
+        ui.separator();
+        world
+            .run_system_cached_with(list_journeys, (ui, self.journeys.as_slice()))
+            .unwrap();
+    }
+    fn display_display(&mut self, _world: &mut World, ui: &mut Ui) {
+        ui.label(
+            "Journeys are listed if no other journey departs later, arrives earlier, \
+            and takes fewer transfers.",
+        );
+    }
+}
+
+// TODO: support selecting stations from the diagram or the graph
+pub(crate) fn select_station(
+    (InMut(ui), InMut(selected)): (InMut<Ui>, InMut<Option<Entity>>),
+    stations: Query<(Entity, &Name), With<Station>>,
+) {
+    let res = ui.button(
+        selected
+            .and_then(|it| stations.get(it).ok())
+            .map_or("None", |(_, name)| name.as_str()),
+    );
+    egui::Popup::menu(&res).show(|ui| {
+        ScrollArea::vertical().show(ui, |ui| {
+            for (entity, name) in stations.iter() {
+                if ui.button(name.as_str()).clicked() {
+                    *selected = Some(entity)
+                }
+            }
+        })
+    });
+}
+
+fn list_journeys(
+    (InMut(ui), InRef(journeys)): (InMut<Ui>, InRef<[Journey]>),
+    name_q: Query<&Name>,
+    mut commands: Commands,
+) {
+    if journeys.is_empty() {
+        ui.label("No journeys found");
+        return;
+    }
+    let name = |entity: Entity| name_q.get(entity).map_or("<??>", Name::as_str);
+    ScrollArea::vertical().show(ui, |ui| {
+        for (idx, journey) in journeys.iter().enumerate() {
+            let transfers = journey.trips().saturating_sub(1);
+            egui::CollapsingHeader::new(format!(
+                "{} – {} ({}, {} transfers)",
+                journey.departure,
+                journey.arrival,
+                (journey.arrival - journey.departure).to_string_no_arrow(),
+                transfers
+            ))
+            .id_salt(("journey", idx))
+            .show(ui, |ui| {
+                egui::Grid::new(("journey legs", idx))
+                    .num_columns(4)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for leg in journey.legs.iter().copied() {
+                            let (from, to, departure, arrival) = match leg {
+                                Leg::Ride {
+                                    from,
+                                    to,
+                                    departure,
+                                    arrival,
+                                    ..
+                                }
+                                | Leg::Walk {
+                                    from,
+                                    to,
+                                    departure,
+                                    arrival,
+                                } => (from, to, departure, arrival),
+                            };
+                            ui.label(format!("{departure} {}", name(from)));
+                            ui.label(format!("{arrival} {}", name(to)));
+                            match leg {
+                                Leg::Ride { trip, .. } => {
+                                    if ui.link(name(trip)).clicked() {
+                                        commands.write_message(OpenOrFocus(crate::MainTab::Trip(
+                                            TripTab::new(trip),
+                                        )));
+                                    }
+                                }
+                                Leg::Walk { .. } => {
+                                    ui.label("Walk");
+                                }
+                            }
+                            ui.label((arrival - departure).to_string_no_arrow());
+                            ui.end_row();
+                        }
+                    });
+            });
+        }
+    });
+}
@@ -0,0 +1,81 @@
+use super::Tab;
+use bevy::ecs::entity::MapEntities;
+use bevy::prelude::*;
+use egui::{ScrollArea, Ui};
+use paiagram_core::plugin::{LoadPlugin, WasmPlugins, run_plugin};
+use serde::{Deserialize, Serialize};
+
+/// Lists the loaded WebAssembly plugins and runs them.
+#[derive(Default, PartialEq, Clone, Serialize, Deserialize, MapEntities)]
+pub(crate) struct PluginsTab;
+
+impl Tab for PluginsTab {
+    const NAME: &'static str = "Plugins";
+    fn main_display(&mut self, world: &mut World, ui: &mut Ui) {
+        if ui.button("Load plugin...").clicked() {
+            world.commands().trigger(paiagram_rw::read::ReadFile {
+                title: "Load Plugin".to_string(),
+                extensions: vec![("WebAssembly".to_string(), vec!["wasm".to_string()])],
+                callback: |c, file_name, bytes| {
+                    // the plugin is named after its file
+                    let name = file_name
+                        .strip_suffix(".wasm")
+                        .unwrap_or(&file_name)
+                        .to_string();
+                    c.trigger(LoadPlugin { name, bytes });
+                },
+            });
+        }
+        ui.separator();
+        let mut to_run = None;
+        let mut to_remove = None;
+        let mut plugins = world.resource_mut::<WasmPlugins>();
+        if plugins.items.is_empty() {
+            ui.label("No plugins loaded");
+        }
+        ScrollArea::vertical().show(ui, |ui| {
+            for (idx, plugin) in plugins.items.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut plugin.name);
+                    if ui
+                        .add_enabled(!plugin.running, egui::Button::new("Run"))
+                        .clicked()
+                    {
+                        to_run = Some(idx);
+                    }
+                    if plugin.running {
+                        ui.spinner();
+                    }
+                    if ui
+                        .add_enabled(!plugin.running, egui::Button::new("Remove"))
+                        .clicked()
+                    {
+                        to_remove = Some(idx);
+                    }
+                });
+                egui::CollapsingHeader::new(format!("Log ({})", plugin.log.len()))
+                    .id_salt(("plugin log", idx))
+                    .show(ui, |ui| {
+                        for line in &plugin.log {
+                            ui.monospace(line);
+                        }
+                    });
+                ui.separator();
+            }
+        });
+        if let Some(idx) = to_remove {
+            plugins.items.remove(idx);
+        }
+        if let Some(idx) = to_run
+            && let Err(e) = run_plugin(world, idx)
+        {
+            warn!("Plugin failed: {e:?}");
+        }
+    }
+    fn display_display(&mut self, _world: &mut World, ui: &mut Ui) {
+        ui.label(
+            "Plugins read the stations and trips, and can add new ones. \
+            Changes are only applied if the plugin finishes without errors.",
+        );
+    }
+}
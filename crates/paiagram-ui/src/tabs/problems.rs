@@ -0,0 +1,112 @@
+use crate::{OpenOrFocus, tabs::trip::TripTab};
+
+use super::Tab;
+use bevy::ecs::entity::MapEntities;
+use bevy::prelude::*;
+use egui::{ScrollArea, Ui};
+use paiagram_core::{
+    entry::EntryQuery,
+    problems::{ProblemKind, Problems},
+    station::ParentStationOrStation,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Default, PartialEq, Clone, Serialize, Deserialize, MapEntities)]
+pub(crate) struct ProblemsTab {
+    /// Only list problems of this kind
+    #[serde(skip)]
+    kind_filter: Option<ProblemKind>,
+}
+
+impl Tab for ProblemsTab {
+    const NAME: &'static str = "Problems";
+    fn main_display(&mut self, world: &mut World, ui: &mut Ui) {
+        world
+            .run_system_cached_with(list_problems, (ui, self))
+            .unwrap();
+    }
+}
+
+fn list_problems(
+    (InMut(ui), InMut(tab)): (InMut<Ui>, InMut<ProblemsTab>),
+    problems: Res<Problems>,
+    entry_q: Query<EntryQuery>,
+    parent_station_or_station: Query<ParentStationOrStation>,
+    name_q: Query<&Name>,
+    mut commands: Commands,
+) {
+    let kinds: Vec<ProblemKind> = {
+        let mut kinds: Vec<_> = problems.iter().map(|it| it.kind).collect();
+        kinds.dedup();
+        kinds
+    };
+    ui.horizontal(|ui| {
+        ui.label(format!("{} problems", problems.len()));
+        egui::ComboBox::from_label("Kind")
+            .selected_text(
+                tab.kind_filter
+                    .map_or("All".to_string(), |it| it.to_string()),
+            )
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut tab.kind_filter, None, "All");
+                for kind in kinds {
+                    ui.selectable_value(&mut tab.kind_filter, Some(kind), kind.to_string());
+                }
+            });
+    });
+    ui.separator();
+
+    // segments may point at the station itself instead of an entry
+    let station_name = |entry: Entity| -> String {
+        let station = entry_q
+            .get(entry)
+            .ok()
+            .and_then(|it| parent_station_or_station.get(it.stop()).ok())
+            .map_or(entry, |it| it.parent());
+        name_q
+            .get(station)
+            .map_or_else(|_| "<??>".to_string(), Name::to_string)
+    };
+
+    ScrollArea::vertical().show(ui, |ui| {
+        egui::Grid::new("problems grid")
+            .num_columns(4)
+            .striped(true)
+            .show(ui, |ui| {
+                ui.strong("Kind");
+                ui.strong("Time");
+                ui.strong("Location");
+                ui.strong("Trips");
+                ui.end_row();
+                for problem in problems
+                    .iter()
+                    .filter(|it| tab.kind_filter.is_none_or(|kind| kind == it.kind))
+                {
+                    ui.label(problem.kind.to_string());
+                    ui.label(problem.time.to_string());
+                    if let Some(segment) = problem.segments.first() {
+                        let from = station_name(segment.from);
+                        let to = station_name(segment.to);
+                        if segment.from == segment.to {
+                            ui.label(from);
+                        } else {
+                            ui.label(format!("{from} – {to}"));
+                        }
+                    } else {
+                        ui.label("");
+                    }
+                    ui.horizontal(|ui| {
+                        for segment in problem.segments.iter() {
+                            let trip_name = name_q.get(segment.trip).map_or("<??>", Name::as_str);
+                            if ui.link(trip_name).clicked() {
+                                commands.write_message(OpenOrFocus(crate::MainTab::Trip(
+                                    TripTab::new(segment.trip),
+                                )));
+                            }
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+    });
+}
@@ -11,20 +11,33 @@ use paiagram_core::{
         SortRouteByDirectionTrips,
     },
     station::{ParentStationOrStation, Station},
-    trip::{TripQuery, TripQueryItem},
+    trip::{
+        TripQuery, TripQueryItem, TripScenario,
+        calendar::{ServiceCalendar, ServiceDate},
+    },
 };
 
-use crate::widgets::TimeDragValueOud;
+use crate::tabs::diagram::select_scenario;
+use crate::widgets::{ServiceDayFilter, TimeDragValueOud};
 
 #[derive(Serialize, Deserialize, Clone, MapEntities)]
 pub struct RouteTimetableTab {
     #[entities]
     route_entity: Entity,
+    #[serde(default)]
+    service_day: Option<ServiceDate>,
+    #[serde(default)]
+    #[entities]
+    scenario: Option<Entity>,
 }
 
 impl RouteTimetableTab {
     pub fn new(e: Entity) -> Self {
-        Self { route_entity: e }
+        Self {
+            route_entity: e,
+            service_day: None,
+            scenario: None,
+        }
     }
 }
 
@@ -39,6 +52,12 @@ impl super::Tab for RouteTimetableTab {
     fn scroll_bars(&self) -> [bool; 2] {
         [false; 2]
     }
+    fn display_display(&mut self, world: &mut World, ui: &mut Ui) {
+        ui.add(ServiceDayFilter(&mut self.service_day));
+        world
+            .run_system_cached_with(select_scenario, (ui, &mut self.scenario))
+            .unwrap();
+    }
     fn edit_display(&mut self, world: &mut World, ui: &mut Ui) {
         if ui.button("Sort entries").clicked() {
             world.trigger(SortRouteByDirectionTrips {
@@ -51,7 +70,21 @@ impl super::Tab for RouteTimetableTab {
         let by_direction = world
             .get::<RouteByDirectionTrips>(self.route_entity)
             .expect("Route should have RouteByDirectionTrips");
-        let downward_entities = by_direction.downward.clone();
+        let mut downward_entities = by_direction.downward.clone();
+        if let Some(date) = self.service_day {
+            downward_entities.retain(|it| {
+                world
+                    .get::<ServiceCalendar>(*it)
+                    .is_none_or(|calendar| calendar.runs_on(date))
+            });
+        }
+        if let Some(scenario) = self.scenario {
+            downward_entities.retain(|it| {
+                world
+                    .get::<TripScenario>(*it)
+                    .is_some_and(|it| it.0 == scenario)
+            });
+        }
         // use a table
         let table = egui_table::Table::new()
             .id_salt(self.route_entity)
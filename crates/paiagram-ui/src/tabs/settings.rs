@@ -1,9 +1,13 @@
 use paiagram_core::{
+    export::gtfs::DEFAULT_AGENCY_URL,
     i18n::Language,
     settings::{AntialiasingMode, LevelOfDetailMode, ProjectSettings, UserPreferences},
+    units::{distance::Distance, speed::Velocity},
+    vehicle::scheduling::ScheduleVehicles,
 };
 
 use super::Tab;
+use crate::widgets::DurationDragValue;
 use bevy::ecs::entity::MapEntities;
 use bevy::prelude::*;
 use egui::Ui;
@@ -29,6 +33,7 @@ fn show_settings(
     InMut(ui): InMut<Ui>,
     mut preferences: ResMut<UserPreferences>,
     mut settings: ResMut<ProjectSettings>,
+    mut commands: Commands,
 ) {
     ui.heading(tr!("settings-preferences"));
     egui::Grid::new("settings grid 1").show(ui, |ui| {
@@ -97,5 +102,119 @@ fn show_settings(
         ui.end_row();
     });
     ui.heading(tr!("settings-project-settings"));
-    ui.text_edit_multiline(&mut settings.remarks);
+    // other systems recalculate when the project settings change, so only mark the settings as
+    // changed when edited
+    let mut changed = false;
+    let project = settings.bypass_change_detection();
+    egui::Grid::new("settings grid 2").show(ui, |ui| {
+        ui.label("Minimum Headway");
+        changed |= ui
+            .add(DurationDragValue(&mut project.minimum_headway))
+            .changed();
+        ui.end_row();
+
+        ui.label("Minimum Turnaround");
+        changed |= ui
+            .add(DurationDragValue(&mut project.minimum_turnaround))
+            .changed();
+        ui.end_row();
+
+        ui.label("Deadhead Speed");
+        ui.horizontal(|ui| {
+            let mut allowed = project.deadhead_speed.is_some();
+            if ui.checkbox(&mut allowed, "").changed() {
+                project.deadhead_speed = allowed.then(|| Velocity::from_kmh(60.0));
+                changed = true;
+            }
+            if let Some(speed) = project.deadhead_speed {
+                let mut kmh = speed.to_kmh();
+                if ui
+                    .add(
+                        egui::DragValue::new(&mut kmh)
+                            .range(1.0..=300.0)
+                            .suffix(" km/h"),
+                    )
+                    .changed()
+                {
+                    project.deadhead_speed = Some(Velocity::from_kmh(kmh));
+                    changed = true;
+                }
+            }
+        });
+        ui.end_row();
+
+        ui.label("Minimum Transfer Time");
+        changed |= ui
+            .add(DurationDragValue(&mut project.minimum_transfer_time))
+            .changed();
+        ui.end_row();
+
+        ui.label("Walking Distance");
+        ui.horizontal(|ui| {
+            let mut allowed = project.walking_distance.is_some();
+            if ui.checkbox(&mut allowed, "").changed() {
+                project.walking_distance = allowed.then(|| Distance::from_m(300));
+                changed = true;
+            }
+            if let Some(distance) = project.walking_distance {
+                let mut meters = distance.0;
+                if ui
+                    .add(
+                        egui::DragValue::new(&mut meters)
+                            .range(1..=5000)
+                            .suffix(" m"),
+                    )
+                    .changed()
+                {
+                    project.walking_distance = Some(Distance::from_m(meters));
+                    changed = true;
+                }
+            }
+        });
+        ui.end_row();
+
+        ui.label("Walking Speed");
+        let mut kmh = project.walking_speed.to_kmh();
+        if ui
+            .add(
+                egui::DragValue::new(&mut kmh)
+                    .range(1.0..=20.0)
+                    .speed(0.1)
+                    .suffix(" km/h"),
+            )
+            .changed()
+        {
+            project.walking_speed = Velocity::from_kmh(kmh);
+            changed = true;
+        }
+        ui.end_row();
+
+        ui.label("Time Zone");
+        changed |= ui
+            .add(egui::TextEdit::singleline(&mut project.time_zone).hint_text("Etc/UTC"))
+            .on_hover_text("IANA time zone written to exported GTFS feeds")
+            .changed();
+        ui.end_row();
+
+        ui.label("Agency URL");
+        changed |= ui
+            .add(egui::TextEdit::singleline(&mut project.agency_url).hint_text(DEFAULT_AGENCY_URL))
+            .on_hover_text("Website of the operator written to exported GTFS feeds")
+            .changed();
+        ui.end_row();
+    });
+    if ui
+        .button("Schedule Vehicles")
+        .on_hover_text(
+            "Assign trips to as few vehicles as possible. Vehicles created by previous runs are \
+             replaced, while other vehicles are kept.",
+        )
+        .clicked()
+    {
+        commands.trigger(ScheduleVehicles);
+    }
+    changed |= ui.text_edit_multiline(&mut project.remarks).changed();
+    if changed {
+        settings.set_changed();
+    }
 }
@@ -1,7 +1,11 @@
 use crate::{
     GlobalTimer,
-    widgets::indicators::{
-        display_time_indicator_indicator_horizontal, display_time_indicator_indicator_vertical,
+    tabs::diagram::select_scenario,
+    widgets::{
+        ServiceDayFilter,
+        indicators::{
+            display_time_indicator_indicator_horizontal, display_time_indicator_indicator_vertical,
+        },
     },
 };
 use bevy::prelude::*;
@@ -10,9 +14,9 @@ use either::Either;
 use bevy::ecs::entity::MapEntities;
 use paiagram_core::{
     class::ClassQuery,
-    entry::EntryQuery,
+    entry::{EntryEstimate, EntryQuery},
     station::{ParentStationOrStation, PlatformEntries, StationQuery},
-    trip::TripQuery,
+    trip::{TripQuery, calendar::ServiceDate},
     units::time::TimetableTime,
 };
 use serde::{Deserialize, Serialize};
@@ -22,6 +26,11 @@ pub struct StationTab {
     #[entities]
     station_entity: Entity,
     include_nonstop: bool,
+    #[serde(default)]
+    service_day: Option<ServiceDate>,
+    #[serde(default)]
+    #[entities]
+    scenario: Option<Entity>,
 }
 
 impl StationTab {
@@ -29,6 +38,8 @@ impl StationTab {
         Self {
             station_entity,
             include_nonstop: false,
+            service_day: None,
+            scenario: None,
         }
     }
 }
@@ -38,7 +49,13 @@ impl super::Tab for StationTab {
     fn main_display(&mut self, world: &mut World, ui: &mut egui::Ui) {
         let station_name = world.get::<Name>(self.station_entity).unwrap().as_str();
         ui.heading(station_name);
-        ui.checkbox(&mut self.include_nonstop, "Include non-stop");
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.include_nonstop, "Include non-stop");
+            ui.add(ServiceDayFilter(&mut self.service_day));
+            world
+                .run_system_cached_with(select_scenario, (ui, &mut self.scenario))
+                .unwrap();
+        });
         egui::ScrollArea::both().show(ui, |ui| {
             world
                 .run_system_cached_with(display_time_grid, (ui, self))
@@ -61,6 +78,7 @@ fn display_time_grid(
     entry_q: Query<EntryQuery>,
     class_q: Query<ClassQuery>,
     trip_q: Query<TripQuery>,
+    estimate_q: Query<&EntryEstimate>,
     parent_station_or_station: Query<ParentStationOrStation>,
     global_timer: Res<GlobalTimer>,
 ) {
@@ -81,6 +99,9 @@ fn display_time_grid(
             continue;
         };
         let trip = trip_q.get(e.parent_schedule.parent()).unwrap();
+        if !trip.runs_on(tab.service_day) || !trip.is_in_scenario(tab.scenario) {
+            continue;
+        }
         let last_entry_entity = *trip.schedule.last().unwrap();
         let last_stop_entity = entry_q.get(last_entry_entity).unwrap().stop();
         let last_station_entity = parent_station_or_station
@@ -89,12 +110,16 @@ fn display_time_grid(
             .parent();
         let last_station_name = station_q.get(last_station_entity).unwrap().name.as_str();
         let class = class_q.get(trip.class.entity()).unwrap();
-        entry_bucket[estimate.dep.hour() as usize].push(DisplayedEntry {
-            time: estimate.dep,
-            color: class.stroke.color.get(ui.visuals().dark_mode),
-            trip_name: trip.name.as_str(),
-            last_station_abbrev: last_station_name,
-        });
+        // frequency-based trips are listed once per run
+        for offset in trip.run_offsets(&estimate_q) {
+            let time = estimate.dep + offset;
+            entry_bucket[time.hour() as usize].push(DisplayedEntry {
+                time,
+                color: class.stroke.color.get(ui.visuals().dark_mode),
+                trip_name: trip.name.as_str(),
+                last_station_abbrev: last_station_name,
+            });
+        }
     }
     for line in entry_bucket.iter_mut() {
         line.sort_by_key(|it| it.time.minute() * 60 + it.time.second());
@@ -1,12 +1,19 @@
 use paiagram_core::{
     entry::{EntryEstimate, EntryMode, EntryQuery, EntryQueryItem, TravelMode},
-    station::{PlatformQuery, StationQuery},
-    trip::{TripQuery, TripQueryItem},
+    settings::ProjectSettings,
+    station::{ParentStationOrStation, PlatformEntries, PlatformQuery, StationQuery},
+    trip::{
+        TripQuery, TripQueryItem,
+        connection::TripConnection,
+        generator::{GenerateTrips, GeneratedFrom, GeneratedTrips, IsEditedCopy, TripGenerator},
+    },
+    units::time::Duration,
 };
 
 use crate::widgets::timetable_popup::{
     arrival_popup, departure_popup, shift_at_value, shift_for_value,
 };
+use crate::widgets::{DurationDragValue, TimeDragValue};
 
 use super::Tab;
 use bevy::prelude::*;
@@ -32,6 +39,15 @@ impl Tab for TripTab {
     fn main_display(&mut self, world: &mut World, ui: &mut egui::Ui) {
         world.run_system_cached_with(show_trip, (ui, self)).unwrap();
     }
+    fn edit_display(&mut self, world: &mut World, ui: &mut egui::Ui) {
+        world
+            .run_system_cached_with(edit_generator, (ui, self.trip_entity))
+            .unwrap();
+        ui.separator();
+        world
+            .run_system_cached_with(edit_connections, (ui, self.trip_entity))
+            .unwrap();
+    }
 }
 
 impl TripTab {
@@ -51,6 +67,9 @@ fn show_trip(
 ) {
     let trip = trip_q.get(tab.trip_entity).unwrap();
     ui.heading(trip.name.as_str());
+    if let Some(calendar) = trip.calendar {
+        ui.label(calendar.to_string());
+    }
     ui.label(trip.schedule.len().to_string());
     egui::ScrollArea::vertical().show(ui, |ui| {
         egui::Grid::new(ui.id().with("lskdfjlsdkjflkdsjf"))
@@ -117,3 +136,213 @@ fn row_ui(
     };
     departure_popup(&dep_res, &it, RectAlign::RIGHT, &mut commands);
 }
+
+fn edit_generator(
+    (InMut(ui), In(trip)): (InMut<Ui>, In<Entity>),
+    mut generator_q: Query<&mut TripGenerator>,
+    generated_q: Query<&GeneratedTrips>,
+    from_q: Query<&GeneratedFrom>,
+    edited_q: Query<(), With<IsEditedCopy>>,
+    name_q: Query<&Name>,
+    settings: Res<ProjectSettings>,
+    mut commands: Commands,
+) {
+    ui.strong(tr!("trip-generator"));
+    if let Ok(template) = from_q.get(trip) {
+        ui.horizontal(|ui| {
+            ui.label(tr!("trip-generator-generated-from"));
+            ui.label(name_q.get(template.0).map_or("<??>", Name::as_str));
+        });
+        if edited_q.contains(trip) {
+            ui.label(tr!("trip-generator-edited"));
+            if ui.button(tr!("trip-generator-discard-edits")).clicked() {
+                commands.entity(trip).remove::<IsEditedCopy>();
+                commands.trigger(GenerateTrips { entity: template.0 });
+            }
+        }
+        return;
+    }
+    let Ok(mut generator) = generator_q.get_mut(trip) else {
+        if ui.button(tr!("trip-generator-new")).clicked() {
+            commands
+                .entity(trip)
+                .insert(TripGenerator::new(settings.repeat_frequency));
+            commands.trigger(GenerateTrips { entity: trip });
+        }
+        return;
+    };
+    let mut edited = generator.clone();
+    egui::Grid::new(ui.id().with("trip generator"))
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label(tr!("trip-generator-headway"));
+            ui.add(DurationDragValue(&mut edited.headway));
+            ui.end_row();
+            ui.label(tr!("trip-generator-window-start"));
+            ui.add(TimeDragValue(&mut edited.window_start));
+            ui.end_row();
+            ui.label(tr!("trip-generator-window-end"));
+            ui.add(TimeDragValue(&mut edited.window_end));
+            ui.end_row();
+            ui.label(tr!("trip-generator-name-pattern"));
+            ui.text_edit_singleline(&mut edited.name_pattern);
+            ui.end_row();
+            ui.label(tr!("trip-generator-first-number"));
+            ui.add(egui::DragValue::new(&mut edited.first_number));
+            ui.end_row();
+            ui.label(tr!("trip-generator-number-step"));
+            ui.add(egui::DragValue::new(&mut edited.number_step).range(1..=u32::MAX));
+            ui.end_row();
+        });
+    ui.checkbox(&mut edited.regenerate, tr!("trip-generator-regenerate"));
+    let edited_copies = edited_q
+        .iter_many(generated_q.get(trip).iter().flat_map(|it| it.as_trips()))
+        .count();
+    if edited_copies > 0 {
+        ui.label(tr!("trip-generator-edited-copies", { count: edited_copies }));
+    }
+    // only mark the generator as changed when edited, as changes regenerate the copies
+    generator.set_if_neq(edited);
+    ui.horizontal(|ui| {
+        if ui.button(tr!("trip-generator-generate")).clicked() {
+            commands.trigger(GenerateTrips { entity: trip });
+        }
+        if ui.button(tr!("trip-generator-remove")).clicked() {
+            for copy in generated_q.get(trip).iter().flat_map(|it| it.as_trips()) {
+                commands.entity(*copy).despawn();
+            }
+            commands.entity(trip).remove::<TripGenerator>();
+        }
+    });
+}
+
+/// The connection being added in the edit panel
+#[derive(Default)]
+struct ConnectionDraft {
+    feeder: Option<Entity>,
+    station: Option<Entity>,
+    connecting: Option<Entity>,
+    transfer_time: Duration,
+}
+
+fn edit_connections(
+    (InMut(ui), In(trip)): (InMut<Ui>, In<Entity>),
+    mut connection_q: Query<(Entity, &mut TripConnection)>,
+    trip_q: Query<TripQuery>,
+    entry_q: Query<EntryQuery>,
+    station_q: Query<StationQuery>,
+    platform_entries_q: Query<&PlatformEntries>,
+    parent_station_or_station: Query<ParentStationOrStation>,
+    name_q: Query<&Name>,
+    mut draft: Local<ConnectionDraft>,
+    mut commands: Commands,
+) {
+    let name = |entity: Entity| name_q.get(entity).map_or("<??>", Name::as_str);
+    ui.strong(tr!("trip-connections"));
+    egui::Grid::new(ui.id().with("trip connections"))
+        .num_columns(4)
+        .show(ui, |ui| {
+            for (entity, mut connection) in connection_q.iter_mut() {
+                let other = if connection.feeder == trip {
+                    tr!("trip-connection-to", {trip: name(connection.connecting)})
+                } else if connection.connecting == trip {
+                    tr!("trip-connection-from", {trip: name(connection.feeder)})
+                } else {
+                    continue;
+                };
+                ui.label(other);
+                ui.label(name(connection.station));
+                let mut transfer_time = connection.transfer_time;
+                ui.add(DurationDragValue(&mut transfer_time));
+                if transfer_time != connection.transfer_time {
+                    connection.transfer_time = transfer_time;
+                }
+                if ui.button(tr!("trip-connection-remove")).clicked() {
+                    commands.entity(entity).despawn();
+                }
+                ui.end_row();
+            }
+        });
+
+    if draft.feeder != Some(trip) {
+        *draft = ConnectionDraft {
+            feeder: Some(trip),
+            ..default()
+        };
+    }
+    let Ok(feeder) = trip_q.get(trip) else {
+        return;
+    };
+    // stations this trip stops at, in order
+    let mut stations: Vec<Entity> = Vec::new();
+    for entry in entry_q.iter_many(feeder.schedule.iter()) {
+        if let Ok(station) = parent_station_or_station.get(entry.stop())
+            && !stations.contains(&station.parent())
+        {
+            stations.push(station.parent());
+        }
+    }
+    // trips stopping at the selected station
+    let mut candidates: Vec<Entity> = draft
+        .station
+        .and_then(|it| station_q.get(it).ok())
+        .into_iter()
+        .flat_map(|station| {
+            entry_q
+                .iter_many(station.passing_entries(&platform_entries_q))
+                .map(|it| it.parent_schedule.parent())
+                .collect::<Vec<_>>()
+        })
+        .filter(|it| *it != trip)
+        .collect();
+    candidates.sort_unstable();
+    candidates.dedup();
+    candidates.sort_by_key(|it| name(*it));
+
+    ui.label(tr!("trip-connection-new"));
+    egui::Grid::new(ui.id().with("new trip connection"))
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label(tr!("trip-connection-station"));
+            let previous_station = draft.station;
+            egui::ComboBox::new("connection station", "")
+                .selected_text(draft.station.map_or("", name))
+                .show_ui(ui, |ui| {
+                    for station in stations.iter().copied() {
+                        ui.selectable_value(&mut draft.station, Some(station), name(station));
+                    }
+                });
+            if draft.station != previous_station {
+                draft.connecting = None;
+            }
+            ui.end_row();
+            ui.label(tr!("trip-connection-connecting"));
+            egui::ComboBox::new("connecting trip", "")
+                .selected_text(draft.connecting.map_or("", name))
+                .show_ui(ui, |ui| {
+                    for candidate in candidates.iter().copied() {
+                        ui.selectable_value(
+                            &mut draft.connecting,
+                            Some(candidate),
+                            name(candidate),
+                        );
+                    }
+                });
+            ui.end_row();
+            ui.label(tr!("trip-connection-transfer-time"));
+            ui.add(DurationDragValue(&mut draft.transfer_time));
+            ui.end_row();
+        });
+    let (Some(station), Some(connecting)) = (draft.station, draft.connecting) else {
+        return;
+    };
+    if ui.button(tr!("trip-connection-add")).clicked() {
+        commands.spawn(TripConnection {
+            feeder: trip,
+            connecting,
+            station,
+            transfer_time: draft.transfer_time,
+        });
+        draft.connecting = None;
+    }
+}
@@ -0,0 +1,392 @@
+use super::{Navigatable, Tab};
+use crate::{OpenOrFocus, tabs::trip::TripTab};
+use bevy::ecs::entity::MapEntities;
+use bevy::prelude::*;
+use egui::{Align2, Color32, FontId, Margin, Painter, Pos2, Rect, Sense, Stroke, Vec2, pos2};
+use paiagram_core::{
+    entry::EntryEstimate,
+    trip::{TripQuery, class::DisplayedStroke},
+    vehicle::{Vehicle, scheduling::ScheduleVehicles},
+};
+use serde::{Deserialize, Serialize};
+
+/// Height of each vehicle row
+const ROW_HEIGHT: f32 = 28.0;
+/// Width of the vehicle names on the left
+const NAME_WIDTH: f32 = 120.0;
+/// Height of the time labels on the top
+const HEADER_HEIGHT: f32 = 20.0;
+
+#[derive(Default, Clone, Serialize, Deserialize, MapEntities)]
+pub(crate) struct VehiclesTab {
+    navi: VehiclesTabNavigation,
+    #[serde(skip)]
+    rows: Vec<VehicleRow>,
+    #[serde(skip)]
+    dragging: Option<DraggedTrip>,
+}
+
+impl PartialEq for VehiclesTab {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct VehiclesTabNavigation {
+    /// Offset in seconds
+    x_offset: f64,
+    y_offset: f64,
+    zoom: Vec2,
+    visible_rect: Rect,
+}
+
+impl Default for VehiclesTabNavigation {
+    fn default() -> Self {
+        Self {
+            x_offset: 0.0,
+            y_offset: -HEADER_HEIGHT as f64,
+            // 2 minutes per screen unit
+            zoom: Vec2::new(1.0 / 120.0, 1.0),
+            visible_rect: Rect::ZERO,
+        }
+    }
+}
+
+impl Navigatable for VehiclesTabNavigation {
+    type XOffset = f64;
+    type YOffset = f64;
+    fn allow_axis_zoom(&self) -> bool {
+        true
+    }
+    fn zoom_x(&self) -> f32 {
+        self.zoom.x
+    }
+    fn zoom_y(&self) -> f32 {
+        self.zoom.y
+    }
+    fn offset_x(&self) -> f64 {
+        self.x_offset
+    }
+    fn offset_y(&self) -> f64 {
+        self.y_offset
+    }
+    fn set_offset(&mut self, offset_x: f64, offset_y: f64) {
+        self.x_offset = offset_x;
+        self.y_offset = offset_y;
+    }
+    fn visible_rect(&self) -> Rect {
+        self.visible_rect
+    }
+    fn set_zoom(&mut self, zoom_x: f32, zoom_y: f32) {
+        self.zoom.x = zoom_x;
+        self.zoom.y = zoom_y;
+    }
+    fn clamp_zoom(&self, zoom_x: f32, _zoom_y: f32) -> (f32, f32) {
+        // rows always keep the same height
+        (zoom_x.clamp(1.0 / 3600.0, 1.0), 1.0)
+    }
+}
+
+#[derive(Clone)]
+struct VehicleRow {
+    vehicle: Entity,
+    name: String,
+    trips: Vec<Entity>,
+    bars: Vec<TripBar>,
+}
+
+#[derive(Clone)]
+struct TripBar {
+    trip: Entity,
+    name: String,
+    /// Arrival at the first stop, in seconds
+    start: f64,
+    /// Departure from the last stop, in seconds
+    end: f64,
+    color: Color32,
+}
+
+/// The rows are rebuilt every frame, so the dragged trip is kept by its entities
+#[derive(Clone, Copy)]
+struct DraggedTrip {
+    trip: Entity,
+    from: Entity,
+}
+
+impl DraggedTrip {
+    /// The bar of the dragged trip, if the trip is still on its vehicle
+    fn bar<'a>(&self, rows: &'a [VehicleRow]) -> Option<&'a TripBar> {
+        rows.iter()
+            .find(|it| it.vehicle == self.from)?
+            .bars
+            .iter()
+            .find(|it| it.trip == self.trip)
+    }
+}
+
+impl Tab for VehiclesTab {
+    const NAME: &'static str = "Vehicles";
+    fn main_display(&mut self, world: &mut World, ui: &mut egui::Ui) {
+        let is_dark = ui.visuals().dark_mode;
+        world
+            .run_system_cached_with(collect_rows, (&mut self.rows, is_dark))
+            .unwrap();
+        ui.horizontal(|ui| {
+            ui.label(format!("{} vehicles", self.rows.len()));
+            if ui.button("New Vehicle").clicked() {
+                world.spawn((
+                    Name::new(format!("Vehicle {}", self.rows.len() + 1)),
+                    Vehicle::default(),
+                ));
+            }
+            if ui.button("Schedule Vehicles").clicked() {
+                world.trigger(ScheduleVehicles);
+            }
+        });
+        egui::Frame::canvas(ui.style())
+            .inner_margin(Margin::ZERO)
+            .outer_margin(Margin::ZERO)
+            .stroke(Stroke::NONE)
+            .show(ui, |ui| main_display(self, world, ui));
+    }
+    fn scroll_bars(&self) -> [bool; 2] {
+        [false; 2]
+    }
+}
+
+fn collect_rows(
+    (InMut(rows), In(is_dark)): (InMut<Vec<VehicleRow>>, In<bool>),
+    vehicle_q: Query<(Entity, &Name, &Vehicle)>,
+    trip_q: Query<TripQuery>,
+    estimate_q: Query<&EntryEstimate>,
+    stroke_q: Query<&DisplayedStroke>,
+) {
+    rows.clear();
+    for (vehicle, name, Vehicle { trips }) in &vehicle_q {
+        let bars = trip_q
+            .iter_many(trips.iter())
+            .filter_map(|trip| {
+                let start = estimate_q.get(*trip.schedule.first()?).ok()?.arr;
+                let end = estimate_q.get(*trip.schedule.last()?).ok()?.dep;
+                let color = stroke_q
+                    .get(trip.class.0)
+                    .map_or(DisplayedStroke::neutral(is_dark).color, |it| {
+                        it.color.get(is_dark)
+                    });
+                Some(TripBar {
+                    trip: trip.entity,
+                    name: trip.name.to_string(),
+                    start: start.0 as f64,
+                    end: end.0 as f64,
+                    color,
+                })
+            })
+            .collect();
+        rows.push(VehicleRow {
+            vehicle,
+            name: name.to_string(),
+            trips: trips.clone(),
+            bars,
+        });
+    }
+    rows.sort_unstable_by_key(|it| it.vehicle);
+}
+
+fn bar_rect(navi: &VehiclesTabNavigation, row: usize, bar: &TripBar) -> Rect {
+    let top = row as f64 * ROW_HEIGHT as f64;
+    let min = navi.xy_to_screen_pos(bar.start, top);
+    let max = navi.xy_to_screen_pos(bar.end, top + ROW_HEIGHT as f64);
+    Rect::from_min_max(min, max).shrink2(Vec2::new(0.0, 4.0))
+}
+
+fn row_at(navi: &VehiclesTabNavigation, rows: &[VehicleRow], pos: Pos2) -> Option<usize> {
+    let y = navi.screen_y_to_logical_y(pos.y);
+    if y < 0.0 || pos.y < navi.visible_rect.top() + HEADER_HEIGHT {
+        return None;
+    }
+    let row = (y / ROW_HEIGHT as f64) as usize;
+    (row < rows.len()).then_some(row)
+}
+
+fn bar_at(navi: &VehiclesTabNavigation, rows: &[VehicleRow], pos: Pos2) -> Option<(usize, usize)> {
+    if pos.x < navi.visible_rect.left() + NAME_WIDTH {
+        return None;
+    }
+    let row = row_at(navi, rows, pos)?;
+    let bar = rows[row]
+        .bars
+        .iter()
+        .position(|it| bar_rect(navi, row, it).contains(pos))?;
+    Some((row, bar))
+}
+
+fn main_display(tab: &mut VehiclesTab, world: &mut World, ui: &mut egui::Ui) {
+    let (response, painter) =
+        ui.allocate_painter(ui.available_size_before_wrap(), Sense::click_and_drag());
+    tab.navi.visible_rect = response.rect;
+    let pointer = response.interact_pointer_pos().or(response.hover_pos());
+
+    if response.drag_started()
+        && let Some(origin) = ui.input(|input| input.pointer.press_origin())
+        && let Some((row, bar)) = bar_at(&tab.navi, &tab.rows, origin)
+    {
+        tab.dragging = Some(DraggedTrip {
+            trip: tab.rows[row].bars[bar].trip,
+            from: tab.rows[row].vehicle,
+        });
+    }
+    // the trip or its vehicle may have changed during the drag
+    if tab.dragging.is_some_and(|it| it.bar(&tab.rows).is_none()) {
+        tab.dragging = None;
+    }
+    if tab.dragging.is_none() {
+        tab.navi.handle_navigation(ui, &response);
+    }
+
+    draw_time_lines(&painter, &tab.navi, ui.visuals());
+    let target_row = tab
+        .dragging
+        .and_then(|_| pointer.and_then(|pos| row_at(&tab.navi, &tab.rows, pos)));
+    draw_rows(&painter, tab, target_row, ui.visuals());
+
+    if response.clicked()
+        && let Some(pos) = pointer
+        && let Some((row, bar)) = bar_at(&tab.navi, &tab.rows, pos)
+    {
+        let trip = tab.rows[row].bars[bar].trip;
+        world.write_message(OpenOrFocus(crate::MainTab::Trip(TripTab::new(trip))));
+    }
+    if response.drag_stopped()
+        && let Some(dragged) = tab.dragging.take()
+        && let Some(target) = target_row
+        && let Some(start) = dragged.bar(&tab.rows).map(|it| it.start)
+    {
+        let row = &tab.rows[target];
+        // keep the trips of the target vehicle in departure order
+        let index = row
+            .trips
+            .iter()
+            .filter(|it| **it != dragged.trip)
+            .position(|trip| {
+                row.bars
+                    .iter()
+                    .find(|it| it.trip == *trip)
+                    .is_some_and(|it| it.start > start)
+            })
+            .unwrap_or(row.trips.len() - row.trips.contains(&dragged.trip) as usize);
+        let to = row.vehicle;
+        if let Err(e) =
+            crate::actions::move_vehicle_trip(world, dragged.trip, dragged.from, to, index)
+        {
+            error!("Could not move trip: {e}");
+        }
+    }
+    if !response.dragged() {
+        tab.dragging = None;
+    }
+}
+
+fn draw_time_lines(painter: &Painter, navi: &VehiclesTabNavigation, visuals: &egui::Visuals) {
+    let rect = navi.visible_rect;
+    let stroke = Stroke::new(0.6, visuals.window_stroke().color);
+    let text_color = visuals.text_color();
+    // at least 60 screen units between hour lines
+    let step = [1, 2, 3, 6, 12]
+        .into_iter()
+        .find(|hours| (*hours * 3600) as f64 / navi.x_per_screen_unit_f64() >= 60.0)
+        .unwrap_or(24);
+    let range = navi.visible_x();
+    let first = (range.start / 3600.0).floor() as i64;
+    let last = (range.end / 3600.0).ceil() as i64;
+    for hour in (first..=last).filter(|it| it.rem_euclid(step) == 0) {
+        let x = navi.logical_x_to_screen_x(hour as f64 * 3600.0);
+        painter.vline(x, rect.y_range(), stroke);
+        painter.text(
+            pos2(x + 2.0, rect.top()),
+            Align2::LEFT_TOP,
+            format!("{:02}:00", hour.rem_euclid(24)),
+            FontId::proportional(12.0),
+            text_color,
+        );
+    }
+}
+
+fn draw_rows(
+    painter: &Painter,
+    tab: &VehiclesTab,
+    target_row: Option<usize>,
+    visuals: &egui::Visuals,
+) {
+    let navi = &tab.navi;
+    let rect = navi.visible_rect;
+    let stroke = Stroke::new(0.6, visuals.window_stroke().color);
+    let bars_painter = painter.with_clip_rect(Rect::from_min_max(
+        pos2(rect.left() + NAME_WIDTH, rect.top() + HEADER_HEIGHT),
+        rect.max,
+    ));
+    for (idx, row) in tab.rows.iter().enumerate() {
+        let top = navi.logical_y_to_screen_y(idx as f64 * ROW_HEIGHT as f64);
+        if top + ROW_HEIGHT < rect.top() || top > rect.bottom() {
+            continue;
+        }
+        if target_row == Some(idx) {
+            bars_painter.rect_filled(
+                Rect::from_min_size(pos2(rect.left(), top), Vec2::new(rect.width(), ROW_HEIGHT)),
+                0.0,
+                visuals.selection.bg_fill.gamma_multiply(0.3),
+            );
+        }
+        bars_painter.hline(rect.x_range(), top + ROW_HEIGHT, stroke);
+        for bar in &row.bars {
+            let dragged = tab
+                .dragging
+                .is_some_and(|it| it.from == row.vehicle && it.trip == bar.trip);
+            let bar_rect = bar_rect(navi, idx, bar);
+            let color = if dragged {
+                bar.color.gamma_multiply(0.3)
+            } else {
+                bar.color
+            };
+            bars_painter.rect_filled(bar_rect, 2.0, color);
+            if bar_rect.width() > 30.0 {
+                bars_painter.with_clip_rect(bar_rect).text(
+                    bar_rect.left_center() + Vec2::new(3.0, 0.0),
+                    Align2::LEFT_CENTER,
+                    &bar.name,
+                    FontId::proportional(12.0),
+                    visuals.strong_text_color(),
+                );
+            }
+        }
+    }
+    // the dragged trip follows the pointer across rows
+    if let Some(bar) = tab.dragging.and_then(|it| it.bar(&tab.rows))
+        && let Some(target) = target_row
+    {
+        bars_painter.rect_stroke(
+            bar_rect(navi, target, bar),
+            2.0,
+            Stroke::new(2.0, bar.color),
+            egui::StrokeKind::Inside,
+        );
+    }
+
+    let names_rect = Rect::from_min_max(
+        pos2(rect.left(), rect.top() + HEADER_HEIGHT),
+        pos2(rect.left() + NAME_WIDTH, rect.bottom()),
+    );
+    let names_painter = painter.with_clip_rect(names_rect);
+    names_painter.rect_filled(names_rect, 0.0, visuals.panel_fill);
+    names_painter.vline(names_rect.right(), names_rect.y_range(), stroke);
+    for (idx, row) in tab.rows.iter().enumerate() {
+        let top = navi.logical_y_to_screen_y(idx as f64 * ROW_HEIGHT as f64);
+        names_painter.text(
+            pos2(rect.left() + 6.0, top + ROW_HEIGHT / 2.0),
+            Align2::LEFT_CENTER,
+            &row.name,
+            FontId::proportional(13.0),
+            visuals.text_color(),
+        );
+    }
+}
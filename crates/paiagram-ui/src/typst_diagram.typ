@@ -7,16 +7,24 @@
 // If you changed the exported filename, you also need to change the name here,
 // otherwise the document won't compile.
 #let data = json("exported_diagram_data.json")
+// Set the height of the graph. Station positions are in the unit of the route's
+// lengths, which depends on where the route came from, so they are scaled to fit
+// this height.
+#let height = 400pt
+// The position of the last station, which is placed at the bottom of the graph.
+#let max-y = data.stations.at(-1, default: (none, 0)).at(1)
 // Set the base unit. The x or the horizontal component controls the horizontal
-// scale. The larger the x value is, the wider the graph goes, and vise versa,
+// scale, and is the width of one minute. The larger the x value is, the wider
+// the graph goes, and vise versa,
 #let base = (
-  x: 10pt,
-  // the same applies for the y component. The larger the y component is, the
-  // taller the graph goes.
-  y: 3pt,
+  x: 1pt,
+  // the y component is the height of one unit of the route's lengths, derived
+  // from the height above.
+  y: if max-y > 0 { height / max-y } else { 0pt },
 )
 // helper function and data that helps computation
-#let rows = data.stations.slice(1).map(((_, it)) => it * base.y)
+// Each row spans the distance between two adjacent stations
+#let rows = data.stations.slice(1).zip(data.stations).map((((_, b), (_, a))) => (b - a) * base.y)
 #let pts(d) = {
   (d.x * base.x, d.y * base.y)
 }
@@ -46,9 +54,8 @@
         ))
         .flatten()
       place(
-        dy: -rows.first(),
         curve(
-          stroke: color.rgb(..trip.color),
+          stroke: (paint: color.rgb(..trip.color), thickness: trip.width * 1pt),
           curve.move(pts(a)),
           {
             let dx = c.x - b.x
@@ -69,6 +76,6 @@
   grid(
     stroke: 1pt,
     rows: rows,
-    columns: (50 * base.x,) * 24,
+    columns: (60 * base.x,) * 24,
   )
 })
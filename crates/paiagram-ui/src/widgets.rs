@@ -1,5 +1,6 @@
 use egui::DragValue;
 use egui::emath::Numeric;
+use paiagram_core::trip::calendar::ServiceDate;
 use paiagram_core::units::time::{Duration, TimetableTime};
 
 pub mod buttons;
@@ -76,3 +77,34 @@ impl<'a> egui::Widget for DurationDragValue<'a> {
         )
     }
 }
+
+/// Optional service day filter. When disabled, trips on all days are shown.
+pub struct ServiceDayFilter<'a>(pub &'a mut Option<ServiceDate>);
+
+impl<'a> egui::Widget for ServiceDayFilter<'a> {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        ui.horizontal(|ui| {
+            let mut enabled = self.0.is_some();
+            let mut res = ui.checkbox(&mut enabled, "Service day");
+            if res.changed() {
+                *self.0 = enabled
+                    .then(|| ServiceDate::from_naive_date(chrono::Local::now().date_naive()));
+            }
+            if let Some(date) = self.0 {
+                res |= ui.add(
+                    DragValue::from_get_set(|v| {
+                        if let Some(v) = v {
+                            *date = ServiceDate::from_f64(v);
+                        }
+                        date.to_f64()
+                    })
+                    .speed(0.1)
+                    .custom_formatter(|v, _| ServiceDate::from_f64(v).to_string())
+                    .custom_parser(|s| s.parse().ok().map(ServiceDate::to_f64)),
+                );
+            }
+            res
+        })
+        .inner
+    }
+}
@@ -23,12 +23,19 @@
     chapter-path("export/paia"),
     chapter-path("export/oudia"),
     chapter-path("export/typst-diagram"),
+    chapter-path("export/svg"),
+    chapter-path("export/pdf"),
+    chapter-path("export/gtfs"),
     chapter-path("model/network"),
     chapter-path("model/trips-vehicles"),
     chapter-path("panels/index"),
     chapter-path("panels/diagram"),
     chapter-path("panels/map"),
+    chapter-path("panels/vehicles"),
+    chapter-path("panels/journey-planner"),
     chapter-path("misc/web"),
+    chapter-path("misc/cli"),
+    chapter-path("misc/plugins"),
     lib.chapter("changelog", content: [
       #title[Changelog]
       #cmarker.render(label-prefix: "changelog-", read("../CHANGELOG.md"))
@@ -0,0 +1,27 @@
+#title[Exporting as GTFS] <gtfs-export>
+
+The trips of a project can be exported as a static #link("https://gtfs.org/")[GTFS] feed, to be used in journey
+planners and other tools that read GTFS. Click "Export GTFS..." in the File menu, or convert the project from the
+command line (see @cli). Scenarios are alternatives to each other, so a feed holds the trips of one scenario: if the
+project has scenarios, pick one under "Export GTFS" in the File menu, or with `--scenario` on the command line.
+Choosing "All trips" exports the trips of every scenario together, and a warning is logged. The feed is saved as a zip
+file holding the following files:
+
+- `agency.txt`: a single agency named after the authors of the project, with the agency URL set in the project
+  settings.
+- `stops.txt`: every station and platform. Stations with platforms are written as parent stations, and their platforms
+  as stops in them. Platforms without a position of their own use the position of their station.
+- `routes.txt`: one route for each class, in the colour of the class.
+- `trips.txt`: every exported trip, with the name of its last stop as the headsign. Trips assigned to vehicles are put in the
+  block of their first vehicle.
+- `stop_times.txt`: the estimated arrival and departure times of each stop. Stations that trips only pass are left out.
+- `calendar.txt` and `calendar_dates.txt`: the service days of the trips. Trips sharing the same calendar share a
+  service.
+- `frequencies.txt`: the runs of frequency-based trips, with exact times.
+
+GTFS requires a time zone for the agency. Set it in the project settings as an IANA time zone, such as
+`Europe/Berlin` or `Asia/Tokyo`. `Etc/UTC` is written if none is set.
+
+Times after midnight are written as hours past 24:00, as GTFS expects. Trips starting before midnight keep their times
+of day, and are written to the service of the previous day: their calendars are moved back by one day. Entries
+without an estimated time are left out, and a warning is logged.
@@ -2,6 +2,11 @@
 
 You can export the current diagram to `.oud` in case if you need to further edit the diagram in OuDia or OuDiaSecond.
 
+= Scenarios
+
+Each scenario with trips on the exported route is exported as a separate diagram (ダイヤ), named after the scenario.
+Trips that do not belong to any scenario are exported into an extra diagram named "Paiagram Exported".
+
 = File encoding
 
 By default, `.oud` files would use #link("https://en.wikipedia.org/wiki/Shift_JIS")[Shift-JIS]. This is fine if you are
@@ -0,0 +1,21 @@
+#title[Exporting as PDF] <pdf>
+
+A route can be exported as a PDF file for printing. Open the export section of the properties of a diagram tab, and
+click "Export" under "Export to PDF". Only trips running on the service day and belonging to the scenario selected in
+the diagram tab are exported, or all trips if none is selected. The file contains:
+
+- The time–distance diagram of the route, drawn with the colours and line widths of the trips' classes. Each page
+  shows six hours of the day, and each line is labelled with the trip's name. As in the diagram tab, trips are drawn
+  again every repeat cycle set in the project settings.
+- The timetables of the route, first for trips running down the route and then for trips running up. Each station has
+  an arrival row, a departure row, or both, as set in the route timetable tab. Tables that do not fit on a page are
+  continued on the next pages, first by stations and then by trips.
+
+The timetables use the same marks as the route timetable tab: `‥` where a trip does not run, `▔` after the last stop,
+`║` where a trip passes a station without an entry, `○` where a trip stops without a fixed arrival time, and `⇂`
+where no time is shown.
+
+Everything is drawn as vector graphics and the fonts are embedded, so the file prints sharply at any size. Paiagram
+embeds the font it uses for Chinese and Japanese text when it is installed, or once the web version has downloaded it,
+and a Latin font otherwise. PDF files can also be made from the command line, with the fonts of your choice. See @cli
+for details.
@@ -0,0 +1,18 @@
+#title[Exporting as SVG] <svg>
+
+Diagrams can be saved as SVG images, to be edited in #link("https://inkscape.org/")[Inkscape] or other vector graphics
+editors before they go into reports. Open the export section of the properties of a diagram tab, and click one of the
+buttons under "Export to SVG":
+
+- "Current view" saves the times shown in the tab, at the same scale as on screen.
+- "Full day" saves the whole day from 0:00 to 24:00, 100 points wide for each hour.
+
+Only the trips shown in the tab are saved, so the service day and scenario selected in the tab apply to the image as
+well. The image has three layers:
+
+- "Time grid": a line every hour with its time, and a line every ten minutes if there is room for them.
+- "Stations": a line and the name of each station of the route.
+- "Trips": the lines of the trips in the colours and line widths of their classes, with the trip's name at the start
+  of each line. Each trip is a group named after the trip, so it can be picked from the objects panel.
+
+Colours are the ones used in light mode. The lines of the trips are cut at the ends of the saved times.
@@ -1,3 +1,28 @@
 #title[Exporting as Typst Diagram]
 
-_This part is planned._
+Diagrams can be drawn with #link("https://typst.app/")[Typst], for print-quality diagrams in your own documents. Open
+the export section of the properties of a diagram tab, then:
+
++ Click "Export" under "Save Typst module" to save the Typst module, `typst_diagram.typ`.
++ Click "Export" under "Export diagram as JSON" to save the route's data, `exported_diagram_data.json`.
++ Put both files in the same folder and compile the module with Typst.
+
+The module reads the data from `exported_diagram_data.json`. If you saved the data under another name, change the name
+in the module as well. The scale of the diagram is set in the module: `base.x` is the width of one minute, and `height`
+is the height of the whole diagram. Station positions are in the unit of the route's lengths, which depends on how the
+route was created: metres for routes measured on the map, kilometres for routes imported from GTFS, and twice the
+running time in minutes for routes imported from OuDia. The module scales the positions so the last station is at the
+bottom of the diagram.
+
+The data contains the trips of the route running on the service day and in the scenario selected in the diagram tab,
+or all trips if none is selected. Frequency-based trips are exported with all of their runs. It is a JSON object with
+two fields:
+
+- `stations`: the stations of the route from top to bottom. Each station is a pair of its name and its position on the
+  route.
+- `trips`: the trips running on the route. Each trip has a `name`, its class's `color` as an array of red, green, blue,
+  and alpha between 0 and 255, its class's line `width` in points, and its `points`.
+
+The `points` of a trip are a list of segments, each drawn as a separate line. A trip is split into segments where it
+leaves the route or its times are unknown. Each stop of a segment has four points, two at the arrival and two at the
+departure. Each point has an `x` in minutes after midnight and a `y`, the position on the route.
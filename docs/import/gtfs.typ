@@ -3,6 +3,22 @@
 The #link("https://gtfs.org/")[General Transit Feed Specification] is a standard for transit companies to share their
 data. Paiagram provides builtin support for importing static GTFS files.
 
+Trips listed in `frequencies.txt` are imported as frequency-based trips. Each of them is kept as a single trip, and its
+runs are expanded wherever they are displayed.
+
+Stops are imported as stations. Stops that belong to a parent station in `stops.txt` become platforms of that station,
+including platforms that no trip stops at. Stations without a position of their own are placed at their first platform.
+
+If a trip has a shape in `shapes.txt`, the intervals of its route follow the shape on the map instead of a straight line.
+The shape is cut by `shape_dist_traveled` where both the stop and the shape record it, and otherwise where the shape first passes within 150 m of each stop, so loops are kept.
+An interval shared by several routes keeps the first shape found.
+
+Transfers in `transfers.txt` are imported as follows, and transfers that are not possible are left out:
+
+- Transfers within a station set the station's minimum transfer time, to the longest of them.
+- Transfers between stations become footpaths. The walking time is the minimum transfer time of the transfer, or is
+  calculated from the distance between the stops and the walking speed in the project settings.
+
 = Importing in the App
 
 = Importing with Command Line Arguments
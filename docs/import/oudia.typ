@@ -27,3 +27,6 @@ Paiagram would assume the file is UTF-8 encoded.
 For both OuDia and OuDiaSecond formats, Paiagram would try to merge stations based on thet *station name* when
 importing. For OuDiaSecond formats, Paiagram would also look at the station's connectivity info, including the loop-line
 station and branched line settings.
+
+Each diagram (ダイヤ) in the file, e.g. weekday and holiday diagrams, is imported as a separate *scenario*. You can switch
+between scenarios with the "Scenario" selector in the diagram tab's properties panel.
@@ -0,0 +1,57 @@
+#title[Command Line] <cli>
+
+The desktop version of Paiagram can also run without opening a window. This is useful for build pipelines and batch
+processing, and works on machines without a display.
+
+= Converting Files
+
+The `convert` command opens a file and saves it in another format:
+
+```sh
+paiagram convert timetable.oud2 timetable.paia
+paiagram convert network.zip network.ron
+paiagram convert timetable.paia line.oud --route "Main Line"
+paiagram convert timetable.paia line.pdf --route "Main Line" --font NotoSansKR-Regular.ttf
+paiagram convert timetable.paia feed.zip
+```
+
+Any file that can be opened in Paiagram can be converted. The format of the output is guessed from its extension, or
+can be set with `--to`:
+
+#table(
+  columns: 3,
+  [*`--to`*], [*Extension*], [*Format*],
+  [`paia`], [`.paia`], [Paiagram project],
+  [`ron`], [`.ron`], [Paiagram project in RON],
+  [`oud`], [`.oud`], [OuDia],
+  [`pdf`], [`.pdf`], [PDF, see @pdf],
+  [`gtfs`], [`.zip`], [GTFS feed, see @gtfs-export],
+)
+
+OuDia and PDF files hold a single route. Pick the route with `--route`, followed by the route's name. The option can be
+left out if there is only one route. GTFS feeds hold the trips of one scenario, picked with `--scenario` followed by the
+scenario's name, or all trips if the option is left out. Fonts to embed in PDF files are given with `--font`, which can
+be repeated, and are tried before the font the user interface uses for Chinese and Japanese text, if it is installed,
+and the bundled Latin font. The command exits with a non-zero status if the file cannot be opened or converted.
+
+= Checking Files
+
+The `check` command opens a file, computes the routes and estimated times of all trips, and reports what is wrong with
+it:
+
+```sh
+paiagram check timetable.paia
+paiagram check timetable.paia --output report.json
+```
+
+The report is written as JSON to the standard output, or to the file given with `--output`. It lists:
+
+- `unroutable`: consecutive stops of a trip that are not connected in the network, hence no intermediate stops can be
+  derived between them.
+- `missing_estimates`: trips with stops that have no estimated times, usually because the times around them are not
+  set.
+- `graph`: stations and intervals that are missing from the network, or that are in the network but no longer exist.
+- `problems`: the same timetable conflicts as in the Problems tab.
+
+The command exits with status 0 if nothing is found, 2 if the file has issues, and 1 if the file cannot be opened. This
+makes it suitable for checking timetables in continuous integration.
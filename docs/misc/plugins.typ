@@ -0,0 +1,77 @@
+#title[Plugins] <plugins>
+
+Plugins are WebAssembly modules that read the stations and trips of the current project and add new ones. They work in
+both the desktop and the web version. Open the Plugins tab from the tab list, click "Load plugin..." to pick a `.wasm`
+file, and click "Run" to run it. Plugins are named after their files, and run in the background while Paiagram stays
+responsive. Everything a plugin logs, and whether its changes were applied, is shown in its log. Plugins are not saved
+with the project, and have to be loaded again after restarting Paiagram.
+
+A plugin can only change the project through commands. The commands are applied after the plugin finishes, and only if
+all of them are valid. If a station or trip the commands add entries to is deleted while the plugin runs, none of the
+commands are applied. A plugin that runs for too long or uses more than 256 MiB of memory is stopped, and none of its
+commands are applied. Commands and log messages are limited to 1 MiB each.
+
+= Writing Plugins
+
+A plugin exports its memory as `memory`, and a function `run` taking no arguments. Data is exchanged as
+#link("https://cbor.io/")[CBOR]. While `run` is called, the plugin can call these functions imported from the
+`paiagram` module:
+
+#table(
+  columns: 2,
+  [*Function*], [*Description*],
+  [`snapshot_len() -> i32`], [The length of the snapshot in bytes.],
+  [`read_snapshot(ptr: i32)`], [Copies the snapshot into the memory at `ptr`.],
+  [`push_command(ptr: i32, len: i32) -> i32`],
+  [Queues the command at `ptr`. Returns 0, or -1 if the command cannot be read.],
+
+  [`log(ptr: i32, len: i32)`], [Adds the UTF-8 text at `ptr` to the plugin's log.],
+)
+
+The snapshot is a map with a list of `stations` and a list of `trips`. Each station has an `id` and a `name`. Each trip
+has an `id`, a `name`, the name of its `class`, and a list of `entries`. Each entry has a `station_id`, the estimated
+`arrival` and `departure` in seconds after midnight, which are null if they are unknown, and whether the entry was
+`derived` from the route rather than entered. IDs are the positions in the lists.
+
+Commands are maps with a single key naming the command:
+
+#table(
+  columns: 2,
+  [*Command*], [*Fields*],
+  [`CreateStation`], [`name`, and the `id` used to refer to the new station.],
+  [`CreateTrip`],
+  [`name`, `class`, and the `id` used to refer to the new trip. The class is created if no class has the name.],
+
+  [`CreateEntry`],
+  [`trip_id`, `station_id`, and the optional `arrival` and `departure` in seconds after midnight. The entry is added
+    to the end of the trip.],
+)
+
+IDs of new stations and trips must not be taken by other stations or trips. In Rust, a plugin could look like this:
+
+```rust
+#[link(wasm_import_module = "paiagram")]
+unsafe extern "C" {
+    fn snapshot_len() -> i32;
+    fn read_snapshot(ptr: *mut u8);
+    fn push_command(ptr: *const u8, len: i32) -> i32;
+}
+
+#[derive(serde::Serialize)]
+enum Command {
+    CreateStation { name: String, id: u32 },
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn run() {
+    let mut snapshot = vec![0u8; unsafe { snapshot_len() } as usize];
+    unsafe { read_snapshot(snapshot.as_mut_ptr()) };
+    // read the snapshot with a CBOR library...
+    let mut command = Vec::new();
+    let name = "New Station".to_string();
+    ciborium::into_writer(&Command::CreateStation { name, id: 10_000 }, &mut command).unwrap();
+    unsafe { push_command(command.as_ptr(), command.len() as i32) };
+}
+```
+
+Build it for the `wasm32-unknown-unknown` target as a `cdylib`.
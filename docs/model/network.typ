@@ -10,3 +10,26 @@ export the graph to a #link("https://graphviz.org/")[Graphviz] `.dot` file for f
 
 Due to technical limitations and for simplicity, you cannot add multiple intervals with the same direction between two
 stations. If you want to work with 2+ line intervals, the best way to achieve that is by using waypoints.
+
+= Single-Track Lines
+
+Each interval records its number of tracks, which defaults to 2. Intervals with a single track can only be used by one
+direction at a time. A chain of single-track intervals forms a section, and opposing trips may only meet at the stations
+that end the section. Stations have unlimited capacity by default, and you can limit the number of trips a station can
+hold at the same time. Stations with a capacity of 1, such as halts without a passing loop, do not end sections.
+
+You can edit the number of tracks and the station capacity by selecting a station in the #link(<map>)[Map panel].
+Opposing trips occupying the same section at the same time, and stations holding more trips than their capacity, are
+reported as problems.
+
+= Transfers and Footpaths
+
+Route finding lets passengers change between trips at a station after the minimum transfer time, which is set in the
+project settings and can be overridden for each station in the #link(<map>)[Map panel]. Passengers do not need the
+transfer time when changing between the trips of a guaranteed connection.
+
+Passengers may also walk between stations linked by footpaths. To link two stations, select both of them in the
+#link(<map>)[Map panel] and click "Link with a footpath". The walking time is estimated from the distance between the
+stations, and can be changed by selecting either station. Footpaths can be walked in both directions. If a walking
+distance is set in the project settings, stations closer to each other than the walking distance are linked as well,
+with the walking time calculated from the walking speed.
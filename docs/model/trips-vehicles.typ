@@ -39,6 +39,21 @@ Travel modes are designed to simplify work. For example, in cases where you only
 specific intervals. You can easily convert between travel modes without losing work. You can also make it calculate the
 intermediate time for you by setting travel modes to "flexible" or "non-stop".
 
+== Running Times
+
+Flexible entries share the time between the surrounding stable entries. By default, the time is split by the length of
+each interval. If the trip's vehicle or class has rolling stock performance data (maximum speed, acceleration, braking,
+mass, and power), Paiagram calculates the minimum running time of each interval instead, taking the speed limits and
+gradients of intervals into account, and splits the time by those running times. Trains stop at entries with a dwell
+time, and pass other entries without slowing down.
+
+Flexible entries after the last stable entry are timed by the minimum running times. Trips without performance data have
+no estimates for those entries. Running durations you enter are never changed, and runs between two stops that are timed
+faster than the minimum running time are listed in the Problems tab.
+
+You can edit the performance data in the Classes tab, and the speed limit and gradient of intervals by selecting a
+station in the #link(<map>)[Map panel].
+
 = Timetabled, Fixed, and Derived Entries
 
 Sometimes, the trip may only have some timetabled entries, and is only guaranteed to visit some stations. For example,
@@ -71,7 +86,76 @@ entries. In contrast, you can only delete timetabled and fixed entries.
 
 You can edit the trip's path by inserting extra fixed (i.e. Flexible arrival mode, Flexible departure mode) entries.
 
+= Clock-Face Services
+
+Instead of creating every trip of a regular interval service by hand, you can create one trip and generate copies of it.
+Open the trip, and click "Generate copies of this trip" in the Edit tab of the right panel. The copies are shifted by
+multiples of the headway, and only copies departing from their first stop between the first and last departure are
+generated. By default, the copies cover one repeat cycle as set in the project settings.
+
+The copies are named after the name pattern, where `{name}` is replaced by the name of the template trip, and `{n}` by
+the number of the copy. Timetabled times are shifted, while durations and flexible times are kept. The copies belong to
+the same class, scenario, and operating calendar as the template trip.
+
+If "Regenerate when this trip changes" is checked, the copies are replaced whenever you edit the template trip. Copies
+you edit by hand are kept when the copies are generated again, and no new copy is generated in their place. Click
+"Discard edits" in the Edit tab of an edited copy to replace it with a generated one again.
+
+= Frequency-Based Trips
+
+A frequency-based trip runs repeatedly at a fixed headway within one or more time windows, without being copied. The
+trip's own times act as a template: each run has the same running and dwell times, but departs from the first stop at
+the run's departure time. Frequency-based trips are imported from GTFS `frequencies.txt`.
+
+Runs are shown in diagrams and station tabs, and are considered by route finding. Problem checks and vehicle scheduling
+only consider the template trip.
+
+= Guaranteed Connections
+
+A guaranteed connection records that one trip waits for another trip at a station, so that passengers have at least the
+transfer time to change from the feeder trip to the connecting trip. Open the feeder trip, and add the connection in
+the Edit tab of the right panel by choosing the station, the connecting trip, and the transfer time.
+
+If the connecting trip departs before the feeder arrives plus the transfer time, the connection is listed as a missed
+connection in the Problems tab. Connections are drawn as arcs in diagrams, in green if they hold and in red if they are
+missed. Route finding always lets passengers change between the two trips, even when the connection is missed.
+
+= Service Days
+
+A trip may have an operating calendar, which determines the days the trip runs on. A calendar consists of:
+
+- a set of weekdays,
+- a list of date ranges in which the weekdays apply, and
+- exception dates, on which the trip is explicitly added or removed.
+
+Trips without a calendar run on every day. Imported GTFS trips get their calendars from `calendar.txt` and
+`calendar_dates.txt`.
+
+The diagram, station, and route timetable tabs each have a "Service day" filter. When enabled, only trips running on the
+selected day are shown, and route finding only considers those trips.
+
 = Vehicles
 
 A vehicle is the "executor" of trips. Each vehicle runs a set of trips. Trips could be shared by multiple vehicles (as
-in coupling and decoupling trains).
+in coupling and decoupling trains). You can view and edit the trips of each vehicle in the
+#link(<vehicles>)[Vehicles panel].
+
+== Vehicle Scheduling
+
+Paiagram can assign trips to vehicles automatically, using as few vehicles as possible. Click "Schedule Vehicles" in the
+settings tab to do so. A vehicle can run a trip after another if the first trip ends at the station where the second one
+starts, and the vehicle has enough time to turn around. The minimum turnaround time is set in the project settings, and
+can be overridden for each station by selecting the station in the #link(<map>)[Map panel]. If a deadhead speed is set,
+vehicles may also run empty to another station, taking the shortest path in the network.
+
+Trips are only assigned to the same vehicle if they belong to the same scenario and have the same operating calendar.
+Vehicles created by the scheduler are replaced when scheduling again. Trips of other vehicles, such as vehicles imported
+from GTFS blocks, are left untouched.
+
+== Circulation Checks
+
+Paiagram checks that each vehicle can run its trips in the listed order. A trip must start at the station where the
+previous trip ends, unless the vehicle can run empty there with the deadhead speed. The vehicle also needs at least the
+minimum turnaround time, plus the time of the empty run, between the two trips, and a trip must not start before the
+previous one ends. Violations are listed with other problems in the Problems tab. Trips that never run on the same day
+are not compared.
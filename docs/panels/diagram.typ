@@ -21,3 +21,22 @@ You can zoom out to see a wider range of trips, or zoom in to see details of ind
 You can click on the coloured lines to edit the time of different trips. You can highlight trips by clicking on the
 associated lines, and editing the trip's timetable by dragging the handles attached to each entry, or by clicking the
 handles to open a popup, then editing the options provided in the popup.
+
+= Problems
+
+Paiagram checks the timetable for trips that follow each other closer than the minimum headway, trips that overtake
+each other between two stations, trips that meet head-on on single-track lines, and stations that hold more trips than
+their capacity, as well as vehicles that cannot run their trips in order and missed guaranteed connections. Trips
+that belong to different scenarios, or that never run on the same day, are not compared. The minimum headway can be
+changed in the project settings.
+
+The affected parts of trips are highlighted in red. You can turn the highlighting off in the Properties tab, and list
+all problems in the Problems tab. Guaranteed connections are drawn as arcs between the two trips, and can be hidden
+with "Show connections".
+
+= Drawing without a GPU
+
+Trip lines are normally drawn on the GPU. If there is no suitable graphics adapter, for example in a virtual machine or
+on a thin client, Paiagram draws the diagram and the map on the CPU instead. The lines look the same, and still follow
+the anti-aliasing and level of detail settings, but drawing many trips may be slower. If wgpu cannot start at all, the
+desktop version falls back to OpenGL.
@@ -0,0 +1,14 @@
+#title[Journey Planner] <journey-planner>
+
+The journey planner finds journeys between two stations. Open it from the tab list, or click "Plan a journey..." in the
+properties of a diagram tab to plan with the diagram's service day.
+
+Pick the origin and the destination, the window of departure times, and the maximum number of transfers, then click
+"Find". The planner lists every journey leaving the origin within the window that is worth taking: no other journey
+departs later, arrives earlier, and needs fewer transfers. A slower journey is still listed if it needs fewer
+transfers, and a journey departing earlier is only listed if it arrives earlier than every later one.
+
+Expand a journey to see its legs. Each leg shows where and when it starts and ends, and the trip ridden, or "Walk" for
+footpaths between stations. Click on a trip to open it.
+
+Journeys respect the minimum transfer times and footpaths between stations, as well as guaranteed connections.
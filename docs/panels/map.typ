@@ -27,3 +27,31 @@ coordinates. OpenStreetMap, ESRI World Imagery, and Chiri-in Chizu use #link(
 )[WGS84] coordinates
 
 *The usage of map underlay services are subject to the corresponding service providers' terms and conditions.*
+
+= Isochrones
+
+Isochrones show how long it takes to reach every station from one station, and help comparing the accessibility of a
+region before and after timetable changes. Set them up in the "Properties" tab of the right panel: pick the origin,
+the window of departure times, the maximum number of transfers, and the service day, then click "Compute".
+
+Stations are coloured by the shortest travel time of journeys departing within the window, from green at the origin to
+red for the station furthest away, and the travel time is shown next to the station name. Stations that cannot be
+reached are faded out.
+
+Click "Export isochrone to CSV" to save the results. Each row of the file lists a station with its earliest arrival and
+the shortest travel time in seconds. Stations that cannot be reached have empty times.
+
+= Travel Time Matrix
+
+The travel time matrix lists the travel times between every pair of stations selected on the map. Select the stations,
+open the "Export" tab of the right panel, and set the window of departure times, the step between departures, the
+maximum number of transfers, and the service day. Click "Compute" to compute the matrix in the background. Paiagram
+stays responsive while the matrix is computed, and shows the progress in the panel.
+
+For each pair of stations, passengers leave the origin at every step within the window, and take the journey arriving
+the earliest. Travel times include waiting for the first trip. The matrix lists the shortest, median, and longest travel
+times, and the number of transfers of the fastest journey. Click "Export" to save the matrix as CSV, with one row for
+each pair of stations and times in seconds. Pairs that cannot be reached have empty times. Departures that cannot reach
+the destination count as infinitely long, so the median is empty if at least half of the departures cannot reach it,
+and the longest time is empty if any of them cannot. The last two columns count the departures that reach the
+destination and all departures within the window.
@@ -0,0 +1,10 @@
+#title[Vehicles] <vehicles>
+
+The vehicles tab shows the roster of all vehicles. Each row is a vehicle, and each bar in the row is a trip that the
+vehicle runs, from the arrival at its first stop to the departure from its last stop. Bars are colored by the trip's
+class.
+
+Drag a bar onto another row to move the trip to that vehicle. The trip is placed among the vehicle's trips in the order
+of departure. You can undo and redo moves with the buttons in the top panel. Click on a bar to open the trip.
+
+Click "New Vehicle" to add an empty vehicle, or "Schedule Vehicles" to assign trips to vehicles automatically.